@@ -0,0 +1,210 @@
+use crate::models::{Status, Ticket, WipLimits};
+
+/// Width used when the size of the terminal cannot be determined
+/// (e.g. when the output is piped to another program).
+pub const DEFAULT_WIDTH: usize = 80;
+/// Columns never get narrower than this, even on very small terminals.
+const MIN_COLUMN_WIDTH: usize = 12;
+/// Long titles are wrapped over at most this many lines, then truncated.
+const MAX_TITLE_LINES: usize = 3;
+const SEPARATOR: &str = " | ";
+
+/// Width of the terminal we are writing to, falling back to [DEFAULT_WIDTH](DEFAULT_WIDTH).
+pub fn terminal_width() -> usize {
+    terminal_size::terminal_size()
+        .map(|(terminal_size::Width(w), _)| w as usize)
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+/// Render the tickets as a board, with one column for each of the given statuses
+/// (from left to right) sharing the available `width`.
+///
+/// Each column shows how many tickets it holds, followed by the id and title
+/// of each of them, ordered by id.
+/// Columns holding more tickets than their work-in-progress limit are flagged.
+pub fn render(
+    tickets: &[&Ticket],
+    statuses: &[Status],
+    limits: &WipLimits,
+    width: usize,
+) -> String {
+    if statuses.is_empty() {
+        return String::new();
+    }
+    let separators = SEPARATOR.len() * (statuses.len() - 1);
+    let column_width = (width.saturating_sub(separators) / statuses.len()).max(MIN_COLUMN_WIDTH);
+
+    let columns: Vec<Vec<String>> = statuses
+        .iter()
+        .map(|status| {
+            let mut column_tickets: Vec<&&Ticket> =
+                tickets.iter().filter(|t| t.status == *status).collect();
+            column_tickets.sort_by_key(|t| t.id);
+            render_column(*status, &column_tickets, limits.get(*status), column_width)
+        })
+        .collect();
+
+    let height = columns.iter().map(|c| c.len()).max().unwrap_or(0);
+    (0..height)
+        .map(|i| {
+            columns
+                .iter()
+                .map(|column| {
+                    let cell = column.get(i).map(String::as_str).unwrap_or("");
+                    pad(cell, column_width)
+                })
+                .collect::<Vec<String>>()
+                .join(SEPARATOR)
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// The lines making up a single column of the board.
+fn render_column(
+    status: Status,
+    tickets: &[&&Ticket],
+    limit: Option<usize>,
+    width: usize,
+) -> Vec<String> {
+    let mut lines = vec![
+        column_header(status, tickets.len(), limit, width),
+        "-".repeat(width),
+    ];
+    for (i, ticket) in tickets.iter().enumerate() {
+        if i > 0 {
+            lines.push(String::new());
+        }
+        let card = format!("#{} {}", ticket.id, ticket.title);
+        lines.extend(wrap(&card, width, MAX_TITLE_LINES));
+    }
+    lines
+}
+
+/// The title of a column: the name of its status, how many tickets it holds
+/// and its work-in-progress limit, if any.
+/// The name of the status is shortened, rather than the count, to fit in `width`.
+pub fn column_header(status: Status, count: usize, limit: Option<usize>, width: usize) -> String {
+    let count = match limit {
+        Some(limit) if count > limit => format!(" ({}/{}) !", count, limit),
+        Some(limit) => format!(" ({}/{})", count, limit),
+        None => format!(" ({})", count),
+    };
+    let name = truncate(&format!("{:?}", status), width.saturating_sub(count.len()));
+    format!("{}{}", name, count)
+}
+
+/// Split `text` into lines of at most `width` characters, breaking on whitespace
+/// where possible.
+/// If more than `max_lines` lines are needed, the last one is truncated with an ellipsis.
+fn wrap(text: &str, width: usize, max_lines: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        // Words that would not fit on a line of their own are split.
+        while word.len() > width {
+            if !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+            }
+            lines.push(word.drain(..width).collect());
+        }
+        let word: String = word.into_iter().collect();
+        if current.is_empty() {
+            current = word;
+        } else if current.chars().count() + 1 + word.chars().count() <= width {
+            current.push(' ');
+            current.push_str(&word);
+        } else {
+            lines.push(std::mem::replace(&mut current, word));
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+
+    if lines.len() > max_lines {
+        lines.truncate(max_lines);
+        let last = lines.pop().unwrap_or_default();
+        let last: String = last.chars().take(width.saturating_sub(1)).collect();
+        lines.push(format!("{}…", last));
+    }
+    lines
+}
+
+/// Cut `text` to at most `width` characters.
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+/// Pad `text` with spaces on the right to exactly `width` characters.
+fn pad(text: &str, width: usize) -> String {
+    let padding = width.saturating_sub(text.chars().count());
+    format!("{}{}", text, " ".repeat(padding))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::{column_header, render, wrap};
+    use crate::models::{Status, Ticket, WipLimits};
+
+    #[test]
+    fn wrapping_breaks_on_whitespace() {
+        assert_eq!(
+            wrap("fix the login page", 8, 3),
+            vec!["fix the", "login", "page"]
+        );
+    }
+
+    #[test]
+    fn wrapping_splits_words_longer_than_a_line() {
+        assert_eq!(wrap("abcdefghij", 4, 5), vec!["abcd", "efgh", "ij"]);
+    }
+
+    #[test]
+    fn wrapping_truncates_after_the_maximum_number_of_lines() {
+        assert_eq!(wrap("one two three four", 5, 2), vec!["one", "two…"]);
+    }
+
+    #[test]
+    fn board_has_a_column_per_status_with_counts() {
+        // arrange
+        let tickets = [
+            Ticket::sample(2, "Fix login", Status::ToDo),
+            Ticket::sample(1, "Write docs", Status::ToDo),
+            Ticket::sample(3, "Ship it", Status::Done),
+        ];
+        let tickets: Vec<&Ticket> = tickets.iter().collect();
+
+        // act
+        let board = render(
+            &tickets,
+            &[Status::ToDo, Status::Done],
+            &WipLimits::default(),
+            31,
+        );
+
+        // assert
+        let expected = "\
+ToDo (2)       | Done (1)
+-------------- | --------------
+#1 Write docs  | #3 Ship it
+               |
+#2 Fix login   |";
+        assert_eq!(board, expected);
+    }
+
+    #[test]
+    fn columns_over_their_limit_are_flagged() {
+        assert_eq!(
+            column_header(Status::InProgress, 6, Some(5), 30),
+            "InProgress (6/5) !"
+        );
+        assert_eq!(
+            column_header(Status::InProgress, 5, Some(5), 30),
+            "InProgress (5/5)"
+        );
+    }
+}
@@ -0,0 +1,352 @@
+use crate::error::JiraError;
+use crate::models::Status;
+use crate::persistence::project_dirs;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::read_to_string;
+use std::path::PathBuf;
+
+const CONFIG_FILE: &str = "config.yaml";
+
+/// Settings of the CLI, read from `config.yaml` in the configuration directory of IronJira.
+///
+/// Every setting is optional: a missing file, or a missing entry, gets the default value.
+#[derive(PartialEq, Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Where tickets are persisted.
+    pub backend: BackendKind,
+    /// Directory holding the ticket store. Defaults to the data directory of IronJira.
+    pub store: Option<PathBuf>,
+    /// Address of a server started with `jira-cli serve`, e.g. `http://jira.local:8080`,
+    /// whose store is used instead of a local one.
+    pub remote: Option<String>,
+    /// API token sent to the [remote](Config::remote) server, as created by
+    /// `jira-cli token create` on it.
+    pub token: Option<String>,
+    /// Named stores, selected with `--profile`.
+    pub profiles: BTreeMap<String, Profile>,
+    /// Settings of the journal backend.
+    pub journal: JournalConfig,
+    /// Settings of the backups taken before saving.
+    pub backups: BackupConfig,
+    /// Settings of the import of Jira exports.
+    pub jira: JiraConfig,
+    /// Settings of the linking of git commits to tickets.
+    pub git: GitConfig,
+    /// URLs notified of ticket events.
+    pub webhooks: WebhooksConfig,
+    /// Settings of the API tokens of the users of `jira-cli serve`.
+    pub tokens: TokensConfig,
+}
+
+/// A named store, e.g. `work` or `personal`, overriding the top-level settings when selected.
+#[derive(PartialEq, Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    /// Directory holding the ticket store. A relative path is resolved from the current directory,
+    /// which makes it possible to keep a store inside a repository.
+    pub store: Option<PathBuf>,
+    /// Where tickets are persisted, if different from the top-level backend.
+    pub backend: Option<BackendKind>,
+    /// Address of the server whose store is used, if different from the top-level one.
+    pub remote: Option<String>,
+    /// API token sent to the server of the profile.
+    pub token: Option<String>,
+}
+
+impl Config {
+    /// Apply the settings of the profile called `name` on top of the top-level ones.
+    pub fn with_profile(mut self, name: &str) -> Result<Config, JiraError> {
+        let profile = self.profiles.get(name).cloned().ok_or_else(|| {
+            let available: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            JiraError::NotFound(format!(
+                "There is no profile named {} in the configuration file. Available profiles: {}.",
+                name,
+                if available.is_empty() {
+                    "none".to_string()
+                } else {
+                    available.join(", ")
+                }
+            ))
+        })?;
+        if profile.store.is_some() || profile.backend.is_some() {
+            // A profile pointing to a local store does not use the top-level server.
+            self.remote = None;
+            self.token = None;
+        }
+        if profile.store.is_some() {
+            self.store = profile.store;
+        }
+        if let Some(backend) = profile.backend {
+            self.backend = backend;
+        }
+        if profile.remote.is_some() || profile.token.is_some() {
+            // The token of the top-level server is never sent to the one of the profile.
+            self.token = profile.token;
+        }
+        if profile.remote.is_some() {
+            self.remote = profile.remote;
+        }
+        Ok(self)
+    }
+}
+
+/// The available [StorageBackend](crate::persistence::StorageBackend)s.
+#[derive(PartialEq, Debug, Copy, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    /// A single YAML file in the data directory.
+    #[default]
+    Yaml,
+    /// An SQLite database in the data directory, updated one ticket at a time.
+    Sqlite,
+    /// A snapshot plus an append-only journal of changes in the data directory.
+    Journal,
+    /// Nothing is saved to disk, mostly useful for testing.
+    Memory,
+}
+
+/// Settings of the [JournalBackend](crate::persistence::JournalBackend).
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JournalConfig {
+    /// Number of journal entries after which the journal is folded into a new snapshot.
+    pub compact_after: usize,
+}
+
+impl Default for JournalConfig {
+    fn default() -> Self {
+        Self { compact_after: 100 }
+    }
+}
+
+/// Settings of the [Backups](crate::persistence::Backups) of file-based backends.
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupConfig {
+    /// Number of backups to keep. 0 disables backups.
+    pub retention: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self { retention: 10 }
+    }
+}
+
+/// Settings of the import of Jira exports.
+#[derive(PartialEq, Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JiraConfig {
+    /// The [Status] each Jira status maps to, on top of the statuses of the default Jira workflows.
+    pub status_mapping: BTreeMap<String, Status>,
+}
+
+/// Settings of `git sync`, which links commits to the tickets they reference.
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GitConfig {
+    /// Prefix of ticket references in commit messages, e.g. `IRON` for `IRON-12`.
+    /// References like `#12` are always recognised.
+    pub prefix: String,
+}
+
+impl Default for GitConfig {
+    fn default() -> Self {
+        Self {
+            prefix: "IRON".to_string(),
+        }
+    }
+}
+
+/// Settings of the webhooks, which receive an HTTP POST with a JSON payload for every
+/// ticket event, e.g. `ticket.created`, `ticket.moved` or `ticket.commented`.
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhooksConfig {
+    /// Where events are sent.
+    pub endpoints: Vec<WebhookEndpoint>,
+    /// Number of attempts after which a delivery is given up.
+    pub max_attempts: u32,
+    /// Seconds to wait before retrying a failed delivery, doubled after every attempt up to
+    /// 30 days.
+    pub retry_delay: u64,
+    /// Seconds to wait for a receiver to answer.
+    pub timeout: u64,
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        Self {
+            endpoints: Vec::new(),
+            max_attempts: 8,
+            retry_delay: 30,
+            timeout: 10,
+        }
+    }
+}
+
+/// A URL notified of ticket events.
+#[derive(PartialEq, Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookEndpoint {
+    pub url: String,
+    /// Key of the HMAC-SHA256 signature of the payloads. Payloads are not signed without it.
+    pub secret: Option<String>,
+    /// Names of the events sent to this URL, e.g. `ticket.moved`. Every event if empty.
+    pub events: Vec<String>,
+}
+
+/// Settings of the API tokens authenticating the users of `jira-cli serve`.
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TokensConfig {
+    /// Days after which a new token expires. 0 makes tokens that never expire.
+    pub lifetime: u32,
+}
+
+impl Default for TokensConfig {
+    fn default() -> Self {
+        Self { lifetime: 90 }
+    }
+}
+
+/// Read the configuration file, if available.
+pub fn load() -> Result<Config, JiraError> {
+    let filename = project_dirs()?.config_dir().join(CONFIG_FILE);
+    match read_to_string(&filename) {
+        Ok(data) => serde_yaml::from_str(&data).map_err(|e| {
+            JiraError::parse(
+                &format!("Failed to parse the configuration file {:?}", filename),
+                e,
+            )
+        }),
+        Err(e) => match e.kind() {
+            // No configuration file - stick to the defaults.
+            std::io::ErrorKind::NotFound => Ok(Config::default()),
+            _ => Err(JiraError::io(
+                &format!("Failed to read the configuration file {:?}", filename),
+                e,
+            )),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{BackendKind, Config};
+    use std::path::PathBuf;
+
+    #[test]
+    fn missing_entries_get_default_values() {
+        let config: Config = serde_yaml::from_str("{}").unwrap();
+
+        assert_eq!(config.backend, BackendKind::Yaml);
+        assert_eq!(config.journal.compact_after, 100);
+        assert_eq!(config.backups.retention, 10);
+        assert_eq!(config.tokens.lifetime, 90);
+    }
+
+    #[test]
+    fn webhooks_can_be_configured() {
+        let config: Config = serde_yaml::from_str(
+            "webhooks:\n  endpoints:\n    - url: http://ci.local/hook\n      secret: s3cr3t\n",
+        )
+        .unwrap();
+
+        assert_eq!(config.webhooks.endpoints[0].url, "http://ci.local/hook");
+        assert_eq!(
+            config.webhooks.endpoints[0].secret,
+            Some("s3cr3t".to_string())
+        );
+        assert!(config.webhooks.endpoints[0].events.is_empty());
+        assert_eq!(config.webhooks.max_attempts, 8);
+    }
+
+    #[test]
+    fn the_backend_is_read_in_lowercase() {
+        let config: Config = serde_yaml::from_str("backend: memory").unwrap();
+
+        assert_eq!(config.backend, BackendKind::Memory);
+    }
+
+    #[test]
+    fn the_journal_can_be_configured() {
+        let config: Config =
+            serde_yaml::from_str("backend: journal\njournal:\n  compact_after: 10").unwrap();
+
+        assert_eq!(config.backend, BackendKind::Journal);
+        assert_eq!(config.journal.compact_after, 10);
+    }
+
+    #[test]
+    fn a_profile_overrides_the_top_level_settings() {
+        // arrange
+        let config: Config = serde_yaml::from_str(
+            "
+backend: sqlite
+store: /home/me/tickets
+profiles:
+  work:
+    store: .jira
+    backend: yaml
+  personal:
+    store: /home/me/personal
+",
+        )
+        .unwrap();
+
+        // act
+        let work = config.clone().with_profile("work").unwrap();
+        let personal = config.clone().with_profile("personal").unwrap();
+
+        // assert
+        assert_eq!(work.store, Some(PathBuf::from(".jira")));
+        assert_eq!(work.backend, BackendKind::Yaml);
+        assert_eq!(personal.store, Some(PathBuf::from("/home/me/personal")));
+        assert_eq!(personal.backend, BackendKind::Sqlite);
+    }
+
+    #[test]
+    fn the_token_of_the_top_level_server_stays_with_it() {
+        // arrange
+        let config: Config = serde_yaml::from_str(
+            "
+remote: http://jira.local:8080
+token: ij_1a2b3c4d_secret
+profiles:
+  staging:
+    remote: http://staging.local:8080
+  local:
+    store: .jira
+",
+        )
+        .unwrap();
+
+        // act
+        let staging = config.clone().with_profile("staging").unwrap();
+        let local = config.clone().with_profile("local").unwrap();
+
+        // assert
+        assert_eq!(
+            staging.remote,
+            Some("http://staging.local:8080".to_string())
+        );
+        assert_eq!(staging.token, None);
+        assert_eq!(local.remote, None);
+        assert_eq!(local.token, None);
+    }
+
+    #[test]
+    fn an_unknown_profile_is_an_error() {
+        let config: Config = serde_yaml::from_str("profiles:\n  work: {}").unwrap();
+
+        let error = config.with_profile("home").unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "There is no profile named home in the configuration file. Available profiles: work."
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use crate::models::{CommentError, TitleError, WipLimitError};
+use crate::persistence::schema::SchemaError;
+use crate::persistence::StoreLockError;
+use crate::store::StatusUpdateError;
+use std::error::Error;
+use std::fmt;
+
+/// Everything that can make a command fail.
+///
+/// Each kind of error exits the process with its own [code](JiraError::exit_code),
+/// so that scripts can tell them apart. Exit code 1 is left to invalid command-line arguments.
+#[derive(PartialEq, Debug, Clone)]
+pub enum JiraError {
+    /// The input is not valid, e.g. an empty title or a work-in-progress limit of 0.
+    Validation(String),
+    /// The ticket, view, profile or backup does not exist.
+    NotFound(String),
+    /// The store, or the terminal, could not be read or written.
+    Io(String),
+    /// The store or the configuration file could not be understood.
+    Parse(String),
+    /// The command clashes with the state of the store, e.g. a work-in-progress limit
+    /// or another process using the store.
+    Conflict(String),
+    /// The server refused the request: its API token is missing, expired or revoked.
+    Unauthorized(String),
+}
+
+impl JiraError {
+    /// The code the process exits with when the command fails with this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            JiraError::Validation(_) => 2,
+            JiraError::NotFound(_) => 3,
+            JiraError::Io(_) => 4,
+            JiraError::Parse(_) => 5,
+            JiraError::Conflict(_) => 6,
+            JiraError::Unauthorized(_) => 7,
+        }
+    }
+
+    /// An [Io](JiraError::Io) error, explaining what we were doing when it happened.
+    pub fn io(context: &str, error: impl fmt::Display) -> JiraError {
+        JiraError::Io(format!("{}: {}", context, error))
+    }
+
+    /// A [Parse](JiraError::Parse) error, explaining what we were reading when it happened.
+    pub fn parse(context: &str, error: impl fmt::Display) -> JiraError {
+        JiraError::Parse(format!("{}: {}", context, error))
+    }
+
+    /// A [NotFound](JiraError::NotFound) error for a missing ticket.
+    pub fn ticket_not_found(id: u64) -> JiraError {
+        JiraError::NotFound(format!(
+            "There was no ticket associated to the ticket id {:?}",
+            id
+        ))
+    }
+}
+
+impl fmt::Display for JiraError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JiraError::Validation(details)
+            | JiraError::NotFound(details)
+            | JiraError::Io(details)
+            | JiraError::Parse(details)
+            | JiraError::Conflict(details)
+            | JiraError::Unauthorized(details) => write!(f, "{}", details),
+        }
+    }
+}
+
+impl Error for JiraError {}
+
+impl From<TitleError> for JiraError {
+    fn from(e: TitleError) -> Self {
+        JiraError::Validation(e.to_string())
+    }
+}
+
+impl From<CommentError> for JiraError {
+    fn from(e: CommentError) -> Self {
+        JiraError::Validation(e.to_string())
+    }
+}
+
+impl From<WipLimitError> for JiraError {
+    fn from(e: WipLimitError) -> Self {
+        JiraError::Validation(e.to_string())
+    }
+}
+
+impl From<StatusUpdateError> for JiraError {
+    fn from(e: StatusUpdateError) -> Self {
+        match e {
+            StatusUpdateError::TicketNotFound(_) => JiraError::NotFound(e.to_string()),
+            StatusUpdateError::WipLimitExceeded { .. } => JiraError::Conflict(e.to_string()),
+        }
+    }
+}
+
+impl From<StoreLockError> for JiraError {
+    fn from(e: StoreLockError) -> Self {
+        if e.is_contended() {
+            JiraError::Conflict(e.to_string())
+        } else {
+            JiraError::Io(e.to_string())
+        }
+    }
+}
+
+impl From<SchemaError> for JiraError {
+    fn from(e: SchemaError) -> Self {
+        JiraError::Parse(e.to_string())
+    }
+}
+
+impl From<rusqlite::Error> for JiraError {
+    fn from(e: rusqlite::Error) -> Self {
+        JiraError::io("Failed to access the ticket database", e)
+    }
+}
+
+impl From<std::io::Error> for JiraError {
+    fn from(e: std::io::Error) -> Self {
+        JiraError::Io(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::JiraError;
+    use crate::models::Status;
+    use crate::store::StatusUpdateError;
+    use std::collections::HashSet;
+
+    #[test]
+    fn every_kind_of_error_has_its_own_exit_code() {
+        let errors = [
+            JiraError::Validation(String::new()),
+            JiraError::NotFound(String::new()),
+            JiraError::Io(String::new()),
+            JiraError::Parse(String::new()),
+            JiraError::Conflict(String::new()),
+            JiraError::Unauthorized(String::new()),
+        ];
+
+        let codes: HashSet<i32> = errors.iter().map(JiraError::exit_code).collect();
+
+        assert_eq!(codes.len(), errors.len());
+        // 0 means success and 1 is used for invalid command-line arguments.
+        assert!(!codes.contains(&0) && !codes.contains(&1));
+    }
+
+    #[test]
+    fn a_wip_limit_is_a_conflict_and_a_missing_ticket_is_not_found() {
+        let limit = StatusUpdateError::WipLimitExceeded {
+            status: Status::InProgress,
+            limit: 2,
+        };
+
+        let missing = StatusUpdateError::TicketNotFound(1);
+
+        assert!(matches!(JiraError::from(limit), JiraError::Conflict(_)));
+        assert_eq!(JiraError::from(missing), JiraError::ticket_not_found(1));
+    }
+}
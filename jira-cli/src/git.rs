@@ -0,0 +1,416 @@
+use crate::error::JiraError;
+use crate::models::{LinkedCommit, Status, TicketId};
+use crate::store::TicketStore;
+use chrono::{DateTime, Utc};
+use std::path::Path;
+use std::process::Command;
+
+/// Words closing the tickets referenced right after them, e.g. `closes #12` or `Fixes IRON-12`.
+const CLOSING_KEYWORDS: [&str; 9] = [
+    "close", "closes", "closed", "fix", "fixes", "fixed", "resolve", "resolves", "resolved",
+];
+
+/// Separator of the fields, and of the commits, in the output of `git log -z`: the only
+/// character git does not accept in a commit message.
+const SEPARATOR: char = '\0';
+
+/// Number of fields of a commit in the output of `git log`, as formatted by [read_log].
+const COMMIT_FIELDS: usize = 4;
+
+/// A commit read from the history of a git repository.
+#[derive(PartialEq, Debug, Clone)]
+pub struct GitCommit {
+    pub hash: String,
+    pub author: String,
+    pub date: DateTime<Utc>,
+    /// The full commit message, subject included.
+    pub message: String,
+}
+
+impl GitCommit {
+    /// What is recorded on the tickets the commit references.
+    fn to_linked_commit(&self) -> LinkedCommit {
+        LinkedCommit {
+            hash: self.hash.clone(),
+            author: self.author.clone(),
+            date: self.date,
+            subject: self.message.lines().next().unwrap_or_default().to_string(),
+        }
+    }
+}
+
+/// A ticket mentioned in a commit message.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Reference {
+    pub id: TicketId,
+    /// The ticket follows a [closing keyword](CLOSING_KEYWORDS), e.g. `closes #12`.
+    pub closes: bool,
+}
+
+/// The outcome of [sync](sync).
+#[derive(PartialEq, Debug, Default)]
+pub struct SyncReport {
+    /// Number of commits linked to a ticket, not counting the ones linked by a previous sync.
+    pub linked: usize,
+    /// Tickets moved to [Done](Status::Done) by a closing keyword.
+    pub closed: Vec<TicketId>,
+    /// Tickets that could not be moved, and why.
+    pub warnings: Vec<String>,
+}
+
+/// Read the history of the current branch of a local repository, oldest commit first.
+/// The history of a repository without commits yet is empty.
+pub fn read_log(repository: &Path) -> Result<Vec<GitCommit>, JiraError> {
+    if git(repository, &["rev-parse", "--verify", "--quiet", "HEAD"]).is_err() {
+        // Still fail if this is not a repository at all.
+        git(repository, &["rev-parse", "--git-dir"])?;
+        return Ok(Vec::new());
+    }
+    let log = git(
+        repository,
+        &[
+            "log",
+            "-z",
+            "--reverse",
+            "--no-color",
+            "--format=%H%x00%an%x00%aI%x00%B",
+        ],
+    )?;
+    parse_log(&log)
+}
+
+/// The name of the branch checked out in a local repository, or `None` if the repository
+/// is not on a branch, e.g. in the middle of a rebase.
+pub fn current_branch(repository: &Path) -> Result<Option<String>, JiraError> {
+    let branch = git(repository, &["rev-parse", "--abbrev-ref", "HEAD"])?;
+    Ok(Some(branch).filter(|branch| branch != "HEAD"))
+}
+
+/// Check out a branch of a local repository, creating it from the current commit if missing.
+/// Returns `true` if the branch was created.
+pub fn checkout_branch(repository: &Path, branch: &str) -> Result<bool, JiraError> {
+    let reference = format!("refs/heads/{}", branch);
+    let exists = git(
+        repository,
+        &["rev-parse", "--verify", "--quiet", &reference],
+    )
+    .is_ok();
+    if exists {
+        git(repository, &["checkout", "--quiet", branch])?;
+    } else {
+        git(repository, &["checkout", "--quiet", "-b", branch])?;
+    }
+    Ok(!exists)
+}
+
+/// The ticket a branch is about, if its name is like `12-fix-login`, `feature/IRON-12`
+/// or `iron-12-fix-login`, with `prefix` being `IRON`.
+pub fn ticket_from_branch(branch: &str, prefix: &str) -> Option<TicketId> {
+    let name = branch.rsplit('/').next().unwrap_or(branch);
+    let name = match name.get(..prefix.len() + 1) {
+        Some(start) if start.eq_ignore_ascii_case(&format!("{}-", prefix)) => {
+            &name[prefix.len() + 1..]
+        }
+        _ => name,
+    };
+    let id = name.split('-').next().unwrap_or_default();
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    id.parse().ok()
+}
+
+/// The name of the branch to work on a ticket, e.g. `12-fix-login`.
+pub fn branch_name(id: TicketId, title: &str) -> String {
+    let slug: Vec<String> = title
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .take(6)
+        .map(|word| word.to_lowercase())
+        .collect();
+    if slug.is_empty() {
+        id.to_string()
+    } else {
+        format!("{}-{}", id, slug.join("-"))
+    }
+}
+
+/// Run a git command in a local repository, returning what it printed.
+pub(crate) fn git(repository: &Path, args: &[&str]) -> Result<String, JiraError> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repository)
+        .args(args)
+        .output()
+        .map_err(|e| JiraError::io("Failed to run git", e))?;
+    if !output.status.success() {
+        return Err(JiraError::Io(format!(
+            "git {} failed in {:?}: {}",
+            args.join(" "),
+            repository,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Parse the output of `git log`, as formatted by [read_log](read_log).
+fn parse_log(log: &str) -> Result<Vec<GitCommit>, JiraError> {
+    // Every commit, the last one included, ends with a separator.
+    let log = log.strip_suffix(SEPARATOR).unwrap_or(log);
+    if log.is_empty() {
+        return Ok(Vec::new());
+    }
+    let fields: Vec<&str> = log.split(SEPARATOR).collect();
+    fields
+        .chunks(COMMIT_FIELDS)
+        .map(|commit| match commit {
+            [hash, author, date, message] => Ok(GitCommit {
+                hash: hash.to_string(),
+                author: author.to_string(),
+                date: DateTime::parse_from_rfc3339(date)
+                    .map_err(|e| JiraError::parse("Invalid commit date in the git history", e))?
+                    .with_timezone(&Utc),
+                message: message.trim_end().to_string(),
+            }),
+            _ => Err(JiraError::Parse(format!(
+                "Unexpected commit in the git history: {:?}",
+                commit.join("\n")
+            ))),
+        })
+        .collect()
+}
+
+/// The tickets referenced in a commit message, either as `<prefix>-12` (case-insensitive)
+/// or as `#12`. A reference preceded by a [closing keyword](CLOSING_KEYWORDS), or by another
+/// closing reference as in `fixes #12, #13`, closes its ticket.
+pub fn references(message: &str, prefix: &str) -> Vec<Reference> {
+    let mut references: Vec<Reference> = Vec::new();
+    let mut closing = false;
+    for word in message.split_whitespace() {
+        let word = word.trim_matches(|c: char| ",.;:!?()[]{}\"'`".contains(c));
+        match parse_reference(word, prefix) {
+            Some(id) => match references.iter_mut().find(|r| r.id == id) {
+                Some(reference) => reference.closes |= closing,
+                None => references.push(Reference {
+                    id,
+                    closes: closing,
+                }),
+            },
+            None => closing = CLOSING_KEYWORDS.contains(&word.to_lowercase().as_str()),
+        }
+    }
+    references
+}
+
+fn parse_reference(word: &str, prefix: &str) -> Option<TicketId> {
+    let id = match word.strip_prefix('#') {
+        Some(id) => id,
+        None => {
+            let (word_prefix, id) = word.split_at(word.find('-')?);
+            if !word_prefix.eq_ignore_ascii_case(prefix) {
+                return None;
+            }
+            &id[1..]
+        }
+    };
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    id.parse().ok()
+}
+
+/// Link every commit to the tickets it references, skipping the commits linked before.
+/// References to tickets that do not exist, e.g. pull request numbers, are ignored.
+///
+/// If `transition` is set, tickets closed by a newly linked commit are moved to
+/// [Done](Status::Done), within the work-in-progress limits.
+pub fn sync(
+    ticket_store: &mut TicketStore,
+    commits: &[GitCommit],
+    prefix: &str,
+    transition: bool,
+) -> SyncReport {
+    let mut report = SyncReport::default();
+    for commit in commits {
+        for reference in references(&commit.message, prefix) {
+            let id = reference.id;
+            if ticket_store.link_commit(id, commit.to_linked_commit()) != Some(true) {
+                continue;
+            }
+            report.linked += 1;
+            let done = ticket_store.get(id).map(|t| t.status) == Some(Status::Done);
+            if !transition || !reference.closes || done {
+                continue;
+            }
+            match ticket_store.update_ticket_status(id, Status::Done) {
+                Ok(()) => report.closed.push(id),
+                Err(e) => report.warnings.push(format!(
+                    "Commit {} closes ticket {}, which was not moved: {}",
+                    commit.hash.chars().take(7).collect::<String>(),
+                    id,
+                    e
+                )),
+            }
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git::{
+        branch_name, git, parse_log, read_log, references, sync, ticket_from_branch, GitCommit,
+        Reference,
+    };
+    use crate::models::{Status, TicketDraft, Title};
+    use crate::store::TicketStore;
+    use chrono::Utc;
+
+    fn commit(hash: &str, message: &str) -> GitCommit {
+        GitCommit {
+            hash: hash.to_string(),
+            author: "Jane Doe".to_string(),
+            date: Utc::now(),
+            message: message.to_string(),
+        }
+    }
+
+    fn ticket_store(tickets: usize) -> TicketStore {
+        let mut ticket_store = TicketStore::new();
+        for _ in 0..tickets {
+            ticket_store.create(TicketDraft {
+                title: Title::new("A ticket".to_string()).unwrap(),
+                description: String::new(),
+            });
+        }
+        ticket_store
+    }
+
+    #[test]
+    fn references_use_the_prefix_or_a_hash() {
+        let found = references("iron-12: fix login (see #3, ABC-4 and #x)", "IRON");
+
+        let ids: Vec<u64> = found.iter().map(|r| r.id).collect();
+        assert_eq!(ids, vec![12, 3]);
+        assert!(found.iter().all(|r| !r.closes));
+    }
+
+    #[test]
+    fn closing_keywords_apply_to_the_references_right_after_them() {
+        let found = references("Refactor #1\n\nFixes #2, IRON-3 and #4. Closes: #1", "IRON");
+
+        assert_eq!(
+            found,
+            vec![
+                Reference {
+                    id: 1,
+                    closes: true
+                },
+                Reference {
+                    id: 2,
+                    closes: true
+                },
+                Reference {
+                    id: 3,
+                    closes: true
+                },
+                Reference {
+                    id: 4,
+                    closes: false
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn the_output_of_git_log_is_parsed() {
+        let log = "abc123\u{0}Jane Doe\u{0}2020-05-01T10:00:00+02:00\u{0}\
+                   IRON-1: fix\n\nBody \u{1e}\u{1f}\n\u{0}\
+                   def456\u{0}John Doe\u{0}2020-05-02T10:00:00Z\u{0}Second\n\u{0}";
+
+        let commits = parse_log(log).unwrap();
+
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].hash, "abc123");
+        assert_eq!(commits[0].message, "IRON-1: fix\n\nBody \u{1e}\u{1f}");
+        assert_eq!(commits[0].date.to_rfc3339(), "2020-05-01T08:00:00+00:00");
+        assert_eq!(commits[1].author, "John Doe");
+    }
+
+    #[test]
+    fn a_repository_without_commits_has_an_empty_history() {
+        let dir = tempfile::tempdir().unwrap();
+        git(dir.path(), &["init", "--quiet"]).unwrap();
+
+        let commits = read_log(dir.path()).unwrap();
+
+        assert!(commits.is_empty());
+    }
+
+    #[test]
+    fn syncing_links_commits_once_and_closes_tickets_when_asked() {
+        // arrange
+        let mut ticket_store = ticket_store(2);
+        let commits = vec![
+            commit("aaaaaaa", "IRON-1: start login"),
+            commit("bbbbbbb", "Fix login, closes #1 and #99"),
+            commit("ccccccc", "Fixes #2"),
+        ];
+
+        // act
+        let first = sync(&mut ticket_store, &commits, "IRON", true);
+        let second = sync(&mut ticket_store, &commits, "IRON", true);
+
+        // assert
+        assert_eq!(first.linked, 3);
+        assert_eq!(first.closed, vec![1, 2]);
+        assert_eq!(second.linked, 0);
+        let ticket = ticket_store.get(1).unwrap();
+        assert_eq!(ticket.status, Status::Done);
+        let subjects: Vec<&str> = ticket.commits.iter().map(|c| c.subject.as_str()).collect();
+        assert_eq!(
+            subjects,
+            vec!["IRON-1: start login", "Fix login, closes #1 and #99"]
+        );
+    }
+
+    #[test]
+    fn tickets_are_only_closed_with_transition() {
+        let mut ticket_store = ticket_store(1);
+
+        let report = sync(
+            &mut ticket_store,
+            &[commit("a", "closes #1")],
+            "IRON",
+            false,
+        );
+
+        assert_eq!(report.linked, 1);
+        assert!(report.closed.is_empty());
+        assert_eq!(ticket_store.get(1).unwrap().status, Status::ToDo);
+    }
+
+    #[test]
+    fn the_ticket_is_read_from_the_branch_name() {
+        assert_eq!(ticket_from_branch("12-fix-login", "IRON"), Some(12));
+        assert_eq!(ticket_from_branch("feature/IRON-12", "IRON"), Some(12));
+        assert_eq!(
+            ticket_from_branch("bugfix/iron-7-fix-login", "IRON"),
+            Some(7)
+        );
+        assert_eq!(ticket_from_branch("12", "IRON"), Some(12));
+        assert_eq!(ticket_from_branch("main", "IRON"), None);
+        assert_eq!(ticket_from_branch("feature/ABC-12", "IRON"), None);
+        assert_eq!(ticket_from_branch("v2-release", "IRON"), None);
+    }
+
+    #[test]
+    fn branch_names_start_with_the_ticket_id() {
+        let branch = branch_name(12, "Fix the login page (again!)");
+
+        assert_eq!(branch, "12-fix-the-login-page-again");
+        assert_eq!(ticket_from_branch(&branch, "IRON"), Some(12));
+        assert_eq!(branch_name(3, "???"), "3");
+    }
+}
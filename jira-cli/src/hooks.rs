@@ -0,0 +1,152 @@
+use crate::error::JiraError;
+use crate::git::{git, references};
+use crate::models::Status;
+use crate::store::TicketStore;
+use std::path::{Path, PathBuf};
+
+/// Marks the hooks written by [install](install), which can be replaced without `--force`.
+const MARKER: &str = "# Installed by `jira-cli hooks install`.";
+
+/// Git cuts the commit message at this line when committing with `--verbose`.
+const SCISSORS: &str = "# ------------------------ >8 ------------------------";
+
+/// Write a `commit-msg` hook into a local repository, running `command` followed by
+/// `hooks check-msg` on the message of every commit.
+///
+/// A hook that was not installed by jira-cli is only replaced if `force` is set.
+/// Returns the path of the hook.
+pub fn install(repository: &Path, command: &[String], force: bool) -> Result<PathBuf, JiraError> {
+    let path = hooks_dir(repository)?.join("commit-msg");
+    if let Ok(existing) = std::fs::read_to_string(&path) {
+        if !existing.contains(MARKER) && !force {
+            return Err(JiraError::Conflict(format!(
+                "There is already a commit-msg hook in {:?}. Use --force to replace it.",
+                path
+            )));
+        }
+    }
+    let command: Vec<String> = command.iter().map(|arg| quote(arg)).collect();
+    let script = format!(
+        "#!/bin/sh\n{}\n# Rejects commits that do not reference an open ticket.\nexec {} hooks check-msg \"$1\"\n",
+        MARKER,
+        command.join(" ")
+    );
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| JiraError::io(&format!("Failed to create {:?}", dir), e))?;
+    }
+    std::fs::write(&path, script)
+        .map_err(|e| JiraError::io(&format!("Failed to write {:?}", path), e))?;
+    make_executable(&path)?;
+    Ok(path)
+}
+
+/// Check that a commit message references at least one existing ticket that is not
+/// [Done](Status::Done). Merge commits are accepted as they are.
+///
+/// Lines starting with `#` are ignored, like git does when it cleans up the message.
+pub fn check_message(
+    ticket_store: &TicketStore,
+    message: &str,
+    prefix: &str,
+) -> Result<(), JiraError> {
+    let message: Vec<&str> = message
+        .lines()
+        .take_while(|line| *line != SCISSORS)
+        .filter(|line| !line.starts_with('#'))
+        .collect();
+    let message = message.join("\n");
+    if message.starts_with("Merge ") {
+        return Ok(());
+    }
+
+    let mut problems = Vec::new();
+    for reference in references(&message, prefix) {
+        match ticket_store.get(reference.id) {
+            Some(ticket) if ticket.status != Status::Done => return Ok(()),
+            Some(_) => problems.push(format!("ticket {} is done", reference.id)),
+            None => problems.push(format!("ticket {} does not exist", reference.id)),
+        }
+    }
+    if problems.is_empty() {
+        Err(JiraError::Validation(format!(
+            "The commit message does not reference any ticket. Mention an open ticket, e.g. {}-12 or #12.",
+            prefix
+        )))
+    } else {
+        Err(JiraError::Validation(format!(
+            "The commit message does not reference an open ticket: {}.",
+            problems.join(", ")
+        )))
+    }
+}
+
+/// The directory git runs the hooks of the repository from, `core.hooksPath` included.
+fn hooks_dir(repository: &Path) -> Result<PathBuf, JiraError> {
+    let hooks = git(repository, &["rev-parse", "--git-path", "hooks"])?;
+    // The path is relative to the repository, unless `core.hooksPath` is absolute.
+    Ok(repository.join(hooks))
+}
+
+/// Quote an argument for `sh`.
+fn quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<(), JiraError> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))
+        .map_err(|e| JiraError::io(&format!("Failed to make {:?} executable", path), e))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<(), JiraError> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hooks::{check_message, quote};
+    use crate::models::{Status, TicketDraft, Title};
+    use crate::store::TicketStore;
+
+    fn ticket_store() -> TicketStore {
+        let mut ticket_store = TicketStore::new();
+        for _ in 0..2 {
+            ticket_store.create(TicketDraft {
+                title: Title::new("A ticket".to_string()).unwrap(),
+                description: String::new(),
+            });
+        }
+        ticket_store.force_ticket_status(2, Status::Done);
+        ticket_store
+    }
+
+    #[test]
+    fn messages_must_reference_an_open_ticket() {
+        let ticket_store = ticket_store();
+
+        assert!(check_message(&ticket_store, "IRON-1: fix login", "IRON").is_ok());
+        assert!(check_message(&ticket_store, "Fix login (#2, #1)", "IRON").is_ok());
+        assert!(check_message(&ticket_store, "Fix login", "IRON").is_err());
+        assert!(check_message(&ticket_store, "IRON-2: fix login", "IRON").is_err());
+        assert!(check_message(&ticket_store, "IRON-3: fix login", "IRON").is_err());
+        assert!(check_message(&ticket_store, "Merge branch 'main'", "IRON").is_ok());
+    }
+
+    #[test]
+    fn comment_lines_are_ignored() {
+        let ticket_store = ticket_store();
+        let message = "Fix login\n# Please mention IRON-1\n";
+
+        let result = check_message(&ticket_store, message, "IRON");
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn arguments_are_quoted_for_the_shell() {
+        assert_eq!(quote("/home/me/it's here"), r"'/home/me/it'\''s here'");
+    }
+}
@@ -0,0 +1,433 @@
+use crate::board;
+use crate::error::JiraError;
+use crate::models::{Comment, Status, Ticket, TicketId, TicketPatch, Title};
+use crate::persistence::StorageBackend;
+use crate::store::TicketStore;
+use crossterm::cursor::Show;
+use crossterm::event::{self, Event, KeyCode, KeyEvent};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use std::io::Stdout;
+use tui::backend::{Backend, CrosstermBackend};
+use tui::layout::{Constraint, Direction, Layout};
+use tui::style::{Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap};
+use tui::{Frame, Terminal};
+
+const HELP: &str = "←→ column  ↑↓ ticket  </> move  t title  e description  c comment  q quit";
+
+/// Run the full-screen interface until the user quits.
+///
+/// Every change is applied through the [TicketStore](TicketStore) methods
+/// and saved with the storage backend straight away.
+pub fn run(
+    ticket_store: &mut TicketStore,
+    backend: &mut dyn StorageBackend,
+) -> Result<(), JiraError> {
+    let _guard = TerminalGuard::enter()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
+    event_loop(&mut terminal, ticket_store, backend)
+}
+
+/// Puts the terminal in raw mode on the alternate screen for as long as it lives.
+///
+/// The terminal is given back to the user when the guard is dropped, even if something went
+/// wrong or panicked along the way.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> Result<Self, JiraError> {
+        enable_raw_mode()?;
+        // From here on, dropping the guard restores the terminal.
+        let guard = TerminalGuard;
+        execute!(std::io::stdout(), EnterAlternateScreen)?;
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        // Nothing more can be done if the terminal cannot be restored.
+        let _ = disable_raw_mode();
+        let _ = execute!(std::io::stdout(), LeaveAlternateScreen, Show);
+    }
+}
+
+fn event_loop(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    ticket_store: &mut TicketStore,
+    backend: &mut dyn StorageBackend,
+) -> Result<(), JiraError> {
+    let mut app = App::new();
+    loop {
+        terminal.draw(|f| draw(f, &app, ticket_store))?;
+        if let Event::Key(key) = event::read()? {
+            match app.handle_key(key, ticket_store) {
+                Outcome::Quit => return Ok(()),
+                Outcome::Changed(id) => backend.save_ticket(ticket_store, id)?,
+                Outcome::Unchanged => {}
+            }
+        }
+    }
+}
+
+/// What happened to the store after handling a key press.
+#[derive(PartialEq, Debug)]
+enum Outcome {
+    Unchanged,
+    /// The ticket with the given id was updated.
+    Changed(TicketId),
+    Quit,
+}
+
+/// The field of a ticket being edited, or a comment being written.
+#[derive(PartialEq, Debug, Copy, Clone)]
+enum Field {
+    Title,
+    Description,
+    Comment,
+}
+
+#[derive(PartialEq, Debug)]
+enum Mode {
+    Browsing,
+    Editing { field: Field, input: String },
+}
+
+/// State of the interface: which ticket is selected and what the user is doing.
+struct App {
+    /// Index of the selected column in [Status::all](Status::all).
+    column: usize,
+    /// Index of the selected ticket in its column.
+    row: usize,
+    mode: Mode,
+    /// Feedback for the user, shown in the status line until the next key press.
+    message: Option<String>,
+}
+
+impl App {
+    fn new() -> Self {
+        Self {
+            column: 0,
+            row: 0,
+            mode: Mode::Browsing,
+            message: None,
+        }
+    }
+
+    fn status(&self) -> Status {
+        Status::all()[self.column]
+    }
+
+    fn selected<'a>(&self, ticket_store: &'a TicketStore) -> Option<&'a Ticket> {
+        column_tickets(ticket_store, self.status())
+            .get(self.row)
+            .copied()
+    }
+
+    fn handle_key(&mut self, key: KeyEvent, ticket_store: &mut TicketStore) -> Outcome {
+        self.message = None;
+        match std::mem::replace(&mut self.mode, Mode::Browsing) {
+            Mode::Browsing => self.browse(key.code, ticket_store),
+            Mode::Editing { field, mut input } => match key.code {
+                KeyCode::Enter => self.commit(field, input, ticket_store),
+                KeyCode::Esc => Outcome::Unchanged,
+                KeyCode::Backspace => {
+                    input.pop();
+                    self.mode = Mode::Editing { field, input };
+                    Outcome::Unchanged
+                }
+                KeyCode::Char(c) => {
+                    input.push(c);
+                    self.mode = Mode::Editing { field, input };
+                    Outcome::Unchanged
+                }
+                _ => {
+                    self.mode = Mode::Editing { field, input };
+                    Outcome::Unchanged
+                }
+            },
+        }
+    }
+
+    fn browse(&mut self, key: KeyCode, ticket_store: &mut TicketStore) -> Outcome {
+        let n_columns = Status::all().len();
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => return Outcome::Quit,
+            KeyCode::Left | KeyCode::Char('h') => {
+                self.column = (self.column + n_columns - 1) % n_columns;
+                self.row = 0;
+            }
+            KeyCode::Right | KeyCode::Char('l') => {
+                self.column = (self.column + 1) % n_columns;
+                self.row = 0;
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.row = self.row.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                let n_tickets = column_tickets(ticket_store, self.status()).len();
+                if self.row + 1 < n_tickets {
+                    self.row += 1;
+                }
+            }
+            KeyCode::Char('<') if self.column > 0 => return self.move_selected(-1, ticket_store),
+            KeyCode::Char('>') if self.column + 1 < n_columns => {
+                return self.move_selected(1, ticket_store)
+            }
+            KeyCode::Char('t') | KeyCode::Char('e') | KeyCode::Char('c') => {
+                if let Some(ticket) = self.selected(ticket_store) {
+                    let (field, input) = match key {
+                        KeyCode::Char('t') => (Field::Title, ticket.title.to_string()),
+                        KeyCode::Char('e') => (Field::Description, ticket.description.clone()),
+                        _ => (Field::Comment, String::new()),
+                    };
+                    self.mode = Mode::Editing { field, input };
+                }
+            }
+            _ => {}
+        }
+        Outcome::Unchanged
+    }
+
+    /// Move the selected ticket to the neighbouring column, keeping it selected.
+    fn move_selected(&mut self, offset: isize, ticket_store: &mut TicketStore) -> Outcome {
+        let id = match self.selected(ticket_store) {
+            Some(ticket) => ticket.id,
+            None => return Outcome::Unchanged,
+        };
+        let column = (self.column as isize + offset) as usize;
+        match ticket_store.update_ticket_status(id, Status::all()[column]) {
+            Ok(()) => {
+                self.column = column;
+                self.select(id, ticket_store);
+                Outcome::Changed(id)
+            }
+            Err(e) => {
+                self.message = Some(e.to_string());
+                Outcome::Unchanged
+            }
+        }
+    }
+
+    /// Apply the text the user typed to the selected ticket.
+    fn commit(&mut self, field: Field, input: String, ticket_store: &mut TicketStore) -> Outcome {
+        let id = match self.selected(ticket_store) {
+            Some(ticket) => ticket.id,
+            None => return Outcome::Unchanged,
+        };
+        let result = match field {
+            Field::Title => Title::new(input)
+                .map(|title| {
+                    let patch = TicketPatch {
+                        title: Some(title),
+                        description: None,
+                    };
+                    ticket_store.update_ticket(id, patch);
+                })
+                .map_err(|e| e.to_string()),
+            Field::Description => {
+                let patch = TicketPatch {
+                    title: None,
+                    description: Some(input),
+                };
+                ticket_store.update_ticket(id, patch);
+                Ok(())
+            }
+            Field::Comment => Comment::new(input)
+                .map(|comment| {
+                    ticket_store.add_comment_to_ticket(id, comment);
+                })
+                .map_err(|e| e.to_string()),
+        };
+        match result {
+            Ok(()) => Outcome::Changed(id),
+            Err(e) => {
+                self.message = Some(e);
+                Outcome::Unchanged
+            }
+        }
+    }
+
+    fn select(&mut self, id: TicketId, ticket_store: &TicketStore) {
+        self.row = column_tickets(ticket_store, self.status())
+            .iter()
+            .position(|t| t.id == id)
+            .unwrap_or(0);
+    }
+}
+
+/// The tickets in a given status, ordered by id.
+fn column_tickets(ticket_store: &TicketStore, status: Status) -> Vec<&Ticket> {
+    let mut tickets: Vec<&Ticket> = ticket_store
+        .list()
+        .into_iter()
+        .filter(|t| t.status == status)
+        .collect();
+    tickets.sort_by_key(|t| t.id);
+    tickets
+}
+
+fn draw<B: Backend>(f: &mut Frame<B>, app: &App, ticket_store: &TicketStore) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(5), Constraint::Length(3)])
+        .split(f.size());
+    let panes = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(65), Constraint::Percentage(35)])
+        .split(rows[0]);
+
+    // Board, one list per status.
+    let statuses = Status::all();
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            statuses
+                .iter()
+                .map(|_| Constraint::Ratio(1, statuses.len() as u32))
+                .collect::<Vec<_>>(),
+        )
+        .split(panes[0]);
+    for (i, status) in statuses.iter().enumerate() {
+        let tickets = column_tickets(ticket_store, *status);
+        let items: Vec<ListItem> = tickets
+            .iter()
+            .map(|t| ListItem::new(format!("#{} {}", t.id, t.title)))
+            .collect();
+        let limit = ticket_store.wip_limits().get(*status);
+        let width = columns[i].width.saturating_sub(2) as usize;
+        let mut block = Block::default()
+            .borders(Borders::ALL)
+            .title(board::column_header(*status, tickets.len(), limit, width));
+        let mut state = ListState::default();
+        if i == app.column {
+            block = block.border_style(Style::default().add_modifier(Modifier::BOLD));
+            if !tickets.is_empty() {
+                state.select(Some(app.row));
+            }
+        }
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        f.render_stateful_widget(list, columns[i], &mut state);
+    }
+
+    // Details of the selected ticket.
+    let details = match app.selected(ticket_store) {
+        Some(ticket) => {
+            let mut lines = vec![
+                Spans::from(Span::styled(
+                    format!("#{} {}", ticket.id, ticket.title),
+                    Style::default().add_modifier(Modifier::BOLD),
+                )),
+                Spans::from(format!("Status: {:?}", ticket.status)),
+                Spans::from(""),
+                Spans::from(ticket.description.clone()),
+                Spans::from(""),
+                Spans::from(format!("Comments ({}):", ticket.comments.len())),
+            ];
+            lines.extend(
+                ticket
+                    .comments
+                    .iter()
+                    .map(|c| Spans::from(format!("- {}", c))),
+            );
+            lines
+        }
+        None => vec![Spans::from("No ticket selected.")],
+    };
+    let details = Paragraph::new(details)
+        .block(Block::default().borders(Borders::ALL).title("Ticket"))
+        .wrap(Wrap { trim: false });
+    f.render_widget(details, panes[1]);
+
+    // Input line, feedback or key bindings.
+    let (title, text) = match &app.mode {
+        Mode::Editing { field, input } => (
+            format!("{:?} (enter to save, esc to cancel)", field),
+            input.clone(),
+        ),
+        Mode::Browsing => match &app.message {
+            Some(message) => ("Error".to_string(), message.clone()),
+            None => ("Keys".to_string(), HELP.to_string()),
+        },
+    };
+    let footer = Paragraph::new(text).block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(footer, rows[1]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{App, Mode, Outcome};
+    use crate::models::{Status, TicketDraft, Title};
+    use crate::store::TicketStore;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    fn press(app: &mut App, store: &mut TicketStore, code: KeyCode) -> Outcome {
+        app.handle_key(KeyEvent::new(code, KeyModifiers::NONE), store)
+    }
+
+    fn store_with_one_ticket() -> TicketStore {
+        let mut store = TicketStore::new();
+        store.create(TicketDraft {
+            title: Title::new("Fix login".to_string()).unwrap(),
+            description: "".to_string(),
+        });
+        store
+    }
+
+    #[test]
+    fn moving_a_ticket_changes_its_status_and_keeps_it_selected() {
+        // arrange
+        let mut store = store_with_one_ticket();
+        let mut app = App::new();
+
+        // act
+        let outcome = press(&mut app, &mut store, KeyCode::Char('>'));
+
+        // assert
+        assert_eq!(outcome, Outcome::Changed(1));
+        assert_eq!(store.get(1).unwrap().status, Status::InProgress);
+        assert_eq!(app.selected(&store).map(|t| t.id), Some(1));
+    }
+
+    #[test]
+    fn editing_a_title_goes_through_validation() {
+        // arrange
+        let mut store = store_with_one_ticket();
+        let mut app = App::new();
+        press(&mut app, &mut store, KeyCode::Char('t'));
+        for _ in 0.."Fix login".len() {
+            press(&mut app, &mut store, KeyCode::Backspace);
+        }
+
+        // act
+        let outcome = press(&mut app, &mut store, KeyCode::Enter);
+
+        // assert
+        assert_eq!(outcome, Outcome::Unchanged);
+        assert_eq!(app.mode, Mode::Browsing);
+        assert!(app.message.is_some());
+        assert_eq!(store.get(1).unwrap().title.to_string(), "Fix login");
+    }
+
+    #[test]
+    fn adding_a_comment_to_the_selected_ticket() {
+        // arrange
+        let mut store = store_with_one_ticket();
+        let mut app = App::new();
+        press(&mut app, &mut store, KeyCode::Char('c'));
+        for c in "LGTM".chars() {
+            press(&mut app, &mut store, KeyCode::Char(c));
+        }
+
+        // act
+        let outcome = press(&mut app, &mut store, KeyCode::Enter);
+
+        // assert
+        assert_eq!(outcome, Outcome::Changed(1));
+        assert_eq!(store.get(1).unwrap().comments.len(), 1);
+    }
+}
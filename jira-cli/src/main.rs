@@ -1,15 +1,57 @@
 #![allow(clippy::new_without_default)]
 
-use crate::models::{Comment, Status, TicketDraft, TicketPatch, Title};
+use crate::config::Config;
+use crate::error::JiraError;
+use crate::models::{
+    Column, Comment, SortKey, Status, Ticket, TicketDraft, TicketFilter, TicketId, TicketPatch,
+    Title, View,
+};
+use crate::report::FlowReport;
+use crate::server::ServerState;
+use crate::store::StatusUpdateError;
+use crate::transfer::{Format, StatusMapping};
+use crate::webhooks::DeliveryQueue;
+use chrono::{Duration, Utc};
 use std::error::Error;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
+pub mod board;
+pub mod config;
+pub mod error;
+pub mod git;
+pub mod hooks;
+pub mod interactive;
 pub mod models;
 pub mod persistence;
+pub mod report;
+pub mod server;
 pub mod store;
+pub mod transfer;
+pub mod webhooks;
 
 #[derive(structopt::StructOpt)]
 /// A small command-line interface to interact with a toy Jira clone, IronJira.
+pub struct Opts {
+    /// Directory holding the ticket store, instead of the one from the configuration.
+    #[structopt(long, env = "JIRA_STORE", global = true)]
+    store: Option<PathBuf>,
+    /// Use a store profile defined in the configuration file, e.g. `work` or `personal`.
+    #[structopt(long, global = true)]
+    profile: Option<String>,
+    /// Use the store of a server started with `jira-cli serve`, e.g. `http://jira.local:8080`.
+    #[structopt(long, env = "JIRA_REMOTE", global = true)]
+    remote: Option<String>,
+    /// API token to authenticate with the remote server, as created by `jira-cli token create`
+    /// on it.
+    #[structopt(long, env = "JIRA_TOKEN", global = true, hide_env_values = true)]
+    token: Option<String>,
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(structopt::StructOpt)]
 pub enum Command {
     /// Create a ticket on your board.
     Create {
@@ -22,8 +64,10 @@ pub enum Command {
     },
     /// Edit the details of an existing ticket.
     Edit {
+        /// Id of the ticket. Defaults to the ticket named by the current git branch,
+        /// e.g. `12-fix-login` or `feature/IRON-12`.
         #[structopt(long)]
-        ticket_id: u64,
+        ticket_id: Option<TicketId>,
         #[structopt(long)]
         title: Option<String>,
         #[structopt(long)]
@@ -35,22 +79,273 @@ pub enum Command {
         ticket_id: u64,
     },
     /// List all existing tickets.
-    List,
+    List {
+        /// Name of a saved view to filter, sort and display the tickets with.
+        #[structopt(long)]
+        view: Option<String>,
+    },
+    /// Show all tickets on a board, with a column for each status.
+    Board {
+        /// Width of the board, in characters. Defaults to the width of the terminal.
+        #[structopt(long)]
+        width: Option<usize>,
+    },
+    /// Browse and edit tickets in a full-screen interface.
+    Tui,
     /// Move a ticket to a new status.
     Move {
+        /// Id of the ticket. Defaults to the ticket named by the current git branch,
+        /// e.g. `12-fix-login` or `feature/IRON-12`.
         #[structopt(long)]
-        ticket_id: u64,
+        ticket_id: Option<TicketId>,
         #[structopt(long)]
         status: Status,
+        /// Move the ticket even if the new status is at its work-in-progress limit.
+        #[structopt(long)]
+        force: bool,
+    },
+    /// Start working on a ticket: check out a git branch for it, e.g. `12-fix-login`,
+    /// and move it to in-progress.
+    Start {
+        /// Id of the ticket.
+        ticket_id: TicketId,
+        /// Move the ticket even if in-progress is at its work-in-progress limit.
+        #[structopt(long)]
+        force: bool,
+    },
+    /// Set the maximum number of tickets allowed in a status.
+    Limit {
+        #[structopt(long)]
+        status: Status,
+        /// Maximum number of tickets. Omit it to remove the limit.
+        #[structopt(long)]
+        max: Option<usize>,
     },
     /// Add a comment to a ticket
     Comment {
+        /// Id of the ticket. Defaults to the ticket named by the current git branch,
+        /// e.g. `12-fix-login` or `feature/IRON-12`.
         #[structopt(long)]
-        ticket_id: u64,
+        ticket_id: Option<TicketId>,
         /// Add a comment on the ticket - cannot be empty!
         #[structopt(long)]
         comment: String,
     },
+    /// Relate two tickets to each other.
+    Relate {
+        /// Id of the ticket. Defaults to the ticket named by the current git branch,
+        /// e.g. `12-fix-login` or `feature/IRON-12`.
+        #[structopt(long)]
+        ticket_id: Option<TicketId>,
+        /// Id of the ticket to relate it to.
+        #[structopt(long)]
+        other: TicketId,
+        /// Remove the relation instead.
+        #[structopt(long)]
+        remove: bool,
+    },
+    /// Manage saved views.
+    View(ViewCommand),
+    /// Analyse how tickets flow through the board.
+    Report(ReportCommand),
+    /// Write tickets to a file, or to standard output.
+    ///
+    /// The markdown and html formats render a report grouping tickets by status,
+    /// with their descriptions and comments, to attach to emails or wiki pages.
+    Export {
+        /// Format of the file: csv, markdown or html.
+        #[structopt(long, default_value = "csv")]
+        format: Format,
+        /// File to write to. Defaults to standard output.
+        #[structopt(long)]
+        output: Option<PathBuf>,
+        /// Only export the tickets of a saved view, in its order.
+        #[structopt(long)]
+        view: Option<String>,
+        /// Only export tickets in this status. Can be repeated.
+        #[structopt(long)]
+        status: Vec<Status>,
+        /// Only export tickets whose title or description contain this text.
+        #[structopt(long)]
+        text: Option<String>,
+    },
+    /// Create tickets from a file, e.g. a spreadsheet saved as CSV or an export of Jira.
+    ///
+    /// Issues imported from Jira keep their key: importing them again only adds their new
+    /// comments, unless --overwrite is given.
+    Import {
+        /// Format of the file: csv, jira-json (REST search response) or jira-xml (RSS export).
+        #[structopt(long, default_value = "csv")]
+        format: Format,
+        /// File to read the tickets from.
+        file: PathBuf,
+        /// Only check the file and report what would be imported.
+        #[structopt(long)]
+        dry_run: bool,
+        /// Replace the title, description, status and comments of the tickets imported
+        /// before with the ones of the file.
+        #[structopt(long)]
+        overwrite: bool,
+    },
+    /// List or restore the backups taken before the store is saved.
+    Backup(BackupCommand),
+    /// Link the commits of a git repository to the tickets they reference.
+    Git(GitCommand),
+    /// Enforce that commits of a git repository reference an open ticket.
+    Hooks(HooksCommand),
+    /// Inspect and retry the webhook events still to be delivered.
+    Webhooks(WebhooksCommand),
+    /// Manage the users of the API served by `jira-cli serve` and their API tokens.
+    ///
+    /// As soon as a user exists, every request to the server needs a token, sent as
+    /// `Authorization: Bearer <token>`, and changes are recorded as made by its user.
+    /// A running server only knows the tokens that existed when it was started.
+    Token(TokenCommand),
+    /// Serve the ticket store as a JSON REST API, until stopped.
+    ///
+    /// Tickets are under `/tickets`. A subset of the Jira REST API v2 is available under
+    /// `/rest/api/2`, with issue keys made of `git.prefix` and the ticket id, e.g. `IRON-12`.
+    /// A GraphQL schema over tickets, their comments and related tickets is served on `/graphql`.
+    ///
+    /// Requests are handled one at a time and saved as they happen. Other jira-cli commands
+    /// cannot use the store while it is being served. Requests need an API token once
+    /// `token create` was run.
+    Serve {
+        /// Address to listen on.
+        #[structopt(long, default_value = "127.0.0.1:8080")]
+        address: String,
+    },
+    /// Upgrade the saved ticket store to the format of this version of jira-cli.
+    Migrate {
+        /// Only report what would change, without touching the store.
+        #[structopt(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(structopt::StructOpt)]
+pub enum ReportCommand {
+    /// Lead time, cycle time and cumulative flow over the last days.
+    Flow {
+        /// Number of days covered by the report, today included.
+        #[structopt(long, default_value = "30")]
+        days: i64,
+    },
+}
+
+#[derive(structopt::StructOpt)]
+pub enum BackupCommand {
+    /// List the available backups, oldest first.
+    List,
+    /// Replace the store with a backup. The current store is backed up first.
+    Restore {
+        /// Id of the backup, as shown by `backup list`.
+        id: String,
+    },
+}
+
+#[derive(structopt::StructOpt)]
+pub enum GitCommand {
+    /// Scan the history of a local repository for ticket references, e.g. `IRON-12: fix login`
+    /// or `#12`, and record the commits on the tickets.
+    ///
+    /// The prefix of references can be changed with `git.prefix` in the configuration file.
+    /// Commits linked before are skipped.
+    Sync {
+        /// Path of the repository.
+        #[structopt(long, default_value = ".")]
+        repository: PathBuf,
+        /// Move tickets to done when a commit closes them, e.g. `closes #12` or `fixes IRON-12`.
+        #[structopt(long)]
+        transition: bool,
+    },
+}
+
+#[derive(structopt::StructOpt)]
+pub enum WebhooksCommand {
+    /// List the events waiting to be delivered, with the reason of their last failure.
+    List,
+    /// Send the events that are due for a retry.
+    Deliver {
+        /// Send every waiting event, even the ones scheduled for later.
+        #[structopt(long)]
+        all: bool,
+    },
+}
+
+#[derive(structopt::StructOpt)]
+pub enum TokenCommand {
+    /// Create an API token for a user, creating the user if needed.
+    /// The token is only shown once: the store keeps its hash.
+    Create {
+        /// Name of the user.
+        #[structopt(long)]
+        user: String,
+        /// Days after which the token expires, 0 for never.
+        /// Defaults to `tokens.lifetime` in the configuration file.
+        #[structopt(long)]
+        expires_in: Option<u32>,
+    },
+    /// List the users and the ids of their tokens.
+    List,
+    /// Revoke a token, so that it is refused from now on.
+    Revoke {
+        /// Id of the token, as shown by `token list`.
+        id: String,
+    },
+}
+
+#[derive(structopt::StructOpt)]
+pub enum HooksCommand {
+    /// Write a commit-msg hook into a repository, running `hooks check-msg` on every commit.
+    ///
+    /// The hook uses the same store as this command: pass `--store` or `--profile` here
+    /// to make the hook check another store.
+    Install {
+        /// Path of the repository.
+        #[structopt(long, default_value = ".")]
+        repository: PathBuf,
+        /// Replace a commit-msg hook that was not installed by jira-cli.
+        #[structopt(long)]
+        force: bool,
+    },
+    /// Fail unless the commit message in the file references an existing ticket
+    /// that is not done. Merge commits are accepted.
+    CheckMsg {
+        /// File holding the commit message, as passed by git to the hook.
+        file: PathBuf,
+    },
+}
+
+#[derive(structopt::StructOpt)]
+pub enum ViewCommand {
+    /// Save a view under a name, replacing any view with the same name.
+    Save {
+        #[structopt(long)]
+        name: String,
+        /// Only show tickets in this status. Can be repeated.
+        #[structopt(long)]
+        status: Vec<Status>,
+        /// Only show tickets whose title or description contain this text.
+        #[structopt(long)]
+        text: Option<String>,
+        /// Sort tickets by id, title or status.
+        #[structopt(long, default_value = "id")]
+        sort: SortKey,
+        /// Reverse the sort order.
+        #[structopt(long)]
+        descending: bool,
+        /// Column to display: id, title, status, description or comments. Can be repeated.
+        #[structopt(long)]
+        column: Vec<Column>,
+    },
+    /// List all saved views.
+    List,
+    /// Delete a saved view.
+    Delete {
+        #[structopt(long)]
+        name: String,
+    },
 }
 
 impl FromStr for Status {
@@ -63,51 +358,154 @@ impl FromStr for Status {
             "inprogress" | "in-progress" => Status::InProgress,
             "blocked" => Status::Blocked,
             "done" => Status::Done,
-            _ => panic!("The status you specified is not valid. Valid values: todo, inprogress, blocked and done.")
+            _ => return Err("The status you specified is not valid. Valid values: todo, inprogress, blocked and done.".into())
         };
         Ok(status)
     }
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
+/// What a command changed in the store, i.e. what has to be persisted.
+enum Change {
+    Nothing,
+    Ticket(TicketId),
+    Tickets(Vec<TicketId>),
+    DeletedTicket(TicketId),
+    /// Views or configuration of the store.
+    Settings,
+}
+
+/// The ticket given on the command line or, if omitted, the one named by the current git branch.
+fn current_ticket(ticket_id: Option<TicketId>, config: &Config) -> Result<TicketId, JiraError> {
+    if let Some(ticket_id) = ticket_id {
+        return Ok(ticket_id);
+    }
+    let missing = || {
+        JiraError::Validation(
+            "No --ticket-id given, and the current git branch does not name a ticket.".to_string(),
+        )
+    };
+    let branch = git::current_branch(Path::new(".")).map_err(|_| missing())?;
+    branch
+        .and_then(|branch| git::ticket_from_branch(&branch, &config.git.prefix))
+        .ok_or_else(missing)
+}
+
+/// The webhook queue of the local store: the one of a remote store is on its server.
+fn local_webhooks(queue: &mut Option<DeliveryQueue>) -> Result<&mut DeliveryQueue, JiraError> {
+    queue.as_mut().ok_or_else(|| {
+        JiraError::Validation(
+            "The webhooks of a remote store are sent by its server. Run this command there."
+                .to_string(),
+        )
+    })
+}
+
+/// Refuse to manage the users of a remote store: they live on its server.
+fn check_local_users(config: &Config) -> Result<(), JiraError> {
+    match &config.remote {
+        Some(remote) => Err(JiraError::Validation(format!(
+            "The users of {} are managed on its server. Run this command there.",
+            remote
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Upgrade the saved store to the format of this version of the CLI, listing the changes.
+fn migrate(backend: &mut dyn persistence::StorageBackend, dry_run: bool) -> Result<(), JiraError> {
+    let changes = backend.migrate(dry_run)?;
+    if changes.is_empty() {
+        println!("The ticket store is up to date.");
+    } else {
+        let verb = if dry_run { "Would apply" } else { "Applied" };
+        println!("{} {} change(s):", verb, changes.len());
+        for change in changes {
+            println!("  - {}", change);
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {}", e);
+        std::process::exit(e.exit_code());
+    }
+}
+
+fn run() -> Result<(), JiraError> {
     // Parse the command-line arguments.
-    let command = <Command as paw::ParseArgs>::parse_args()?;
-    // Load the store from disk. If missing, a brand new one will be created.
-    let mut ticket_store = persistence::load();
-    match command {
+    // Invalid arguments are reported by `structopt`, which exits with code 1.
+    let opts = <Opts as paw::ParseArgs>::parse_args()?;
+    // Pick where tickets are stored according to the configuration and the options.
+    let mut config = config::load()?;
+    if let Some(profile) = &opts.profile {
+        config = config.with_profile(profile)?;
+    }
+    if opts.store.is_some() {
+        config.store = opts.store.clone();
+        config.remote = None;
+    }
+    if opts.remote.is_some() {
+        if opts.remote != config.remote {
+            // The configured token belongs to another server.
+            config.token = None;
+        }
+        config.remote = opts.remote.clone();
+    }
+    if opts.token.is_some() {
+        config.token = opts.token.clone();
+    }
+    let mut backend = persistence::backend(&config)?;
+    // Keep other jira-cli processes away from the store until we are done with it.
+    let _lock = backend.lock()?;
+    // Loading upgrades some stores on the way, e.g. SQLite databases: migrate them first.
+    if let Command::Migrate { dry_run } = opts.command {
+        return migrate(backend.as_mut(), dry_run);
+    }
+    // Load the store. If missing, a brand new one will be created.
+    let mut ticket_store = backend.load()?;
+    // Events are sent to the webhooks once the store is saved, by the server for a remote store.
+    let mut webhook_queue = match &config.remote {
+        Some(_) => None,
+        None => Some(DeliveryQueue::open(
+            persistence::store_dir(&config)?.join(webhooks::WEBHOOK_QUEUE),
+            config.webhooks.clone(),
+        )?),
+    };
+    let change = match opts.command {
         Command::Create { description, title } => {
             let draft = TicketDraft {
                 title: Title::new(title)?,
                 description,
             };
-            ticket_store.create(draft);
+            Change::Ticket(ticket_store.create(draft))
         }
         Command::Edit {
             ticket_id,
             title,
             description,
         } => {
+            let ticket_id = current_ticket(ticket_id, &config)?;
             let title = title.map(Title::new).transpose()?;
             let ticket_patch = TicketPatch { title, description };
-            match ticket_store.update_ticket(ticket_id, ticket_patch) {
-                Some(_) => println!("Ticket {:?} was updated.", ticket_id),
-                None => println!(
-                    "There was no ticket associated to the ticket id {:?}",
-                    ticket_id
-                ),
-            }
+            ticket_store
+                .update_ticket(ticket_id, ticket_patch)
+                .ok_or_else(|| JiraError::ticket_not_found(ticket_id))?;
+            println!("Ticket {:?} was updated.", ticket_id);
+            Change::Ticket(ticket_id)
         }
-        Command::Delete { ticket_id } => match ticket_store.delete(ticket_id) {
-            Some(deleted_ticket) => println!(
+        Command::Delete { ticket_id } => {
+            let deleted_ticket = ticket_store
+                .delete(ticket_id)
+                .ok_or_else(|| JiraError::ticket_not_found(ticket_id))?;
+            println!(
                 "The following ticket has been deleted:\n{:?}",
                 deleted_ticket
-            ),
-            None => println!(
-                "There was no ticket associated to the ticket id {:?}",
-                ticket_id
-            ),
-        },
-        Command::List => {
+            );
+            Change::DeletedTicket(ticket_id)
+        }
+        Command::List { view: None } => {
             let ticket_list = ticket_store
                 .list()
                 .into_iter()
@@ -115,31 +513,487 @@ fn main() -> Result<(), Box<dyn Error>> {
                 .collect::<Vec<String>>()
                 .join("\n\n");
             println!("{}", ticket_list);
+            Change::Nothing
         }
-        Command::Move { ticket_id, status } => {
-            match ticket_store.update_ticket_status(ticket_id, status) {
-                Some(_) => println!(
-                    "Status of ticket {:?} was updated to {:?}",
-                    ticket_id, status
-                ),
-                None => println!(
-                    "There was no ticket associated to the ticket id {:?}",
-                    ticket_id
-                ),
+        Command::List { view: Some(name) } => {
+            let view = ticket_store
+                .get_view(&name)
+                .ok_or_else(|| view_not_found(&name))?;
+            let tickets = view.apply(ticket_store.list());
+            println!("{}", view.render(&tickets));
+            Change::Nothing
+        }
+        Command::Board { width } => {
+            let width = width.unwrap_or_else(board::terminal_width);
+            println!(
+                "{}",
+                board::render(
+                    &ticket_store.list(),
+                    &Status::all(),
+                    ticket_store.wip_limits(),
+                    width
+                )
+            );
+            Change::Nothing
+        }
+        Command::Tui => {
+            // The interface saves every change as it happens.
+            interactive::run(&mut ticket_store, backend.as_mut())?;
+            Change::Nothing
+        }
+        Command::Move {
+            ticket_id,
+            status,
+            force,
+        } => {
+            let ticket_id = current_ticket(ticket_id, &config)?;
+            let result = if force {
+                ticket_store
+                    .force_ticket_status(ticket_id, status)
+                    .ok_or(StatusUpdateError::TicketNotFound(ticket_id))
+            } else {
+                ticket_store.update_ticket_status(ticket_id, status)
+            };
+            result?;
+            println!(
+                "Status of ticket {:?} was updated to {:?}",
+                ticket_id, status
+            );
+            Change::Ticket(ticket_id)
+        }
+        Command::Start { ticket_id, force } => {
+            let title = ticket_store
+                .get(ticket_id)
+                .ok_or_else(|| JiraError::ticket_not_found(ticket_id))?
+                .title
+                .to_string();
+            // Check the work-in-progress limit before touching the repository.
+            if force {
+                ticket_store.force_ticket_status(ticket_id, Status::InProgress);
+            } else {
+                ticket_store.update_ticket_status(ticket_id, Status::InProgress)?;
+            }
+            let repository = Path::new(".");
+            let current = git::current_branch(repository)?;
+            let prefix = &config.git.prefix;
+            match current.filter(|b| git::ticket_from_branch(b, prefix) == Some(ticket_id)) {
+                Some(branch) => println!("Already on branch {}.", branch),
+                None => {
+                    let branch = git::branch_name(ticket_id, &title);
+                    if git::checkout_branch(repository, &branch)? {
+                        println!("Created branch {}.", branch);
+                    } else {
+                        println!("Switched to branch {}.", branch);
+                    }
+                }
             }
+            println!(
+                "Status of ticket {:?} was updated to {:?}",
+                ticket_id,
+                Status::InProgress
+            );
+            Change::Ticket(ticket_id)
+        }
+        Command::Limit { status, max } => {
+            ticket_store.set_wip_limit(status, max)?;
+            match max {
+                Some(max) => println!("{:?} is now limited to {} ticket(s).", status, max),
+                None => println!("{:?} is no longer limited.", status),
+            }
+            Change::Settings
         }
         Command::Comment { ticket_id, comment } => {
+            let ticket_id = current_ticket(ticket_id, &config)?;
             let new_comment = Comment::new(comment)?;
-            match ticket_store.add_comment_to_ticket(ticket_id, new_comment) {
-                Some(_) => println!("Comment has been added to ticket {:?}", ticket_id),
-                None => println!(
-                    "There was no ticket associated to the ticket id {:?}",
-                    ticket_id
+            ticket_store
+                .add_comment_to_ticket(ticket_id, new_comment)
+                .ok_or_else(|| JiraError::ticket_not_found(ticket_id))?;
+            println!("Comment has been added to ticket {:?}", ticket_id);
+            Change::Ticket(ticket_id)
+        }
+        Command::Relate {
+            ticket_id,
+            other,
+            remove,
+        } => {
+            let ticket_id = current_ticket(ticket_id, &config)?;
+            if ticket_id == other {
+                return Err(JiraError::Validation(
+                    "A ticket cannot be related to itself.".to_string(),
+                ));
+            }
+            let changed = if remove {
+                ticket_store.unrelate_tickets(ticket_id, other)
+            } else {
+                ticket_store.relate_tickets(ticket_id, other)
+            };
+            let missing = if ticket_store.get(ticket_id).is_none() {
+                ticket_id
+            } else {
+                other
+            };
+            match (
+                changed.ok_or_else(|| JiraError::ticket_not_found(missing))?,
+                remove,
+            ) {
+                (true, false) => println!("Tickets {} and {} are now related.", ticket_id, other),
+                (false, false) => {
+                    println!("Tickets {} and {} were already related.", ticket_id, other)
+                }
+                (true, true) => {
+                    println!("Tickets {} and {} are no longer related.", ticket_id, other)
+                }
+                (false, true) => println!("Tickets {} and {} were not related.", ticket_id, other),
+            }
+            Change::Tickets(vec![ticket_id, other])
+        }
+        Command::View(ViewCommand::Save {
+            name,
+            status,
+            text,
+            sort,
+            descending,
+            column,
+        }) => {
+            let columns = if column.is_empty() {
+                Column::defaults()
+            } else {
+                column
+            };
+            let view = View {
+                filter: TicketFilter {
+                    statuses: status,
+                    text,
+                },
+                sort,
+                descending,
+                columns,
+            };
+            match ticket_store.save_view(name.clone(), view) {
+                Some(_) => println!("View {:?} was updated.", name),
+                None => println!("View {:?} was saved.", name),
+            }
+            Change::Settings
+        }
+        Command::View(ViewCommand::List) => {
+            for (name, view) in ticket_store.list_views() {
+                println!("View {}:\n{}", name, view);
+            }
+            Change::Nothing
+        }
+        Command::View(ViewCommand::Delete { name }) => {
+            ticket_store
+                .delete_view(&name)
+                .ok_or_else(|| view_not_found(&name))?;
+            println!("View {:?} has been deleted.", name);
+            Change::Settings
+        }
+        Command::Report(ReportCommand::Flow { days }) => {
+            if days > report::MAX_DAYS {
+                return Err(JiraError::Validation(format!(
+                    "A report covers at most {} days.",
+                    report::MAX_DAYS
+                )));
+            }
+            let until = Utc::now();
+            let since = until - Duration::days(days.max(1) - 1);
+            let report = FlowReport::compute(&ticket_store.list(), since, until);
+            println!("{}", report.render());
+            Change::Nothing
+        }
+        Command::Export {
+            format,
+            output,
+            view,
+            status,
+            text,
+        } => {
+            let tickets = match view {
+                Some(name) => ticket_store
+                    .get_view(&name)
+                    .ok_or_else(|| view_not_found(&name))?
+                    .apply(ticket_store.list()),
+                None => transfer::sorted_tickets(&ticket_store),
+            };
+            let filter = TicketFilter {
+                statuses: status,
+                text,
+            };
+            let tickets: Vec<&Ticket> = tickets.into_iter().filter(|t| filter.matches(t)).collect();
+            let output: Box<dyn Write> = match output {
+                Some(path) => Box::new(
+                    std::fs::File::create(&path)
+                        .map_err(|e| JiraError::io(&format!("Failed to create {:?}", path), e))?,
                 ),
+                None => Box::new(std::io::stdout()),
+            };
+            match format {
+                Format::Csv => transfer::export_csv(&tickets, output)?,
+                Format::Markdown => transfer::export_markdown(&tickets, output)?,
+                Format::Html => transfer::export_html(&tickets, output)?,
+                Format::JiraJson | Format::JiraXml => {
+                    return Err(JiraError::Validation(format!(
+                        "Tickets cannot be exported as {:?}, only imported.",
+                        format
+                    )))
+                }
             }
+            Change::Nothing
+        }
+        Command::Import {
+            format,
+            file,
+            dry_run,
+            overwrite,
+        } => {
+            let input = std::fs::File::open(&file)
+                .map_err(|e| JiraError::io(&format!("Failed to open {:?}", file), e))?;
+            let mapping = StatusMapping::new(&config.jira.status_mapping);
+            let rows = match format {
+                Format::Csv => transfer::read_csv(input)?,
+                Format::JiraJson => transfer::read_jira_json(input, &mapping)?,
+                Format::JiraXml => transfer::read_jira_xml(input, &mapping)?,
+                Format::Markdown | Format::Html => {
+                    return Err(JiraError::Validation(format!(
+                        "Tickets cannot be imported from {:?}, only exported.",
+                        format
+                    )))
+                }
+            };
+            let report = transfer::import(&mut ticket_store, rows, dry_run, overwrite);
+            for error in &report.errors {
+                eprintln!("{}", error);
+            }
+            if dry_run {
+                println!(
+                    "Would create {} ticket(s) and update {} ticket(s).",
+                    report.created, report.updated
+                );
+            } else {
+                println!(
+                    "Created {} ticket(s) and updated {} ticket(s).",
+                    report.created, report.updated
+                );
+                backend.save(&ticket_store)?;
+            }
+            if !report.errors.is_empty() {
+                return Err(JiraError::Validation(format!(
+                    "{} row(s) could not be imported.",
+                    report.errors.len()
+                )));
+            }
+            // Imported tickets were saved above, even if some rows were invalid.
+            Change::Nothing
+        }
+        Command::Backup(BackupCommand::List) => {
+            match backend.backups().map(|backups| backups.list()) {
+                None => println!("Backups are not supported by the configured storage backend."),
+                Some(backups) if backups.is_empty() => println!("There are no backups yet."),
+                Some(backups) => {
+                    for backup in backups {
+                        println!("{}\t{}", backup.id, backup.taken_at.to_rfc2822());
+                    }
+                }
+            }
+            Change::Nothing
+        }
+        Command::Backup(BackupCommand::Restore { id }) => {
+            backend.restore(&id)?;
+            println!("Restored backup {}.", id);
+            // The store on disk has been replaced: saving the one we loaded would undo the restore.
+            Change::Nothing
+        }
+        Command::Git(GitCommand::Sync {
+            repository,
+            transition,
+        }) => {
+            let commits = git::read_log(&repository)?;
+            let report = git::sync(&mut ticket_store, &commits, &config.git.prefix, transition);
+            for warning in &report.warnings {
+                eprintln!("{}", warning);
+            }
+            println!(
+                "Scanned {} commit(s), linked {} new commit(s) to tickets.",
+                commits.len(),
+                report.linked
+            );
+            for id in &report.closed {
+                println!("Ticket {:?} was moved to {:?}.", id, Status::Done);
+            }
+            if report.linked > 0 {
+                backend.save(&ticket_store)?;
+            }
+            Change::Nothing
+        }
+        Command::Hooks(HooksCommand::Install { repository, force }) => {
+            let executable = std::env::current_exe()
+                .map_err(|e| JiraError::io("Failed to locate the jira-cli executable", e))?;
+            let mut command = vec![executable.to_string_lossy().into_owned()];
+            if let Some(store) = &opts.store {
+                // The hook runs from the repository: relative paths would point elsewhere.
+                let store = std::env::current_dir()?.join(store);
+                command.extend(vec![
+                    "--store".to_string(),
+                    store.to_string_lossy().into_owned(),
+                ]);
+            }
+            if let Some(profile) = &opts.profile {
+                command.extend(vec!["--profile".to_string(), profile.clone()]);
+            }
+            if let Some(remote) = &opts.remote {
+                command.extend(vec!["--remote".to_string(), remote.clone()]);
+            }
+            let path = hooks::install(&repository, &command, force)?;
+            println!("Installed the commit-msg hook in {:?}.", path);
+            Change::Nothing
+        }
+        Command::Hooks(HooksCommand::CheckMsg { file }) => {
+            let message = std::fs::read_to_string(&file)
+                .map_err(|e| JiraError::io(&format!("Failed to read {:?}", file), e))?;
+            hooks::check_message(&ticket_store, &message, &config.git.prefix)?;
+            Change::Nothing
+        }
+        Command::Serve { address } => {
+            if let Some(remote) = &config.remote {
+                return Err(JiraError::Validation(format!(
+                    "The store of {} cannot be served again. Serve a local store instead.",
+                    remote
+                )));
+            }
+            if ticket_store.list_users().is_empty() {
+                eprintln!(
+                    "Warning: anyone reaching {} can read and change the store. \
+                     Require API tokens by creating one with `jira-cli token create`.",
+                    address
+                );
+            }
+            let mut state = ServerState::new(&mut ticket_store, backend.as_mut())
+                .with_project_key(&config.git.prefix)
+                .with_status_mapping(StatusMapping::new(&config.jira.status_mapping));
+            if let Some(queue) = &mut webhook_queue {
+                state = state.with_webhooks(queue);
+            }
+            // Every request saves its own changes.
+            server::serve(&address, &mut state)?;
+            Change::Nothing
+        }
+        Command::Webhooks(WebhooksCommand::List) => {
+            let deliveries = local_webhooks(&mut webhook_queue)?.deliveries();
+            if deliveries.is_empty() {
+                println!("No webhook event is waiting to be delivered.");
+            }
+            for delivery in deliveries {
+                println!(
+                    "{} {} to {}: {} failed attempt(s), next one at {}{}",
+                    delivery.id,
+                    delivery.event,
+                    delivery.url,
+                    delivery.attempts,
+                    delivery.next_attempt_at.format("%Y-%m-%d %H:%M:%S"),
+                    delivery
+                        .last_error
+                        .as_ref()
+                        .map(|e| format!(" ({})", e))
+                        .unwrap_or_default()
+                );
+            }
+            Change::Nothing
+        }
+        Command::Webhooks(WebhooksCommand::Deliver { all }) => {
+            let webhook_queue = local_webhooks(&mut webhook_queue)?;
+            let report = webhook_queue.deliver(Utc::now(), all);
+            webhook_queue.save()?;
+            println!(
+                "Delivered {} webhook event(s), {} still waiting.",
+                report.delivered,
+                webhook_queue.deliveries().len()
+            );
+            for warning in report.warnings() {
+                eprintln!("Warning: {}", warning);
+            }
+            Change::Nothing
+        }
+        Command::Token(TokenCommand::Create { user, expires_in }) => {
+            check_local_users(&config)?;
+            let days = expires_in.unwrap_or(config.tokens.lifetime);
+            let lifetime = Some(Duration::days(days.into())).filter(|_| days > 0);
+            let (token, secret) = ticket_store.create_token(&user, lifetime).ok_or_else(|| {
+                JiraError::Validation(format!(
+                    "A token cannot expire in {} days: pick a shorter lifetime.",
+                    days
+                ))
+            })?;
+            match token.expires_at {
+                Some(expires_at) => println!(
+                    "Created token {} for {}, expiring on {}:",
+                    token.id,
+                    user,
+                    expires_at.format("%Y-%m-%d")
+                ),
+                None => println!("Created token {} for {}, never expiring:", token.id, user),
+            }
+            println!("{}", secret);
+            println!("It cannot be shown again: keep it somewhere safe.");
+            Change::Settings
+        }
+        Command::Token(TokenCommand::List) => {
+            check_local_users(&config)?;
+            let users = ticket_store.list_users();
+            if users.is_empty() {
+                println!("There are no users: the API served by jira-cli is open to anyone.");
+            }
+            let now = Utc::now();
+            for (name, user) in users {
+                println!("{}", name);
+                for token in &user.tokens {
+                    let expiry = match token.expires_at {
+                        Some(at) if token.is_expired(now) => {
+                            format!("expired on {}", at.format("%Y-%m-%d"))
+                        }
+                        Some(at) => format!("expires on {}", at.format("%Y-%m-%d")),
+                        None => "never expires".to_string(),
+                    };
+                    println!(
+                        "  {} created on {}, {}",
+                        token.id,
+                        token.created_at.format("%Y-%m-%d"),
+                        expiry
+                    );
+                }
+            }
+            Change::Nothing
+        }
+        Command::Token(TokenCommand::Revoke { id }) => {
+            check_local_users(&config)?;
+            let user = ticket_store.revoke_token(&id).ok_or_else(|| {
+                JiraError::NotFound(format!("There is no API token with id {:?}", id))
+            })?;
+            println!("Revoked token {} of {}.", id, user);
+            Change::Settings
+        }
+        // Handled before the store is loaded.
+        Command::Migrate { .. } => Change::Nothing,
+    };
+    // Persist what we changed, now that we have completed our action.
+    match change {
+        Change::Nothing => Ok(()),
+        Change::Ticket(id) => backend.save_ticket(&ticket_store, id),
+        Change::Tickets(ids) => ids
+            .into_iter()
+            .try_for_each(|id| backend.save_ticket(&ticket_store, id)),
+        Change::DeletedTicket(id) => backend.delete_ticket(&ticket_store, id),
+        Change::Settings => backend.save_settings(&ticket_store),
+    }?;
+    // Only tell the webhooks about changes that were saved.
+    if let Some(queue) = &mut webhook_queue {
+        let report = webhooks::notify(queue, &mut ticket_store)?;
+        for warning in report.warnings() {
+            eprintln!("Warning: {}", warning);
         }
     }
-    // Save the store state to disk after we have completed our action.
-    persistence::save(&ticket_store);
     Ok(())
 }
+
+fn view_not_found(name: &str) -> JiraError {
+    JiraError::NotFound(format!("There is no saved view named {:?}", name))
+}
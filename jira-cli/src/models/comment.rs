@@ -8,6 +8,9 @@ use std::fmt::Formatter;
 /// Wraps a string and checks that it is not empty when set
 pub struct Comment {
     comment: String,
+    /// Name of the [User](crate::models::User) who wrote the comment, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    author: Option<String>,
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -45,9 +48,27 @@ impl Comment {
         if comment.is_empty() {
             Err(CommentError::new("Comment cannot be empty"))
         } else {
-            Ok(Comment { comment })
+            Ok(Comment {
+                comment,
+                author: None,
+            })
         }
     }
+
+    /// Record who wrote the comment.
+    pub fn with_author(mut self, author: &str) -> Comment {
+        self.author = Some(author.to_string());
+        self
+    }
+
+    /// Replace who the comment is recorded as written by.
+    pub(crate) fn set_author(&mut self, author: Option<String>) {
+        self.author = author;
+    }
+
+    pub fn author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
 }
 
 impl std::fmt::Display for Comment {
@@ -0,0 +1,48 @@
+use crate::models::{Status, TicketId};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+/// Something that happened to a [Ticket](crate::models::Ticket) in the
+/// [TicketStore](crate::store::TicketStore), as sent to webhooks.
+#[serde(tag = "event")]
+pub enum TicketEvent {
+    #[serde(rename = "ticket.created")]
+    Created {
+        ticket_id: TicketId,
+        at: DateTime<Utc>,
+    },
+    #[serde(rename = "ticket.moved")]
+    Moved {
+        ticket_id: TicketId,
+        from: Status,
+        to: Status,
+        at: DateTime<Utc>,
+    },
+    #[serde(rename = "ticket.commented")]
+    Commented {
+        ticket_id: TicketId,
+        comment: String,
+        at: DateTime<Utc>,
+    },
+}
+
+impl TicketEvent {
+    /// Name of the event, e.g. `ticket.moved`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TicketEvent::Created { .. } => "ticket.created",
+            TicketEvent::Moved { .. } => "ticket.moved",
+            TicketEvent::Commented { .. } => "ticket.commented",
+        }
+    }
+
+    /// The ticket the event happened to.
+    pub fn ticket_id(&self) -> TicketId {
+        match self {
+            TicketEvent::Created { ticket_id, .. }
+            | TicketEvent::Moved { ticket_id, .. }
+            | TicketEvent::Commented { ticket_id, .. } => *ticket_id,
+        }
+    }
+}
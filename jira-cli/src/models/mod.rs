@@ -1,11 +1,19 @@
 mod comment;
+mod event;
+mod store_config;
 mod ticket;
 mod ticket_draft;
 mod ticket_patch;
 mod title;
+mod user;
+mod view;
 
 pub use comment::*;
+pub use event::*;
+pub use store_config::*;
 pub use ticket::*;
 pub use ticket_draft::*;
 pub use ticket_patch::*;
 pub use title::*;
+pub use user::*;
+pub use view::*;
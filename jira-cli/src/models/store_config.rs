@@ -0,0 +1,119 @@
+use crate::models::Status;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+#[derive(PartialEq, Debug, Clone, Default, Serialize, Deserialize)]
+/// Settings that belong to a [TicketStore](TicketStore) and are saved alongside its tickets.
+pub struct StoreConfig {
+    #[serde(default)]
+    pub wip_limits: WipLimits,
+}
+
+#[derive(PartialEq, Debug, Clone, Default, Serialize, Deserialize)]
+/// The maximum number of tickets allowed in each [Status](Status), if any.
+///
+/// **Invariant**: every limit is at least 1.
+/// The invariant is also checked when loading limits from disk.
+#[serde(try_from = "HashMap<Status, usize>", into = "HashMap<Status, usize>")]
+pub struct WipLimits {
+    limits: HashMap<Status, usize>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+/// Error if a work-in-progress limit is not valid
+pub struct WipLimitError {
+    details: String,
+}
+
+impl WipLimitError {
+    fn new(msg: &str) -> WipLimitError {
+        WipLimitError {
+            details: msg.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for WipLimitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+impl Error for WipLimitError {
+    fn description(&self) -> &str {
+        &self.details
+    }
+}
+
+impl WipLimits {
+    /// The limit for a status. Returns `None` if the status is unlimited.
+    pub fn get(&self, status: Status) -> Option<usize> {
+        self.limits.get(&status).copied()
+    }
+
+    /// Set the limit for a status, or remove it by passing `None`.
+    /// Results in a [WipLimitError](WipLimitError) if the limit is zero.
+    pub fn set(&mut self, status: Status, limit: Option<usize>) -> Result<(), WipLimitError> {
+        match limit {
+            Some(0) => Err(WipLimitError::new(
+                "A work-in-progress limit must allow at least one ticket",
+            )),
+            Some(limit) => {
+                self.limits.insert(status, limit);
+                Ok(())
+            }
+            None => {
+                self.limits.remove(&status);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl TryFrom<HashMap<Status, usize>> for WipLimits {
+    type Error = WipLimitError;
+
+    fn try_from(limits: HashMap<Status, usize>) -> Result<Self, Self::Error> {
+        let mut wip_limits = WipLimits::default();
+        for (status, limit) in limits {
+            wip_limits.set(status, Some(limit))?;
+        }
+        Ok(wip_limits)
+    }
+}
+
+impl From<WipLimits> for HashMap<Status, usize> {
+    fn from(wip_limits: WipLimits) -> Self {
+        wip_limits.limits
+    }
+}
+
+#[cfg(test)]
+mod store_config_tests {
+    use crate::models::{Status, WipLimits};
+
+    #[test]
+    fn a_limit_of_zero_is_rejected() {
+        // arrange
+        let mut limits = WipLimits::default();
+
+        // act
+        let result = limits.set(Status::InProgress, Some(0));
+
+        // assert
+        assert!(result.is_err());
+        assert_eq!(limits.get(Status::InProgress), None);
+    }
+
+    #[test]
+    fn loading_a_limit_of_zero_fails() {
+        // act
+        let result = serde_yaml::from_str::<WipLimits>("InProgress: 0");
+
+        // assert
+        assert!(result.is_err());
+    }
+}
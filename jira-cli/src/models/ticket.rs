@@ -1,4 +1,5 @@
 use crate::models::{Comment, Title};
+use chrono::{DateTime, Utc};
 use serde::export::fmt::Error;
 use serde::export::Formatter;
 use serde::{Deserialize, Serialize};
@@ -18,17 +19,96 @@ pub struct Ticket {
     pub description: String,
     pub status: Status,
     pub comments: Vec<Comment>,
+    /// When the ticket was created. Missing for tickets created before it was recorded.
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+    /// Every [Status] change the ticket went through, oldest first.
+    #[serde(default)]
+    pub history: Vec<StatusChange>,
+    /// Key of the ticket in the tool it was imported from, e.g. `PROJ-42` for Jira.
+    #[serde(default)]
+    pub external_key: Option<String>,
+    /// Commits of a git repository referencing the ticket, oldest first.
+    #[serde(default)]
+    pub commits: Vec<LinkedCommit>,
+    /// Ids of the tickets related to this one. Relations go both ways.
+    #[serde(default)]
+    pub related: Vec<TicketId>,
+}
+
+#[derive(PartialEq, Debug, Clone, Hash, Eq, Serialize, Deserialize)]
+/// A [Ticket](Ticket) moving from one [Status](Status) to another.
+pub struct StatusChange {
+    pub from: Status,
+    pub to: Status,
+    pub at: DateTime<Utc>,
+    /// Name of the [User](crate::models::User) who moved the ticket, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+}
+
+#[derive(PartialEq, Debug, Clone, Hash, Eq, Serialize, Deserialize)]
+/// A git commit whose message references a [Ticket](Ticket).
+pub struct LinkedCommit {
+    pub hash: String,
+    pub author: String,
+    pub date: DateTime<Utc>,
+    /// First line of the commit message.
+    pub subject: String,
+}
+
+#[cfg(test)]
+impl Ticket {
+    /// A ticket with nothing but an id, a title and a status, for tests outside the store.
+    pub(crate) fn sample(id: TicketId, title: &str, status: Status) -> Ticket {
+        Ticket {
+            id,
+            title: Title::new(title.to_string()).unwrap(),
+            description: "".to_string(),
+            status,
+            comments: Vec::new(),
+            created_at: None,
+            history: Vec::new(),
+            external_key: None,
+            commits: Vec::new(),
+            related: Vec::new(),
+        }
+    }
 }
 
 impl std::fmt::Display for Ticket {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         writeln!(
             f,
-            "Ticket:\n\tId:{:?}\n\tTitle:{}\n\tDescription:{}\n\tStatus:{:?}\n\tComments:",
+            "Ticket:\n\tId:{:?}\n\tTitle:{}\n\tDescription:{}\n\tStatus:{:?}",
             self.id, self.title, self.description, self.status
         )?;
+        if let Some(key) = &self.external_key {
+            writeln!(f, "\tExternal key:{}", key)?;
+        }
+        writeln!(f, "\tComments:")?;
         for comment in self.comments.iter() {
-            writeln!(f, "\t- {}", comment)?;
+            match comment.author() {
+                Some(author) => writeln!(f, "\t- {} ({})", comment, author)?,
+                None => writeln!(f, "\t- {}", comment)?,
+            }
+        }
+        if !self.related.is_empty() {
+            let related: Vec<String> = self.related.iter().map(|id| id.to_string()).collect();
+            writeln!(f, "\tRelated:{}", related.join(", "))?;
+        }
+        if !self.commits.is_empty() {
+            writeln!(f, "\tCommits:")?;
+            for commit in self.commits.iter() {
+                writeln!(
+                    f,
+                    "\t- {} {} ({}, {})",
+                    commit.hash.chars().take(7).collect::<String>(),
+                    commit.subject,
+                    commit.author,
+                    commit.date.format("%Y-%m-%d")
+                )?;
+            }
         }
         Ok(())
     }
@@ -43,6 +123,18 @@ pub enum Status {
     Done,
 }
 
+impl Status {
+    /// All the statuses a ticket can be in, in the order they appear on the board.
+    pub fn all() -> [Status; 4] {
+        [
+            Status::ToDo,
+            Status::InProgress,
+            Status::Blocked,
+            Status::Done,
+        ]
+    }
+}
+
 #[derive(PartialEq, Debug)]
 /// A ticket that was deleted from the store.
 ///
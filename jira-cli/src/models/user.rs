@@ -0,0 +1,113 @@
+use chrono::{DateTime, Duration, Utc};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Prefix of every API token, making them easy to recognise, e.g. in a leaked file.
+const TOKEN_PREFIX: &str = "ij";
+
+#[derive(PartialEq, Debug, Clone, Default, Serialize, Deserialize)]
+/// Someone allowed to use the API served by `jira-cli serve`, authenticating with one of
+/// their [ApiToken]s.
+pub struct User {
+    #[serde(default)]
+    pub tokens: Vec<ApiToken>,
+}
+
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+/// A secret a [User] authenticates with, e.g. `ij_1a2b3c4d_…`.
+///
+/// Only the SHA-256 hash of the token is stored: the token itself is shown once, when created.
+pub struct ApiToken {
+    /// Public part of the token, used to tell tokens apart and to revoke them.
+    pub id: String,
+    /// Hex-encoded SHA-256 hash of the whole token.
+    pub hash: String,
+    pub created_at: DateTime<Utc>,
+    /// When the token stops being accepted. Never if `None`.
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl ApiToken {
+    /// Generate a new token valid for `lifetime`, or forever if `None`.
+    /// Returns it along with the secret to hand over to its user, or `None` if the token
+    /// would expire too far in the future to be represented.
+    pub fn generate(now: DateTime<Utc>, lifetime: Option<Duration>) -> Option<(ApiToken, String)> {
+        let expires_at = match lifetime {
+            Some(lifetime) => Some(now.checked_add_signed(lifetime)?),
+            None => None,
+        };
+        let id = random_hex(4);
+        let secret = format!("{}_{}_{}", TOKEN_PREFIX, id, random_hex(32));
+        let token = ApiToken {
+            id,
+            hash: hash(&secret),
+            created_at: now,
+            expires_at,
+        };
+        Some((token, secret))
+    }
+
+    /// The id of the token a secret claims to be, if it looks like one of our tokens.
+    pub fn id_of(secret: &str) -> Option<&str> {
+        let mut parts = secret.splitn(3, '_');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(TOKEN_PREFIX), Some(id), Some(_)) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Whether `secret` is this token and it has not expired at `now`.
+    pub fn accepts(&self, secret: &str, now: DateTime<Utc>) -> bool {
+        !self.is_expired(now) && self.hash == hash(secret)
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+fn hash(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+/// `bytes` random bytes from the operating system, hex-encoded.
+fn random_hex(bytes: usize) -> String {
+    let mut buffer = vec![0; bytes];
+    OsRng.fill_bytes(&mut buffer);
+    hex::encode(buffer)
+}
+
+#[cfg(test)]
+mod user_tests {
+    use crate::models::ApiToken;
+    use chrono::{Duration, Utc};
+
+    #[test]
+    fn a_token_only_accepts_its_own_secret_until_it_expires() {
+        // arrange
+        let now = Utc::now();
+        let (token, secret) = ApiToken::generate(now, Some(Duration::days(30))).unwrap();
+        let (_, other_secret) = ApiToken::generate(now, None).unwrap();
+
+        // act
+        let accepted = token.accepts(&secret, now);
+        let other_accepted = token.accepts(&other_secret, now);
+        let accepted_later = token.accepts(&secret, now + Duration::days(30));
+
+        // assert
+        assert!(accepted);
+        assert!(!other_accepted);
+        assert!(!accepted_later);
+        assert_eq!(ApiToken::id_of(&secret), Some(token.id.as_str()));
+        assert!(!token.hash.contains(&secret) && !secret.contains(&token.hash));
+    }
+
+    #[test]
+    fn a_token_expiring_beyond_the_representable_dates_is_not_generated() {
+        let token = ApiToken::generate(Utc::now(), Some(Duration::days(u32::MAX.into())));
+
+        assert!(token.is_none());
+    }
+}
@@ -0,0 +1,240 @@
+use crate::models::{Status, Ticket};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::str::FromStr;
+
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+/// A named way of looking at the tickets in the [TicketStore](TicketStore):
+/// which tickets to show, in which order and with which columns.
+pub struct View {
+    pub filter: TicketFilter,
+    pub sort: SortKey,
+    /// Reverse the ordering given by [sort](View::sort).
+    pub descending: bool,
+    /// Columns to display, from left to right.
+    pub columns: Vec<Column>,
+}
+
+#[derive(PartialEq, Debug, Clone, Default, Serialize, Deserialize)]
+/// Criteria a [Ticket](Ticket) has to satisfy to be part of a [View](View).
+pub struct TicketFilter {
+    /// Only keep tickets in one of these statuses. An empty list matches every status.
+    pub statuses: Vec<Status>,
+    /// Only keep tickets whose title or description contain this text (case-insensitive).
+    pub text: Option<String>,
+}
+
+/// The field used to order the tickets in a [View](View).
+#[derive(PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum SortKey {
+    Id,
+    Title,
+    Status,
+}
+
+/// A column that can be displayed when listing tickets through a [View](View).
+#[derive(PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum Column {
+    Id,
+    Title,
+    Status,
+    Description,
+    Comments,
+}
+
+impl TicketFilter {
+    /// Returns `true` if the ticket satisfies all the criteria of the filter.
+    pub fn matches(&self, ticket: &Ticket) -> bool {
+        if !self.statuses.is_empty() && !self.statuses.contains(&ticket.status) {
+            return false;
+        }
+        match &self.text {
+            Some(text) => {
+                let text = text.to_lowercase();
+                ticket.title.to_string().to_lowercase().contains(&text)
+                    || ticket.description.to_lowercase().contains(&text)
+            }
+            None => true,
+        }
+    }
+}
+
+impl View {
+    /// Keep the tickets matching the [filter](View::filter) and order them
+    /// according to the [sort key](View::sort).
+    pub fn apply<'a>(&self, tickets: Vec<&'a Ticket>) -> Vec<&'a Ticket> {
+        let mut tickets: Vec<&Ticket> = tickets
+            .into_iter()
+            .filter(|t| self.filter.matches(t))
+            .collect();
+        match self.sort {
+            SortKey::Id => tickets.sort_by_key(|t| t.id),
+            SortKey::Title => tickets.sort_by_key(|t| (t.title.to_string(), t.id)),
+            SortKey::Status => tickets.sort_by_key(|t| {
+                let rank = Status::all().iter().position(|s| *s == t.status);
+                (rank, t.id)
+            }),
+        }
+        if self.descending {
+            tickets.reverse();
+        }
+        tickets
+    }
+
+    /// Render the tickets as a table, one row per ticket and one column
+    /// for each entry in [columns](View::columns).
+    pub fn render(&self, tickets: &[&Ticket]) -> String {
+        let header: Vec<String> = self.columns.iter().map(|c| c.header()).collect();
+        let rows: Vec<Vec<String>> = tickets
+            .iter()
+            .map(|t| self.columns.iter().map(|c| c.cell(t)).collect())
+            .collect();
+
+        let widths: Vec<usize> = (0..self.columns.len())
+            .map(|i| {
+                rows.iter()
+                    .map(|row| row[i].chars().count())
+                    .chain(std::iter::once(header[i].chars().count()))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        std::iter::once(&header)
+            .chain(rows.iter())
+            .map(|row| {
+                row.iter()
+                    .zip(widths.iter())
+                    .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                    .collect::<Vec<String>>()
+                    .join("  ")
+                    .trim_end()
+                    .to_string()
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+impl std::fmt::Display for View {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "\tStatuses:{:?}\n\tText:{:?}\n\tSort:{:?}{}\n\tColumns:{:?}",
+            self.filter.statuses,
+            self.filter.text,
+            self.sort,
+            if self.descending { " (descending)" } else { "" },
+            self.columns
+        )
+    }
+}
+
+impl Column {
+    /// The columns shown when a view does not specify any.
+    pub fn defaults() -> Vec<Column> {
+        vec![Column::Id, Column::Title, Column::Status]
+    }
+
+    fn header(&self) -> String {
+        format!("{:?}", self).to_uppercase()
+    }
+
+    fn cell(&self, ticket: &Ticket) -> String {
+        match self {
+            Column::Id => ticket.id.to_string(),
+            Column::Title => ticket.title.to_string(),
+            Column::Status => format!("{:?}", ticket.status),
+            // Keep every ticket on a single row.
+            Column::Description => ticket.description.replace('\n', " "),
+            Column::Comments => ticket.comments.len().to_string(),
+        }
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "id" => Ok(SortKey::Id),
+            "title" => Ok(SortKey::Title),
+            "status" => Ok(SortKey::Status),
+            _ => Err(
+                "The sort key you specified is not valid. Valid values: id, title and status."
+                    .into(),
+            ),
+        }
+    }
+}
+
+impl FromStr for Column {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "id" => Ok(Column::Id),
+            "title" => Ok(Column::Title),
+            "status" => Ok(Column::Status),
+            "description" => Ok(Column::Description),
+            "comments" => Ok(Column::Comments),
+            _ => Err("The column you specified is not valid. \
+                 Valid values: id, title, status, description and comments."
+                .into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod view_tests {
+    use crate::models::{Column, SortKey, Status, Ticket, TicketFilter, View};
+
+    #[test]
+    fn filter_keeps_only_the_requested_statuses() {
+        // arrange
+        let filter = TicketFilter {
+            statuses: vec![Status::Blocked],
+            text: None,
+        };
+
+        // act & assert
+        assert!(filter.matches(&Ticket::sample(1, "Fix login", Status::Blocked)));
+        assert!(!filter.matches(&Ticket::sample(2, "Fix login", Status::ToDo)));
+    }
+
+    #[test]
+    fn filter_on_text_is_case_insensitive() {
+        // arrange
+        let filter = TicketFilter {
+            statuses: Vec::new(),
+            text: Some("LOGIN".to_string()),
+        };
+
+        // act & assert
+        assert!(filter.matches(&Ticket::sample(1, "Fix login", Status::ToDo)));
+        assert!(!filter.matches(&Ticket::sample(2, "Fix logout", Status::ToDo)));
+    }
+
+    #[test]
+    fn view_sorts_and_renders_the_selected_columns() {
+        // arrange
+        let tickets = [
+            Ticket::sample(1, "b", Status::Done),
+            Ticket::sample(2, "a", Status::ToDo),
+            Ticket::sample(3, "c", Status::InProgress),
+        ];
+        let view = View {
+            filter: TicketFilter::default(),
+            sort: SortKey::Title,
+            descending: true,
+            columns: vec![Column::Id, Column::Title],
+        };
+
+        // act
+        let selected = view.apply(tickets.iter().collect());
+        let rendered = view.render(&selected);
+
+        // assert
+        assert_eq!(rendered, "ID  TITLE\n3   c\n1   b\n2   a");
+    }
+}
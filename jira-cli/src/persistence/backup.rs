@@ -0,0 +1,184 @@
+use crate::error::JiraError;
+use crate::persistence::write_atomically;
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use std::path::{Path, PathBuf};
+
+const BACKUP_DIRECTORY: &str = "backups";
+const ID_FORMAT: &str = "%Y%m%d-%H%M%S-%6f";
+
+/// A rotating set of timestamped copies of a store file, taken before it is overwritten.
+///
+/// Backups live in a `backups` directory next to the store file.
+/// Only the most recent `retention` backups are kept.
+pub struct Backups {
+    store_path: PathBuf,
+    directory: PathBuf,
+    retention: usize,
+}
+
+/// A copy of the store file, as it was at `taken_at`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Backup {
+    pub id: String,
+    pub taken_at: DateTime<Utc>,
+    pub path: PathBuf,
+}
+
+impl Backups {
+    /// Keep up to `retention` backups of the file at `store_path`. A `retention` of 0 disables backups.
+    pub fn new(store_path: &Path, retention: usize) -> Self {
+        let directory = store_path
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join(BACKUP_DIRECTORY);
+        Self {
+            store_path: store_path.to_path_buf(),
+            directory,
+            retention,
+        }
+    }
+
+    /// Copy the store file as it is now, if there is one, dropping the oldest backups
+    /// beyond the retention.
+    pub fn take(&self) -> std::io::Result<()> {
+        if self.retention == 0 || !self.store_path.exists() {
+            return Ok(());
+        }
+        std::fs::create_dir_all(&self.directory)?;
+        let id = Utc::now().format(ID_FORMAT).to_string();
+        std::fs::copy(&self.store_path, self.backup_path(&id))?;
+
+        let backups = self.list();
+        let expired = backups.len().saturating_sub(self.retention);
+        for backup in &backups[..expired] {
+            std::fs::remove_file(&backup.path)?;
+        }
+        Ok(())
+    }
+
+    /// All the backups available, oldest first.
+    pub fn list(&self) -> Vec<Backup> {
+        let prefix = format!("{}.", self.file_name());
+        let entries = match std::fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+        let mut backups: Vec<Backup> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                let id = name.strip_prefix(&prefix)?;
+                let taken_at = NaiveDateTime::parse_from_str(id, ID_FORMAT).ok()?;
+                Some(Backup {
+                    id: id.to_string(),
+                    taken_at: Utc.from_utc_datetime(&taken_at),
+                    path: entry.path(),
+                })
+            })
+            .collect();
+        backups.sort_by_key(|backup| backup.taken_at);
+        backups
+    }
+
+    /// Replace the store file with the backup identified by `id`.
+    /// The current store file is backed up first, so that a restore can be undone.
+    pub fn restore(&self, id: &str) -> Result<(), JiraError> {
+        let backup = self
+            .list()
+            .into_iter()
+            .find(|backup| backup.id == id)
+            .ok_or_else(|| {
+                JiraError::NotFound(format!(
+                    "There is no backup with id {}. Use `jira-cli backup list` to see the available backups.",
+                    id
+                ))
+            })?;
+        let restore = || -> std::io::Result<()> {
+            let content = std::fs::read(&backup.path)?;
+            self.take()?;
+            write_atomically(&self.store_path, &content)
+        };
+        restore().map_err(|e| JiraError::io(&format!("Failed to restore backup {}", id), e))
+    }
+
+    fn file_name(&self) -> String {
+        self.store_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    fn backup_path(&self, id: &str) -> PathBuf {
+        self.directory.join(format!("{}.{}", self.file_name(), id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::JiraError;
+    use crate::persistence::Backups;
+
+    #[test]
+    fn only_the_most_recent_backups_are_kept() {
+        // arrange
+        let dir = tempfile::tempdir().unwrap();
+        let store_path = dir.path().join("store.yaml");
+        let backups = Backups::new(&store_path, 2);
+
+        // act
+        for content in &["one", "two", "three"] {
+            std::fs::write(&store_path, content).unwrap();
+            backups.take().unwrap();
+        }
+
+        // assert
+        let kept: Vec<String> = backups
+            .list()
+            .iter()
+            .map(|backup| std::fs::read_to_string(&backup.path).unwrap())
+            .collect();
+        assert_eq!(kept, vec!["two", "three"]);
+    }
+
+    #[test]
+    fn restoring_a_backup_keeps_a_copy_of_the_current_store() {
+        // arrange
+        let dir = tempfile::tempdir().unwrap();
+        let store_path = dir.path().join("store.yaml");
+        let backups = Backups::new(&store_path, 10);
+        std::fs::write(&store_path, "good").unwrap();
+        backups.take().unwrap();
+        std::fs::write(&store_path, "bad").unwrap();
+        let id = backups.list()[0].id.clone();
+
+        // act
+        backups.restore(&id).unwrap();
+
+        // assert
+        assert_eq!(std::fs::read_to_string(&store_path).unwrap(), "good");
+        let last = backups.list().pop().unwrap();
+        assert_eq!(std::fs::read_to_string(last.path).unwrap(), "bad");
+    }
+
+    #[test]
+    fn restoring_an_unknown_backup_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let backups = Backups::new(&dir.path().join("store.yaml"), 10);
+
+        let result = backups.restore("20200101-000000-000000");
+
+        assert!(matches!(result, Err(JiraError::NotFound(_))));
+    }
+
+    #[test]
+    fn no_backups_are_taken_with_no_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let store_path = dir.path().join("store.yaml");
+        std::fs::write(&store_path, "content").unwrap();
+        let backups = Backups::new(&store_path, 0);
+
+        backups.take().unwrap();
+
+        assert!(backups.list().is_empty());
+    }
+}
@@ -0,0 +1,339 @@
+use crate::error::JiraError;
+use crate::models::{Ticket, TicketId};
+use crate::persistence::YamlFileBackend;
+use crate::persistence::{write_atomically, Backups, StorageBackend, StoreLock, StoreLockError};
+use crate::store::{StoreSettings, TicketStore};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub const JOURNAL_SNAPSHOT: &str = "ticket_store.snapshot.yaml";
+pub const JOURNAL: &str = "ticket_store.journal";
+
+/// Stores a YAML snapshot of the [TicketStore](TicketStore) plus a journal of the changes
+/// made since the snapshot was taken, one JSON record per line.
+///
+/// Saving a single ticket appends a record to the journal instead of rewriting the whole store.
+/// Once the journal holds `compact_after` records it is folded into a new snapshot.
+pub struct JournalBackend {
+    snapshot: YamlFileBackend,
+    snapshot_path: PathBuf,
+    journal_path: PathBuf,
+    compact_after: usize,
+    entries: usize,
+    /// Store of the YAML backend to carry over on the first load, if nothing was saved yet.
+    yaml_path: Option<PathBuf>,
+}
+
+/// A line of the journal.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub record: JournalRecord,
+}
+
+/// A change made to the store.
+///
+/// Every record carries the full new state of what changed, so replaying a record twice
+/// (e.g. after a crash in the middle of a compaction) is harmless.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum JournalRecord {
+    SaveTicket { current_id: u64, ticket: Ticket },
+    DeleteTicket { id: TicketId },
+    SaveSettings { settings: StoreSettings },
+}
+
+impl JournalBackend {
+    /// Keep the snapshot and the journal in `directory`.
+    pub fn new(directory: &Path, compact_after: usize) -> Self {
+        let snapshot_path = directory.join(JOURNAL_SNAPSHOT);
+        Self {
+            snapshot: YamlFileBackend::new(snapshot_path.clone()),
+            snapshot_path,
+            journal_path: directory.join(JOURNAL),
+            compact_after,
+            entries: 0,
+            yaml_path: None,
+        }
+    }
+
+    /// Back up the snapshot every time the journal is compacted, keeping the last `retention` copies.
+    pub fn keep_backups(mut self, retention: usize) -> Self {
+        self.snapshot = self.snapshot.keep_backups(retention);
+        self
+    }
+
+    /// Carry over the store saved by the [YAML backend](YamlFileBackend) at `yaml_path`
+    /// when the store is loaded, if neither a snapshot nor a journal exist yet.
+    /// It happens on load, once the store is [locked](StorageBackend::lock).
+    pub fn carry_over_yaml(mut self, yaml_path: PathBuf) -> Self {
+        self.yaml_path = Some(yaml_path);
+        self
+    }
+
+    /// If neither a snapshot nor a journal exist yet, take the store saved by the
+    /// [YAML backend](YamlFileBackend) at `yaml_path` as the first snapshot.
+    /// Returns whether anything was migrated.
+    pub fn migrate_from_yaml(&mut self, yaml_path: &Path) -> Result<bool, JiraError> {
+        if self.snapshot_path.exists() || self.journal_path.exists() || !yaml_path.exists() {
+            return Ok(false);
+        }
+        let ticket_store = YamlFileBackend::new(yaml_path.to_path_buf()).load()?;
+        self.save(&ticket_store)?;
+        Ok(true)
+    }
+
+    /// Read the records of the journal, dropping a torn final record left behind by a crash
+    /// in the middle of an append.
+    fn read_journal(&self) -> Result<Vec<JournalEntry>, JiraError> {
+        let data = match std::fs::read(&self.journal_path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(JiraError::io("Failed to read the journal", e)),
+        };
+
+        let mut entries = Vec::new();
+        let mut offset = 0;
+        while offset < data.len() {
+            let end = data[offset..].iter().position(|b| *b == b'\n');
+            let parsed =
+                end.and_then(|end| serde_json::from_slice(&data[offset..offset + end]).ok());
+            match (end, parsed) {
+                (Some(end), Some(entry)) => {
+                    entries.push(entry);
+                    offset += end + 1;
+                }
+                // Only the last record can be incomplete: anything else is corruption.
+                (Some(end), None) if offset + end + 1 < data.len() => {
+                    return Err(JiraError::Parse(format!(
+                        "The journal {:?} is corrupted at byte {}.",
+                        self.journal_path, offset
+                    )));
+                }
+                _ => {
+                    eprintln!("Discarding an incomplete record at the end of the journal.");
+                    OpenOptions::new()
+                        .write(true)
+                        .open(&self.journal_path)
+                        .and_then(|file| file.set_len(offset as u64))
+                        .map_err(|e| JiraError::io("Failed to truncate the journal", e))?;
+                    break;
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Append a record to the journal, compacting it if it grew too long.
+    fn append(
+        &mut self,
+        ticket_store: &TicketStore,
+        record: JournalRecord,
+    ) -> Result<(), JiraError> {
+        if self.entries + 1 >= self.compact_after {
+            return self.save(ticket_store);
+        }
+        let entry = JournalEntry {
+            at: Utc::now(),
+            record,
+        };
+        let mut line = serde_json::to_vec(&entry).expect("Failed to serialize journal record.");
+        line.push(b'\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.journal_path)
+            .and_then(|mut file| {
+                file.write_all(&line)?;
+                file.sync_data()
+            })
+            .map_err(|e| JiraError::io("Failed to append to the journal", e))?;
+        self.entries += 1;
+        Ok(())
+    }
+}
+
+impl StorageBackend for JournalBackend {
+    fn lock(&self) -> Result<Option<StoreLock>, StoreLockError> {
+        StoreLock::acquire(&self.journal_path).map(Some)
+    }
+
+    /// Load the last snapshot and replay the journal on top of it.
+    fn load(&mut self) -> Result<TicketStore, JiraError> {
+        if let Some(yaml_path) = self.yaml_path.take() {
+            self.migrate_from_yaml(&yaml_path)?;
+        }
+        let (mut current_id, tickets, mut settings) = self.snapshot.load()?.into_parts();
+        let mut tickets: HashMap<TicketId, Ticket> =
+            tickets.into_iter().map(|t| (t.id, t)).collect();
+
+        let entries = self.read_journal()?;
+        self.entries = entries.len();
+        for entry in entries {
+            match entry.record {
+                JournalRecord::SaveTicket {
+                    current_id: id,
+                    ticket,
+                } => {
+                    current_id = current_id.max(id);
+                    tickets.insert(ticket.id, ticket);
+                }
+                JournalRecord::DeleteTicket { id } => {
+                    tickets.remove(&id);
+                }
+                JournalRecord::SaveSettings { settings: saved } => settings = saved,
+            }
+        }
+        Ok(TicketStore::from_parts(
+            current_id,
+            tickets.into_values().collect(),
+            settings,
+        ))
+    }
+
+    /// Write a new snapshot of the whole store and empty the journal.
+    fn save(&mut self, ticket_store: &TicketStore) -> Result<(), JiraError> {
+        self.snapshot.save(ticket_store)?;
+        // The snapshot is safely on disk: if we crash before the journal is emptied,
+        // its records are replayed again on load, which is harmless.
+        write_atomically(&self.journal_path, b"")
+            .map_err(|e| JiraError::io("Failed to empty the journal", e))?;
+        self.entries = 0;
+        Ok(())
+    }
+
+    /// Upgrade the snapshot: journal records only ever hold data written by this version.
+    fn migrate(&mut self, dry_run: bool) -> Result<Vec<String>, JiraError> {
+        self.snapshot.migrate(dry_run)
+    }
+
+    fn backups(&self) -> Option<&Backups> {
+        self.snapshot.backups()
+    }
+
+    /// Roll the snapshot back to a backup, dropping the journal.
+    fn restore(&mut self, id: &str) -> Result<(), JiraError> {
+        // Fold the journal into the snapshot first, so that restoring the backup
+        // keeps a copy of the store as it is now.
+        let ticket_store = self.load()?;
+        self.save(&ticket_store)?;
+        self.snapshot.restore(id)
+    }
+
+    fn save_ticket(&mut self, ticket_store: &TicketStore, id: TicketId) -> Result<(), JiraError> {
+        match ticket_store.get(id) {
+            Some(ticket) => {
+                let record = JournalRecord::SaveTicket {
+                    current_id: ticket_store.current_id(),
+                    ticket: ticket.clone(),
+                };
+                self.append(ticket_store, record)
+            }
+            None => self.delete_ticket(ticket_store, id),
+        }
+    }
+
+    fn delete_ticket(&mut self, ticket_store: &TicketStore, id: TicketId) -> Result<(), JiraError> {
+        self.append(ticket_store, JournalRecord::DeleteTicket { id })
+    }
+
+    fn save_settings(&mut self, ticket_store: &TicketStore) -> Result<(), JiraError> {
+        let record = JournalRecord::SaveSettings {
+            settings: ticket_store.settings(),
+        };
+        self.append(ticket_store, record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::{Status, TicketDraft, Title};
+    use crate::persistence::{JournalBackend, StorageBackend, JOURNAL};
+    use crate::store::TicketStore;
+
+    fn draft(title: &str) -> TicketDraft {
+        TicketDraft {
+            title: Title::new(title.to_string()).unwrap(),
+            description: "".to_string(),
+        }
+    }
+
+    #[test]
+    fn the_journal_is_replayed_on_top_of_the_snapshot() {
+        // arrange
+        let dir = tempfile::tempdir().unwrap();
+        let mut backend = JournalBackend::new(dir.path(), 100);
+        let mut ticket_store = TicketStore::new();
+        let kept = ticket_store.create(draft("Kept"));
+        let deleted = ticket_store.create(draft("Deleted"));
+        backend.save(&ticket_store).unwrap();
+
+        // act
+        ticket_store.force_ticket_status(kept, Status::Done);
+        backend.save_ticket(&ticket_store, kept).unwrap();
+        ticket_store.delete(deleted);
+        backend.delete_ticket(&ticket_store, deleted).unwrap();
+        let created = ticket_store.create(draft("Created"));
+        backend.save_ticket(&ticket_store, created).unwrap();
+        let loaded = JournalBackend::new(dir.path(), 100).load().unwrap();
+
+        // assert
+        assert_eq!(loaded.get(kept).unwrap().status, Status::Done);
+        assert_eq!(loaded.get(deleted), None);
+        assert_eq!(loaded.get(created), ticket_store.get(created));
+        assert_eq!(loaded.current_id(), ticket_store.current_id());
+    }
+
+    #[test]
+    fn the_journal_is_compacted_after_enough_entries() {
+        // arrange
+        let dir = tempfile::tempdir().unwrap();
+        let mut backend = JournalBackend::new(dir.path(), 3);
+        let mut ticket_store = TicketStore::new();
+
+        // act
+        for title in &["One", "Two", "Three"] {
+            let id = ticket_store.create(draft(title));
+            backend.save_ticket(&ticket_store, id).unwrap();
+        }
+
+        // assert
+        let journal = std::fs::read_to_string(dir.path().join(JOURNAL)).unwrap();
+        assert!(journal.is_empty());
+        assert_eq!(
+            JournalBackend::new(dir.path(), 3)
+                .load()
+                .unwrap()
+                .list()
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn a_torn_final_record_is_discarded() {
+        // arrange
+        let dir = tempfile::tempdir().unwrap();
+        let mut backend = JournalBackend::new(dir.path(), 100);
+        let mut ticket_store = TicketStore::new();
+        let id = ticket_store.create(draft("Saved"));
+        backend.save_ticket(&ticket_store, id).unwrap();
+        let path = dir.path().join(JOURNAL);
+        let mut journal = std::fs::read(&path).unwrap();
+        let complete = journal.len();
+        journal.extend_from_slice(b"{\"at\":\"2020-");
+        std::fs::write(&path, journal).unwrap();
+
+        // act
+        let loaded = JournalBackend::new(dir.path(), 100).load().unwrap();
+
+        // assert
+        assert!(loaded.get(id).is_some());
+        assert_eq!(std::fs::read(&path).unwrap().len(), complete);
+    }
+}
@@ -0,0 +1,91 @@
+use fs2::FileExt;
+use std::error::Error;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+/// An advisory lock on a ticket store, preventing two `jira-cli` processes
+/// from loading, modifying and saving the same store at the same time.
+///
+/// The lock is released when the value is dropped.
+#[derive(Debug)]
+pub struct StoreLock {
+    // Keeping the file open keeps the lock alive.
+    _file: File,
+}
+
+#[derive(Debug)]
+/// Error if the lock on a ticket store cannot be acquired
+pub struct StoreLockError {
+    path: PathBuf,
+    source: std::io::Error,
+}
+
+impl StoreLock {
+    /// Take the lock for the store saved at `store_path`, using a `.lock` file next to it.
+    /// Fails straight away, instead of waiting, if another process holds the lock.
+    pub fn acquire(store_path: &Path) -> Result<StoreLock, StoreLockError> {
+        let mut path = store_path.as_os_str().to_owned();
+        path.push(".lock");
+        let path = PathBuf::from(path);
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(&path)
+            .and_then(|file| file.try_lock_exclusive().map(|_| file))
+            .map_err(|source| StoreLockError { path, source })?;
+        Ok(StoreLock { _file: file })
+    }
+}
+
+impl StoreLockError {
+    /// Returns `true` if the lock is held by another process, rather than impossible to take.
+    pub fn is_contended(&self) -> bool {
+        self.source.kind() == fs2::lock_contended_error().kind()
+    }
+}
+
+impl fmt::Display for StoreLockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_contended() {
+            write!(
+                f,
+                "Another jira-cli process is using the ticket store (lock held on {:?}). \
+                 Try again once it has finished.",
+                self.path
+            )
+        } else {
+            write!(
+                f,
+                "Failed to lock the ticket store ({:?}): {}",
+                self.path, self.source
+            )
+        }
+    }
+}
+
+impl Error for StoreLockError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::persistence::StoreLock;
+
+    #[test]
+    fn a_store_cannot_be_locked_twice() {
+        // arrange
+        let dir = tempfile::tempdir().unwrap();
+        let store_path = dir.path().join("store.yaml");
+        let lock = StoreLock::acquire(&store_path).unwrap();
+
+        // act
+        let second = StoreLock::acquire(&store_path);
+
+        // assert
+        let error = second.unwrap_err();
+        assert!(error.is_contended());
+        assert!(error.to_string().starts_with("Another jira-cli process"));
+        drop(lock);
+        assert!(StoreLock::acquire(&store_path).is_ok());
+    }
+}
@@ -0,0 +1,28 @@
+use crate::error::JiraError;
+use crate::persistence::StorageBackend;
+use crate::store::TicketStore;
+
+/// Keeps the [TicketStore](TicketStore) in memory: nothing survives the end of the process.
+///
+/// Useful for testing code that needs a [StorageBackend](StorageBackend).
+#[derive(Default)]
+pub struct InMemoryBackend {
+    saved: Option<TicketStore>,
+}
+
+impl InMemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl StorageBackend for InMemoryBackend {
+    fn load(&mut self) -> Result<TicketStore, JiraError> {
+        Ok(self.saved.clone().unwrap_or_else(TicketStore::new))
+    }
+
+    fn save(&mut self, ticket_store: &TicketStore) -> Result<(), JiraError> {
+        self.saved = Some(ticket_store.clone());
+        Ok(())
+    }
+}
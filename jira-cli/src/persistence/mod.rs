@@ -0,0 +1,179 @@
+use crate::config::{BackendKind, Config};
+use crate::error::JiraError;
+use crate::models::TicketId;
+use crate::store::TicketStore;
+use directories::ProjectDirs;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+mod backup;
+mod journal;
+mod lock;
+mod memory;
+mod remote;
+pub mod schema;
+mod sqlite;
+mod yaml;
+
+pub use backup::*;
+pub use journal::*;
+pub use lock::*;
+pub use memory::*;
+pub use remote::*;
+pub use sqlite::*;
+pub use yaml::*;
+
+// `PROJECT_NAME`, `ORGANISATION_NAME` and `QUALIFIER` are used to determine
+// where to store configuration files and secrets for an application
+// according to the convention of the underlying operating system.
+//
+// `qualifier_name` is only relevant for MacOS - we leave it blank.
+const PROJECT_NAME: &str = "IronJIRA";
+const ORGANISATION_NAME: &str = "RustLDNUserGroup";
+const QUALIFIER: &str = "";
+
+/// Where and how a [TicketStore](TicketStore) is persisted between two runs of the CLI.
+///
+/// `load` and `save` deal with the whole store.
+/// The incremental operations are called after a single ticket has changed: backends
+/// that can update a ticket on its own should override them, the default implementations
+/// fall back to saving the whole store.
+pub trait StorageBackend {
+    /// Take an exclusive lock on the store, to be held for a whole load-modify-save cycle.
+    /// Returns `None` for backends that are not shared between processes.
+    fn lock(&self) -> Result<Option<StoreLock>, StoreLockError> {
+        Ok(None)
+    }
+
+    /// Load the store. If nothing was saved yet, a brand new one is returned.
+    fn load(&mut self) -> Result<TicketStore, JiraError>;
+
+    /// Save the whole store.
+    fn save(&mut self, ticket_store: &TicketStore) -> Result<(), JiraError>;
+
+    /// Save a ticket that was just created or updated in the store.
+    fn save_ticket(&mut self, ticket_store: &TicketStore, _id: TicketId) -> Result<(), JiraError> {
+        self.save(ticket_store)
+    }
+
+    /// Persist the removal of a ticket that was just deleted from the store.
+    fn delete_ticket(
+        &mut self,
+        ticket_store: &TicketStore,
+        _id: TicketId,
+    ) -> Result<(), JiraError> {
+        self.save(ticket_store)
+    }
+
+    /// Save the views and configuration of the store, which just changed.
+    fn save_settings(&mut self, ticket_store: &TicketStore) -> Result<(), JiraError> {
+        self.save(ticket_store)
+    }
+
+    /// Upgrade the saved store to the format written by this version of the CLI, e.g. the
+    /// current [schema version](schema::SCHEMA_VERSION) of YAML documents, or only report what
+    /// would change if `dry_run` is set.
+    /// Returns a description of every change, empty if the store is up to date.
+    fn migrate(&mut self, _dry_run: bool) -> Result<Vec<String>, JiraError> {
+        Ok(Vec::new())
+    }
+
+    /// The backups taken before the store is saved, for backends that keep them.
+    fn backups(&self) -> Option<&Backups> {
+        None
+    }
+
+    /// Roll the store back to the backup identified by `id`.
+    fn restore(&mut self, id: &str) -> Result<(), JiraError> {
+        match self.backups() {
+            Some(backups) => backups.restore(id),
+            None => Err(JiraError::Validation(
+                "Backups are not supported by the configured storage backend.".to_string(),
+            )),
+        }
+    }
+}
+
+/// Build the storage backend selected in the configuration, or the one of the
+/// [remote](Config::remote) server if there is one.
+pub fn backend(config: &Config) -> Result<Box<dyn StorageBackend>, JiraError> {
+    if let Some(remote) = &config.remote {
+        let backend = RemoteBackend::new(remote);
+        return Ok(Box::new(match &config.token {
+            Some(token) => backend.with_token(token),
+            None => backend,
+        }));
+    }
+    let store_dir = store_dir(config)?;
+    let backend: Box<dyn StorageBackend> = match config.backend {
+        BackendKind::Yaml => Box::new(
+            YamlFileBackend::new(store_dir.join(TICKET_STORE))
+                .keep_backups(config.backups.retention),
+        ),
+        // Carry over the tickets of users switching from the YAML backend.
+        BackendKind::Sqlite => Box::new(
+            SqliteBackend::open(store_dir.join(SQLITE_STORE))?
+                .carry_over_yaml(store_dir.join(TICKET_STORE)),
+        ),
+        BackendKind::Journal => Box::new(
+            JournalBackend::new(&store_dir, config.journal.compact_after)
+                .keep_backups(config.backups.retention)
+                .carry_over_yaml(store_dir.join(TICKET_STORE)),
+        ),
+        BackendKind::Memory => Box::new(InMemoryBackend::new()),
+    };
+    Ok(backend)
+}
+
+/// Replace the content of a file without ever leaving it half-written: the content is
+/// written to a temporary file next to it, flushed to disk and then renamed over the original.
+pub fn write_atomically(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let mut temporary_path = path.as_os_str().to_owned();
+    temporary_path.push(".tmp");
+    let temporary_path = PathBuf::from(temporary_path);
+
+    let mut file = File::create(&temporary_path)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(&temporary_path, path)?;
+
+    // Flush the directory as well, to make sure the rename itself survives a crash.
+    #[cfg(unix)]
+    {
+        if let Some(directory) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            File::open(directory)?.sync_all()?;
+        }
+    }
+    Ok(())
+}
+
+/// The directories used by IronJira, according to the convention of the underlying operating system.
+pub fn project_dirs() -> Result<ProjectDirs, JiraError> {
+    // The operation could fail if some OS environment variables are not set (e.g. $HOME)
+    ProjectDirs::from(QUALIFIER, ORGANISATION_NAME, PROJECT_NAME).ok_or_else(|| {
+        JiraError::Io("Failed to determine path of the configuration directory.".to_string())
+    })
+}
+
+/// The directory holding the ticket store: the one set in the configuration if any,
+/// otherwise the data directory of IronJira according to the convention of the underlying
+/// operating system.
+pub(crate) fn store_dir(config: &Config) -> Result<PathBuf, JiraError> {
+    let store_dir = match &config.store {
+        Some(store) => store.clone(),
+        None => project_dirs()?.data_dir().to_path_buf(),
+    };
+
+    // Create the directory, if missing.
+    // It also takes care of creating intermediate sub-directory, if necessary.
+    std::fs::create_dir_all(&store_dir).map_err(|e| {
+        JiraError::io(
+            &format!("Failed to create the store directory {:?}", store_dir),
+            e,
+        )
+    })?;
+
+    Ok(store_dir)
+}
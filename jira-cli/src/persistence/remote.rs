@@ -0,0 +1,268 @@
+use crate::error::JiraError;
+use crate::models::{Ticket, TicketId};
+use crate::persistence::StorageBackend;
+use crate::server::{DeleteTicket, PutSettings, PutTicket, StoreSnapshot};
+use crate::store::{StoreSettings, TicketStore};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Works on the store served by `jira-cli serve` on another machine, over its API.
+///
+/// The whole store is downloaded on load. Saving uploads only the tickets and settings that
+/// changed since, together with the version they were loaded at: the server refuses the change
+/// with a [Conflict](JiraError::Conflict) if someone else changed them in the meantime.
+pub struct RemoteBackend {
+    /// Address of the server, e.g. `http://jira.local:8080`.
+    url: String,
+    agent: ureq::Agent,
+    /// The tickets as they were loaded, i.e. as they are on the server.
+    loaded: HashMap<TicketId, Ticket>,
+    /// The settings as they were loaded.
+    settings: StoreSettings,
+    /// API token of the user the requests are made as, if the server needs one.
+    token: Option<String>,
+}
+
+impl RemoteBackend {
+    /// Use the store served at `url`, e.g. `http://jira.local:8080`.
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(30))
+                .build(),
+            loaded: HashMap::new(),
+            settings: StoreSettings::default(),
+            token: None,
+        }
+    }
+
+    /// Authenticate the requests with an API token, as created by `jira-cli token create`
+    /// on the server.
+    pub fn with_token(mut self, token: &str) -> Self {
+        self.token = Some(token.to_string());
+        self
+    }
+
+    /// Send a request to the server, turning its error responses back into [JiraError]s.
+    fn send(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<&impl Serialize>,
+    ) -> Result<String, JiraError> {
+        let url = format!("{}{}", self.url, path);
+        let mut request = self
+            .agent
+            .request(method, &url)
+            .set("Content-Type", "application/json");
+        if let Some(token) = &self.token {
+            request = request.set("Authorization", &format!("Bearer {}", token));
+        }
+        let result = match body {
+            // Our own types always serialise to JSON.
+            Some(body) => request.send_string(&serde_json::to_string(body).unwrap_or_default()),
+            None => request.call(),
+        };
+        let response = match result {
+            Ok(response) => response,
+            Err(ureq::Error::Status(status, response)) => {
+                let message = response
+                    .into_string()
+                    .ok()
+                    .and_then(|body| serde_json::from_str::<serde_json::Value>(&body).ok())
+                    .and_then(|body| body["error"].as_str().map(str::to_string))
+                    .unwrap_or_else(|| format!("The server answered {} {}", status, url));
+                return Err(match status {
+                    400 => JiraError::Validation(message),
+                    401 => JiraError::Unauthorized(message),
+                    404 => JiraError::NotFound(message),
+                    409 => JiraError::Conflict(message),
+                    _ => JiraError::Io(message),
+                });
+            }
+            Err(e) => {
+                return Err(JiraError::io(
+                    &format!("Failed to reach the server at {}", self.url),
+                    e,
+                ))
+            }
+        };
+        response
+            .into_string()
+            .map_err(|e| JiraError::io(&format!("Failed to read the answer of {}", url), e))
+    }
+}
+
+impl StorageBackend for RemoteBackend {
+    fn load(&mut self) -> Result<TicketStore, JiraError> {
+        let body = self.send("GET", "/store", None::<&()>)?;
+        let snapshot: StoreSnapshot = serde_json::from_str(&body).map_err(|e| {
+            JiraError::parse(&format!("Failed to parse the store of {}", self.url), e)
+        })?;
+        self.loaded = snapshot.tickets.iter().map(|t| (t.id, t.clone())).collect();
+        self.settings = snapshot.settings.clone();
+        Ok(TicketStore::from_parts(
+            snapshot.current_id,
+            snapshot.tickets,
+            snapshot.settings,
+        ))
+    }
+
+    /// Upload every ticket that changed, and the settings if they did.
+    fn save(&mut self, ticket_store: &TicketStore) -> Result<(), JiraError> {
+        let mut ids: Vec<TicketId> = ticket_store.list().iter().map(|t| t.id).collect();
+        ids.extend(
+            self.loaded
+                .keys()
+                .filter(|id| ticket_store.get(**id).is_none()),
+        );
+        ids.sort_unstable();
+        for id in ids {
+            self.save_ticket(ticket_store, id)?;
+        }
+        self.save_settings(ticket_store)
+    }
+
+    fn save_ticket(&mut self, ticket_store: &TicketStore, id: TicketId) -> Result<(), JiraError> {
+        let ticket = match ticket_store.get(id) {
+            Some(ticket) => ticket,
+            None => return self.delete_ticket(ticket_store, id),
+        };
+        if self.loaded.get(&id) == Some(ticket) {
+            return Ok(());
+        }
+        let body = PutTicket {
+            expected: self.loaded.get(&id).cloned(),
+            ticket: ticket.clone(),
+        };
+        let saved = self.send("PUT", &format!("/store/tickets/{}", id), Some(&body))?;
+        // Keep the ticket as the server saved it, e.g. with the author of new comments.
+        let saved: Ticket = serde_json::from_str(&saved).map_err(|e| {
+            JiraError::parse(
+                &format!("Failed to parse ticket {} from {}", id, self.url),
+                e,
+            )
+        })?;
+        self.loaded.insert(id, saved);
+        Ok(())
+    }
+
+    fn delete_ticket(
+        &mut self,
+        _ticket_store: &TicketStore,
+        id: TicketId,
+    ) -> Result<(), JiraError> {
+        let expected = match self.loaded.get(&id) {
+            Some(ticket) => ticket.clone(),
+            // Never uploaded: there is nothing to delete on the server.
+            None => return Ok(()),
+        };
+        let body = DeleteTicket { expected };
+        self.send("DELETE", &format!("/store/tickets/{}", id), Some(&body))?;
+        self.loaded.remove(&id);
+        // The server removed the relations to the ticket as well.
+        for ticket in self.loaded.values_mut() {
+            ticket.related.retain(|related| *related != id);
+        }
+        Ok(())
+    }
+
+    fn save_settings(&mut self, ticket_store: &TicketStore) -> Result<(), JiraError> {
+        let settings = ticket_store.settings();
+        if settings == self.settings {
+            return Ok(());
+        }
+        let body = PutSettings {
+            expected: self.settings.clone(),
+            settings: settings.clone(),
+        };
+        self.send("PUT", "/store/settings", Some(&body))?;
+        self.settings = settings;
+        Ok(())
+    }
+
+    fn migrate(&mut self, _dry_run: bool) -> Result<Vec<String>, JiraError> {
+        Err(JiraError::Validation(
+            "A remote store is migrated by running `jira-cli migrate` on its server.".to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::JiraError;
+    use crate::models::{Status, TicketDraft, Title};
+    use crate::persistence::{InMemoryBackend, RemoteBackend, StorageBackend};
+    use crate::server::{serve, ServerState};
+    use crate::store::TicketStore;
+
+    /// Serve an empty in-memory store in the background, returning its address.
+    fn server() -> String {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let address = format!("127.0.0.1:{}", port);
+        let served = address.clone();
+        std::thread::spawn(move || {
+            let mut ticket_store = TicketStore::new();
+            let mut backend = InMemoryBackend::new();
+            serve(
+                &served,
+                &mut ServerState::new(&mut ticket_store, &mut backend),
+            )
+        });
+        // Wait for the server to listen.
+        for _ in 0..50 {
+            if std::net::TcpStream::connect(&address).is_ok() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        format!("http://{}", address)
+    }
+
+    #[test]
+    fn changes_are_shared_and_concurrent_edits_refused() {
+        // arrange
+        let url = server();
+        let mut alice = RemoteBackend::new(&url);
+        let mut bob = RemoteBackend::new(&url);
+        let mut alice_store = alice.load().unwrap();
+        let id = alice_store.create(TicketDraft {
+            title: Title::new("Fix login".to_string()).unwrap(),
+            description: String::new(),
+        });
+        alice.save_ticket(&alice_store, id).unwrap();
+        let mut bob_store = bob.load().unwrap();
+
+        // act
+        alice_store.force_ticket_status(id, Status::InProgress);
+        alice.save_ticket(&alice_store, id).unwrap();
+        bob_store.force_ticket_status(id, Status::Done);
+        let refused = bob.save_ticket(&bob_store, id);
+
+        // assert
+        assert_eq!(bob_store.get(id).unwrap().title.to_string(), "Fix login");
+        assert!(matches!(refused, Err(JiraError::Conflict(_))));
+        let reloaded = RemoteBackend::new(&url).load().unwrap();
+        assert_eq!(reloaded.get(id).unwrap().status, Status::InProgress);
+        assert_eq!(reloaded.current_id(), id);
+    }
+
+    #[test]
+    fn an_unreachable_server_is_an_io_error() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let result = RemoteBackend::new(&format!("http://127.0.0.1:{}", port)).load();
+
+        assert!(matches!(result, Err(JiraError::Io(_))));
+    }
+}
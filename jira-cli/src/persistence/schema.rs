@@ -0,0 +1,209 @@
+use crate::store::TicketStore;
+use serde_yaml::{Mapping, Value};
+use std::error::Error;
+use std::fmt;
+
+/// Version of the on-disk format of a [TicketStore](TicketStore) written by this version of the CLI.
+///
+/// Documents written before the version header was introduced are version 1.
+/// Bump it, and add a function to [MIGRATIONS](MIGRATIONS), every time the format changes
+/// in a way that older documents cannot be read as they are.
+pub const SCHEMA_VERSION: u64 = 2;
+
+const VERSION_KEY: &str = "version";
+
+/// Upgrades a document by one version, describing every change it makes.
+type Migration = fn(&mut Mapping, &mut Vec<String>);
+
+/// `MIGRATIONS[i]` upgrades a document from version `i + 1` to version `i + 2`.
+const MIGRATIONS: [Migration; 1] = [add_defaults];
+
+#[derive(PartialEq, Debug, Clone)]
+/// Error if a serialised store cannot be read
+pub struct SchemaError {
+    details: String,
+}
+
+impl SchemaError {
+    fn new(msg: &str) -> SchemaError {
+        SchemaError {
+            details: msg.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+impl Error for SchemaError {
+    fn description(&self) -> &str {
+        &self.details
+    }
+}
+
+/// Bring a serialised store up to [SCHEMA_VERSION](SCHEMA_VERSION), in place.
+/// Returns a description of every change made, empty if the document was already up to date.
+pub fn upgrade(document: &mut Value) -> Result<Vec<String>, SchemaError> {
+    let mapping = document
+        .as_mapping_mut()
+        .ok_or_else(|| SchemaError::new("The ticket store is not a YAML mapping."))?;
+    let version = match mapping.get(&key(VERSION_KEY)) {
+        None => 1,
+        Some(version) => version
+            .as_u64()
+            .ok_or_else(|| SchemaError::new("The version of the ticket store is not a number."))?,
+    };
+    if version > SCHEMA_VERSION {
+        return Err(SchemaError::new(&format!(
+            "The ticket store was written by a newer version of jira-cli (schema version {}, \
+             this version supports up to {}). Please upgrade jira-cli.",
+            version, SCHEMA_VERSION
+        )));
+    }
+
+    let mut changes = Vec::new();
+    for (from, migration) in MIGRATIONS
+        .iter()
+        .enumerate()
+        .skip(version.max(1) as usize - 1)
+    {
+        migration(mapping, &mut changes);
+        mapping.insert(key(VERSION_KEY), Value::from(from as u64 + 2));
+        changes.push(format!("Set the schema version to {}", from + 2));
+    }
+    Ok(changes)
+}
+
+/// Read a serialised store, upgrading it from older versions if needed.
+pub fn parse(data: &str) -> Result<TicketStore, SchemaError> {
+    let mut document: Value = serde_yaml::from_str(data)
+        .map_err(|e| SchemaError::new(&format!("Failed to parse serialised data: {}", e)))?;
+    upgrade(&mut document)?;
+    if let Some(mapping) = document.as_mapping_mut() {
+        mapping.remove(&key(VERSION_KEY));
+    }
+    serde_yaml::from_value(document)
+        .map_err(|e| SchemaError::new(&format!("Failed to parse serialised data: {}", e)))
+}
+
+/// Serialise a store, stamped with the current [SCHEMA_VERSION](SCHEMA_VERSION).
+pub fn to_string(ticket_store: &TicketStore) -> String {
+    let store = serde_yaml::to_value(ticket_store).expect("Failed to serialize tickets");
+    // The version goes first, where it is easy to spot when opening the file.
+    let mut document = Mapping::new();
+    document.insert(key(VERSION_KEY), Value::from(SCHEMA_VERSION));
+    if let Value::Mapping(store) = store {
+        document.extend(store);
+    }
+    serde_yaml::to_string(&document).expect("Failed to serialize tickets")
+}
+
+fn key(name: &str) -> Value {
+    Value::String(name.to_string())
+}
+
+/// Version 1 to 2: spell out the views, configuration, creation date and status history
+/// that were missing from stores written before those features existed.
+///
+/// Both versions load the same tickets, as the missing fields have defaults: the migration
+/// only writes the document the way this version would, stamped with its version so that
+/// later migrations can tell the formats apart.
+fn add_defaults(store: &mut Mapping, changes: &mut Vec<String>) {
+    if !store.contains_key(&key("views")) {
+        store.insert(key("views"), Value::Mapping(Mapping::new()));
+        changes.push("Add an empty list of saved views".to_string());
+    }
+    if !store.contains_key(&key("config")) {
+        store.insert(key("config"), Value::Mapping(Mapping::new()));
+        changes.push("Add the default store configuration".to_string());
+    }
+    let tickets = store.get_mut(&key("data")).and_then(Value::as_mapping_mut);
+    for (id, ticket) in tickets.into_iter().flat_map(|tickets| tickets.iter_mut()) {
+        let id = id.as_u64().unwrap_or_default();
+        if let Some(ticket) = ticket.as_mapping_mut() {
+            if !ticket.contains_key(&key("created_at")) {
+                ticket.insert(key("created_at"), Value::Null);
+                changes.push(format!("Ticket {}: mark the creation date as unknown", id));
+            }
+            if !ticket.contains_key(&key("history")) {
+                ticket.insert(key("history"), Value::Sequence(Vec::new()));
+                changes.push(format!("Ticket {}: add an empty status history", id));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::{TicketDraft, Title};
+    use crate::persistence::schema::{parse, to_string, upgrade, SCHEMA_VERSION};
+    use crate::store::TicketStore;
+
+    const VERSION_1: &str = "
+current_id: 1
+data:
+  1:
+    id: 1
+    title:
+      title: Fix login
+    description: It is broken
+    status: ToDo
+    comments: []
+";
+
+    #[test]
+    fn an_unversioned_store_is_upgraded() {
+        // arrange
+        let mut document = serde_yaml::from_str(VERSION_1).unwrap();
+
+        // act
+        let changes = upgrade(&mut document).unwrap();
+
+        // assert
+        assert_eq!(
+            changes,
+            vec![
+                "Add an empty list of saved views",
+                "Add the default store configuration",
+                "Ticket 1: mark the creation date as unknown",
+                "Ticket 1: add an empty status history",
+                "Set the schema version to 2",
+            ]
+        );
+        assert_eq!(document["version"].as_u64(), Some(SCHEMA_VERSION));
+        assert_eq!(
+            parse(VERSION_1).unwrap().get(1).unwrap().description,
+            "It is broken"
+        );
+    }
+
+    #[test]
+    fn a_current_store_needs_no_changes() {
+        // arrange
+        let mut ticket_store = TicketStore::new();
+        let id = ticket_store.create(TicketDraft {
+            title: Title::new("Fix login".to_string()).unwrap(),
+            description: "It is broken".to_string(),
+        });
+        let data = to_string(&ticket_store);
+
+        // act
+        let changes = upgrade(&mut serde_yaml::from_str(&data).unwrap()).unwrap();
+
+        // assert
+        assert!(changes.is_empty());
+        assert_eq!(parse(&data).unwrap().get(id), ticket_store.get(id));
+    }
+
+    #[test]
+    fn a_store_from_a_newer_version_is_rejected() {
+        let data = format!("version: {}\ncurrent_id: 0\ndata: {{}}", SCHEMA_VERSION + 1);
+
+        let result = parse(&data);
+
+        assert!(result.is_err());
+    }
+}
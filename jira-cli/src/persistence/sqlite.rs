@@ -0,0 +1,656 @@
+use crate::error::JiraError;
+use crate::models::{Comment, LinkedCommit, Status, StatusChange, Ticket, TicketId, Title};
+use crate::persistence::{StorageBackend, StoreLock, StoreLockError, YamlFileBackend};
+use crate::store::{StoreSettings, TicketStore};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use std::path::{Path, PathBuf};
+
+pub const SQLITE_STORE: &str = "ticket_store.sqlite3";
+
+/// Version of the database schema written by this version of the CLI, kept in
+/// `PRAGMA user_version`. Databases created before the schema was versioned are version 0.
+///
+/// Bump it, and extend [upgrade](SqliteBackend::upgrade), every time a table changes.
+pub const SQLITE_SCHEMA_VERSION: i64 = 1;
+
+const SCHEMA: &str = "
+    PRAGMA foreign_keys = ON;
+    CREATE TABLE IF NOT EXISTS tickets (
+        id          INTEGER PRIMARY KEY,
+        title       TEXT NOT NULL,
+        description TEXT NOT NULL,
+        status       TEXT NOT NULL,
+        created_at   TEXT,
+        external_key TEXT
+    );
+    CREATE TABLE IF NOT EXISTS comments (
+        ticket_id INTEGER NOT NULL REFERENCES tickets (id) ON DELETE CASCADE,
+        position  INTEGER NOT NULL,
+        body      TEXT NOT NULL,
+        author    TEXT,
+        PRIMARY KEY (ticket_id, position)
+    );
+    CREATE TABLE IF NOT EXISTS status_changes (
+        ticket_id   INTEGER NOT NULL REFERENCES tickets (id) ON DELETE CASCADE,
+        position    INTEGER NOT NULL,
+        from_status TEXT NOT NULL,
+        to_status   TEXT NOT NULL,
+        at          TEXT NOT NULL,
+        author      TEXT,
+        PRIMARY KEY (ticket_id, position)
+    );
+    CREATE TABLE IF NOT EXISTS commits (
+        ticket_id INTEGER NOT NULL REFERENCES tickets (id) ON DELETE CASCADE,
+        position  INTEGER NOT NULL,
+        hash      TEXT NOT NULL,
+        author    TEXT NOT NULL,
+        date      TEXT NOT NULL,
+        subject   TEXT NOT NULL,
+        PRIMARY KEY (ticket_id, position)
+    );
+    -- Relations go both ways: each one is stored once for each of its tickets.
+    CREATE TABLE IF NOT EXISTS relations (
+        ticket_id  INTEGER NOT NULL REFERENCES tickets (id) ON DELETE CASCADE,
+        position   INTEGER NOT NULL,
+        related_id INTEGER NOT NULL,
+        PRIMARY KEY (ticket_id, position)
+    );
+    -- A single row holding the id of the last ticket created.
+    CREATE TABLE IF NOT EXISTS sequence (
+        id         INTEGER PRIMARY KEY CHECK (id = 0),
+        current_id INTEGER NOT NULL
+    );
+    -- A single row holding the views and configuration of the store, as YAML.
+    CREATE TABLE IF NOT EXISTS settings (
+        id   INTEGER PRIMARY KEY CHECK (id = 0),
+        yaml TEXT NOT NULL
+    );
+";
+
+/// Stores tickets in an embedded SQLite database, one row per ticket.
+///
+/// Creating, updating or deleting a ticket only touches the rows of that ticket,
+/// instead of rewriting the whole store.
+pub struct SqliteBackend {
+    path: PathBuf,
+    connection: Connection,
+    /// Store of the YAML backend to carry over on the first load, if the database is empty.
+    yaml_path: Option<PathBuf>,
+}
+
+impl SqliteBackend {
+    /// Open the database at the given path, creating it and its tables if missing.
+    pub fn open(path: PathBuf) -> Result<Self, JiraError> {
+        let connection = Connection::open(&path).map_err(|e| {
+            JiraError::io(&format!("Failed to open the ticket database {:?}", path), e)
+        })?;
+        let created = !has_table(&connection, "tickets")?;
+        connection.execute_batch(SCHEMA)?;
+        if created {
+            set_schema_version(&connection)?;
+        }
+        Ok(Self {
+            path,
+            connection,
+            yaml_path: None,
+        })
+    }
+
+    /// Carry over the store saved by the [YAML backend](YamlFileBackend) at `yaml_path`
+    /// when the store is loaded, if nothing was saved in the database yet.
+    /// It happens on load, once the store is [locked](StorageBackend::lock).
+    pub fn carry_over_yaml(mut self, yaml_path: PathBuf) -> Self {
+        self.yaml_path = Some(yaml_path);
+        self
+    }
+
+    /// Returns `true` if nothing was ever saved in the database.
+    pub fn is_empty(&self) -> Result<bool, JiraError> {
+        Ok(self.current_id()?.is_none())
+    }
+
+    /// Copy the tickets of a YAML store into this database, if there is one
+    /// and nothing was saved in the database yet.
+    ///
+    /// The YAML file is left untouched. Returns `true` if tickets were migrated.
+    pub fn migrate_from_yaml(&mut self, yaml_path: &Path) -> Result<bool, JiraError> {
+        if !self.is_empty()? || !yaml_path.exists() {
+            return Ok(false);
+        }
+        let ticket_store = YamlFileBackend::new(yaml_path.to_path_buf()).load()?;
+        self.save(&ticket_store)?;
+        Ok(true)
+    }
+
+    /// Bring the database up to [SQLITE_SCHEMA_VERSION], or only report what would change if
+    /// `dry_run` is set. Returns a description of every change, empty if it is up to date.
+    fn upgrade(&mut self, dry_run: bool) -> Result<Vec<String>, JiraError> {
+        let version: i64 = self
+            .connection
+            .query_row("PRAGMA user_version", params![], |row| row.get(0))?;
+        if version > SQLITE_SCHEMA_VERSION {
+            return Err(JiraError::Validation(format!(
+                "The ticket database was written by a newer version of jira-cli (schema version {}, \
+                 this version supports up to {}). Please upgrade jira-cli.",
+                version, SQLITE_SCHEMA_VERSION
+            )));
+        }
+        if version == SQLITE_SCHEMA_VERSION {
+            return Ok(Vec::new());
+        }
+
+        // Changes to the schema are transactional: a dry run rolls them back.
+        let transaction = self.connection.transaction()?;
+        let mut changes = Vec::new();
+        // Version 0 to 1: the columns added after the first release, which
+        // `CREATE TABLE IF NOT EXISTS` leaves out.
+        for (table, column) in &[
+            ("tickets", "external_key"),
+            ("comments", "author"),
+            ("status_changes", "author"),
+        ] {
+            if add_missing_column(&transaction, table, column)? {
+                changes.push(format!("Add the {} column to the {} table", column, table));
+            }
+        }
+        set_schema_version(&transaction)?;
+        changes.push(format!(
+            "Set the schema version to {}",
+            SQLITE_SCHEMA_VERSION
+        ));
+        if dry_run {
+            transaction.rollback()?;
+        } else {
+            transaction.commit()?;
+        }
+        Ok(changes)
+    }
+
+    fn current_id(&self) -> Result<Option<u64>, JiraError> {
+        let current_id = self
+            .connection
+            .query_row(
+                "SELECT current_id FROM sequence WHERE id = 0",
+                params![],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()?;
+        Ok(current_id.map(|id| id as u64))
+    }
+
+    fn load_tickets(&self) -> Result<Vec<Ticket>, JiraError> {
+        let mut statement = self.connection.prepare(
+            "SELECT id, title, description, status, created_at, external_key FROM tickets",
+        )?;
+        let rows = statement
+            .query_map(params![], |row| {
+                Ok((
+                    row.get::<_, i64>(0)? as TicketId,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, Option<DateTime<Utc>>>(4)?,
+                    row.get::<_, Option<String>>(5)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        rows.into_iter()
+            .map(
+                |(id, title, description, status, created_at, external_key)| {
+                    Ok(Ticket {
+                        id,
+                        title: Title::new(title).map_err(|e| {
+                            JiraError::parse("Invalid title in the ticket database", e)
+                        })?,
+                        description,
+                        status: parse_status(&status)?,
+                        comments: self.load_comments(id)?,
+                        created_at,
+                        history: self.load_history(id)?,
+                        external_key,
+                        commits: self.load_commits(id)?,
+                        related: self.load_relations(id)?,
+                    })
+                },
+            )
+            .collect()
+    }
+
+    fn load_comments(&self, id: TicketId) -> Result<Vec<Comment>, JiraError> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT body, author FROM comments WHERE ticket_id = ?1 ORDER BY position")?;
+        let rows = statement
+            .query_map(params![id as i64], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows.into_iter()
+            .map(|(body, author)| {
+                let comment = Comment::new(body)
+                    .map_err(|e| JiraError::parse("Invalid comment in the ticket database", e))?;
+                Ok(match author {
+                    Some(author) => comment.with_author(&author),
+                    None => comment,
+                })
+            })
+            .collect()
+    }
+
+    fn load_history(&self, id: TicketId) -> Result<Vec<StatusChange>, JiraError> {
+        let mut statement = self.connection.prepare(
+            "SELECT from_status, to_status, at, author FROM status_changes \
+             WHERE ticket_id = ?1 ORDER BY position",
+        )?;
+        let rows = statement
+            .query_map(params![id as i64], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, DateTime<Utc>>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows.into_iter()
+            .map(|(from, to, at, author)| {
+                Ok(StatusChange {
+                    from: parse_status(&from)?,
+                    to: parse_status(&to)?,
+                    at,
+                    author,
+                })
+            })
+            .collect()
+    }
+
+    fn load_commits(&self, id: TicketId) -> Result<Vec<LinkedCommit>, JiraError> {
+        let mut statement = self.connection.prepare(
+            "SELECT hash, author, date, subject FROM commits WHERE ticket_id = ?1 ORDER BY position",
+        )?;
+        let commits = statement
+            .query_map(params![id as i64], |row| {
+                Ok(LinkedCommit {
+                    hash: row.get(0)?,
+                    author: row.get(1)?,
+                    date: row.get(2)?,
+                    subject: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(commits)
+    }
+
+    fn load_relations(&self, id: TicketId) -> Result<Vec<TicketId>, JiraError> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT related_id FROM relations WHERE ticket_id = ?1 ORDER BY position")?;
+        let related = statement
+            .query_map(params![id as i64], |row| {
+                row.get::<_, i64>(0).map(|id| id as TicketId)
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(related)
+    }
+
+    fn load_settings(&self) -> Result<StoreSettings, JiraError> {
+        let yaml = self
+            .connection
+            .query_row("SELECT yaml FROM settings WHERE id = 0", params![], |row| {
+                row.get::<_, String>(0)
+            })
+            .optional()?;
+        match yaml {
+            Some(yaml) => serde_yaml::from_str(&yaml)
+                .map_err(|e| JiraError::parse("Failed to parse the store settings", e)),
+            None => Ok(StoreSettings::default()),
+        }
+    }
+}
+
+impl StorageBackend for SqliteBackend {
+    fn lock(&self) -> Result<Option<StoreLock>, StoreLockError> {
+        StoreLock::acquire(&self.path).map(Some)
+    }
+
+    /// Load the store, upgrading the database first if it was written by an older version.
+    fn load(&mut self) -> Result<TicketStore, JiraError> {
+        self.upgrade(false)?;
+        if let Some(yaml_path) = self.yaml_path.take() {
+            self.migrate_from_yaml(&yaml_path)?;
+        }
+        let current_id = match self.current_id()? {
+            Some(current_id) => current_id,
+            // Nothing saved yet - this is the first time you are using IronJira!
+            None => return Ok(TicketStore::new()),
+        };
+        Ok(TicketStore::from_parts(
+            current_id,
+            self.load_tickets()?,
+            self.load_settings()?,
+        ))
+    }
+
+    fn save(&mut self, ticket_store: &TicketStore) -> Result<(), JiraError> {
+        let transaction = self.connection.transaction()?;
+        transaction.execute("DELETE FROM tickets", params![])?;
+        for ticket in ticket_store.list() {
+            write_ticket(&transaction, ticket)?;
+        }
+        write_sequence(&transaction, ticket_store)?;
+        write_settings(&transaction, ticket_store)?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn save_ticket(&mut self, ticket_store: &TicketStore, id: TicketId) -> Result<(), JiraError> {
+        let ticket = match ticket_store.get(id) {
+            Some(ticket) => ticket,
+            None => return self.delete_ticket(ticket_store, id),
+        };
+        let transaction = self.connection.transaction()?;
+        write_ticket(&transaction, ticket)?;
+        // The ticket may have just been created, moving the sequence forward.
+        write_sequence(&transaction, ticket_store)?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn delete_ticket(
+        &mut self,
+        _ticket_store: &TicketStore,
+        id: TicketId,
+    ) -> Result<(), JiraError> {
+        // Comments, status changes, commits and relations are removed by the cascading
+        // foreign keys, apart from the relations of the other tickets to this one.
+        let transaction = self.connection.transaction()?;
+        transaction.execute("DELETE FROM tickets WHERE id = ?1", params![id as i64])?;
+        transaction.execute(
+            "DELETE FROM relations WHERE related_id = ?1",
+            params![id as i64],
+        )?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn save_settings(&mut self, ticket_store: &TicketStore) -> Result<(), JiraError> {
+        let transaction = self.connection.transaction()?;
+        write_sequence(&transaction, ticket_store)?;
+        write_settings(&transaction, ticket_store)?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn migrate(&mut self, dry_run: bool) -> Result<Vec<String>, JiraError> {
+        self.upgrade(dry_run)
+    }
+}
+
+/// Whether the database has a table with the given name.
+fn has_table(connection: &Connection, table: &str) -> rusqlite::Result<bool> {
+    connection
+        .query_row(
+            "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            params![table],
+            |_| Ok(()),
+        )
+        .optional()
+        .map(|row| row.is_some())
+}
+
+fn set_schema_version(connection: &Connection) -> rusqlite::Result<()> {
+    connection.execute_batch(&format!("PRAGMA user_version = {}", SQLITE_SCHEMA_VERSION))
+}
+
+/// Add a nullable text column to a table, unless it is already there.
+/// Returns whether the column was added.
+fn add_missing_column(
+    connection: &Connection,
+    table: &str,
+    column: &str,
+) -> rusqlite::Result<bool> {
+    let mut statement =
+        connection.prepare(&format!("SELECT name FROM pragma_table_info('{}')", table))?;
+    let columns = statement
+        .query_map(params![], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    if columns.iter().any(|name| name == column) {
+        return Ok(false);
+    }
+    connection.execute(
+        &format!("ALTER TABLE {} ADD COLUMN {} TEXT", table, column),
+        params![],
+    )?;
+    Ok(true)
+}
+
+/// Insert or replace a ticket, together with its comments, status history, linked commits and
+/// relations.
+fn write_ticket(transaction: &Transaction, ticket: &Ticket) -> rusqlite::Result<()> {
+    let id = ticket.id as i64;
+    // Deleting the previous version of the ticket also removes its comments, status changes,
+    // commits and relations.
+    transaction.execute("DELETE FROM tickets WHERE id = ?1", params![id])?;
+    transaction.execute(
+        "INSERT INTO tickets (id, title, description, status, created_at, external_key) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            id,
+            ticket.title.to_string(),
+            ticket.description,
+            format!("{:?}", ticket.status),
+            ticket.created_at,
+            ticket.external_key
+        ],
+    )?;
+    for (position, comment) in ticket.comments.iter().enumerate() {
+        transaction.execute(
+            "INSERT INTO comments (ticket_id, position, body, author) VALUES (?1, ?2, ?3, ?4)",
+            params![id, position as i64, comment.to_string(), comment.author()],
+        )?;
+    }
+    for (position, change) in ticket.history.iter().enumerate() {
+        transaction.execute(
+            "INSERT INTO status_changes (ticket_id, position, from_status, to_status, at, author) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                id,
+                position as i64,
+                format!("{:?}", change.from),
+                format!("{:?}", change.to),
+                change.at,
+                change.author
+            ],
+        )?;
+    }
+    for (position, commit) in ticket.commits.iter().enumerate() {
+        transaction.execute(
+            "INSERT INTO commits (ticket_id, position, hash, author, date, subject) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                id,
+                position as i64,
+                commit.hash,
+                commit.author,
+                commit.date,
+                commit.subject
+            ],
+        )?;
+    }
+    for (position, related_id) in ticket.related.iter().enumerate() {
+        transaction.execute(
+            "INSERT INTO relations (ticket_id, position, related_id) VALUES (?1, ?2, ?3)",
+            params![id, position as i64, *related_id as i64],
+        )?;
+    }
+    Ok(())
+}
+
+fn write_sequence(transaction: &Transaction, ticket_store: &TicketStore) -> rusqlite::Result<()> {
+    transaction.execute(
+        "INSERT OR REPLACE INTO sequence (id, current_id) VALUES (0, ?1)",
+        params![ticket_store.current_id() as i64],
+    )?;
+    Ok(())
+}
+
+fn write_settings(transaction: &Transaction, ticket_store: &TicketStore) -> rusqlite::Result<()> {
+    let yaml =
+        serde_yaml::to_string(&ticket_store.settings()).expect("Failed to serialize settings");
+    transaction.execute(
+        "INSERT OR REPLACE INTO settings (id, yaml) VALUES (0, ?1)",
+        params![yaml],
+    )?;
+    Ok(())
+}
+
+fn parse_status(status: &str) -> Result<Status, JiraError> {
+    Status::all()
+        .iter()
+        .copied()
+        .find(|s| format!("{:?}", s) == status)
+        .ok_or_else(|| {
+            JiraError::Parse(format!("Invalid status in the ticket database: {}", status))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::{Comment, LinkedCommit, Status, TicketDraft, Title};
+    use crate::persistence::{
+        SqliteBackend, StorageBackend, YamlFileBackend, SQLITE_STORE, TICKET_STORE,
+    };
+    use crate::store::TicketStore;
+
+    fn draft(title: &str) -> TicketDraft {
+        TicketDraft {
+            title: Title::new(title.to_string()).unwrap(),
+            description: "A description".to_string(),
+        }
+    }
+
+    #[test]
+    fn a_new_database_loads_an_empty_store() {
+        // arrange
+        let dir = tempfile::tempdir().unwrap();
+        let mut backend = SqliteBackend::open(dir.path().join(SQLITE_STORE)).unwrap();
+
+        // act
+        let ticket_store = backend.load().unwrap();
+
+        // assert
+        assert!(backend.is_empty().unwrap());
+        assert!(ticket_store.list().is_empty());
+    }
+
+    #[test]
+    fn tickets_saved_one_by_one_are_loaded_back() {
+        // arrange
+        let dir = tempfile::tempdir().unwrap();
+        let mut backend = SqliteBackend::open(dir.path().join(SQLITE_STORE)).unwrap();
+        let mut ticket_store = TicketStore::new();
+        let first = ticket_store.create(draft("First"));
+        let second = ticket_store.create(draft("Second"));
+        ticket_store
+            .add_comment_to_ticket(first, Comment::new("LGTM".to_string()).unwrap())
+            .unwrap();
+        ticket_store.set_author(Some("alice".to_string()));
+        ticket_store
+            .update_ticket_status(first, Status::Done)
+            .unwrap();
+        let commit = LinkedCommit {
+            hash: "4b825dc642cb6eb9a060e54bf8d69288fbee4904".to_string(),
+            author: "Jane Doe".to_string(),
+            date: chrono::Utc::now(),
+            subject: "IRON-1: fix login".to_string(),
+        };
+        ticket_store.link_commit(first, commit).unwrap();
+        ticket_store.relate_tickets(first, second).unwrap();
+
+        // act
+        backend.save_ticket(&ticket_store, first).unwrap();
+        backend.save_ticket(&ticket_store, second).unwrap();
+        ticket_store.delete(second);
+        backend.delete_ticket(&ticket_store, second).unwrap();
+        let mut loaded = backend.load().unwrap();
+
+        // assert
+        assert_eq!(loaded.get(first), ticket_store.get(first));
+        assert_eq!(loaded.get(second), None);
+        // The id sequence was saved as well: ids are never reused.
+        assert_eq!(loaded.create(draft("Third")), 3);
+    }
+
+    #[test]
+    fn tickets_are_migrated_from_yaml_only_once() {
+        // arrange
+        let dir = tempfile::tempdir().unwrap();
+        let yaml_path = dir.path().join(TICKET_STORE);
+        let mut ticket_store = TicketStore::new();
+        let id = ticket_store.create(draft("From YAML"));
+        YamlFileBackend::new(yaml_path.clone())
+            .save(&ticket_store)
+            .unwrap();
+        let mut backend = SqliteBackend::open(dir.path().join(SQLITE_STORE))
+            .unwrap()
+            .carry_over_yaml(yaml_path.clone());
+
+        // act
+        let loaded = backend.load().unwrap();
+        let second_migration = backend.migrate_from_yaml(&yaml_path).unwrap();
+
+        // assert
+        assert_eq!(loaded.get(id), ticket_store.get(id));
+        assert!(!second_migration);
+    }
+
+    #[test]
+    fn a_database_from_before_the_schema_was_versioned_is_upgraded() {
+        // arrange
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SQLITE_STORE);
+        let connection = rusqlite::Connection::open(&path).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE tickets (
+                    id INTEGER PRIMARY KEY,
+                    title TEXT NOT NULL,
+                    description TEXT NOT NULL,
+                    status TEXT NOT NULL,
+                    created_at TEXT
+                );",
+            )
+            .unwrap();
+        drop(connection);
+        let mut backend = SqliteBackend::open(path).unwrap();
+
+        // act
+        let dry_run = backend.migrate(true).unwrap();
+        let applied = backend.migrate(false).unwrap();
+        let again = backend.migrate(false).unwrap();
+
+        // assert
+        assert_eq!(dry_run, applied);
+        assert_eq!(
+            applied,
+            vec![
+                "Add the external_key column to the tickets table",
+                "Set the schema version to 1",
+            ]
+        );
+        assert!(again.is_empty());
+        assert!(backend.load().unwrap().list().is_empty());
+    }
+
+    #[test]
+    fn a_new_database_is_up_to_date() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut backend = SqliteBackend::open(dir.path().join(SQLITE_STORE)).unwrap();
+
+        let changes = backend.migrate(false).unwrap();
+
+        assert!(changes.is_empty());
+    }
+}
@@ -0,0 +1,172 @@
+use crate::error::JiraError;
+use crate::persistence::{
+    schema, write_atomically, Backups, StorageBackend, StoreLock, StoreLockError,
+};
+use crate::store::TicketStore;
+use std::fs::read_to_string;
+use std::path::PathBuf;
+
+pub const TICKET_STORE: &str = "ticket_store.yaml";
+
+/// Stores the whole [TicketStore](TicketStore) as a single YAML document.
+pub struct YamlFileBackend {
+    path: PathBuf,
+    backups: Option<Backups>,
+}
+
+impl YamlFileBackend {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            backups: None,
+        }
+    }
+
+    fn read_error(&self, e: std::io::Error) -> JiraError {
+        JiraError::io(&format!("Failed to read tickets from {:?}", self.path), e)
+    }
+
+    /// Back up the file before every save, keeping the last `retention` copies.
+    pub fn keep_backups(mut self, retention: usize) -> Self {
+        self.backups = Some(Backups::new(&self.path, retention));
+        self
+    }
+}
+
+impl StorageBackend for YamlFileBackend {
+    fn lock(&self) -> Result<Option<StoreLock>, StoreLockError> {
+        StoreLock::acquire(&self.path).map(Some)
+    }
+
+    /// Fetch the tickets from the YAML file, if available.
+    fn load(&mut self) -> Result<TicketStore, JiraError> {
+        // Read the data in memory, storing the value in a string
+        match read_to_string(&self.path) {
+            Ok(data) => {
+                // Deserialize configuration from YAML format, upgrading older versions
+                Ok(schema::parse(&data)?)
+            }
+            Err(e) => match e.kind() {
+                // The file is missing - this is the first time you are using IronJira!
+                std::io::ErrorKind::NotFound => {
+                    // Return default configuration
+                    Ok(TicketStore::new())
+                }
+                // Something went wrong - let the user know what.
+                _ => Err(self.read_error(e)),
+            },
+        }
+    }
+
+    /// Save tickets on disk in the right file.
+    fn save(&mut self, ticket_store: &TicketStore) -> Result<(), JiraError> {
+        // Serialize data to YAML format
+        let content = schema::to_string(ticket_store);
+        if let Some(backups) = &self.backups {
+            backups
+                .take()
+                .map_err(|e| JiraError::io("Failed to back up tickets", e))?;
+        }
+        // Save to disk, without corrupting the previous version if we crash midway.
+        write_atomically(&self.path, content.as_bytes())
+            .map_err(|e| JiraError::io(&format!("Failed to write tickets to {:?}", self.path), e))
+    }
+
+    fn backups(&self) -> Option<&Backups> {
+        self.backups.as_ref()
+    }
+
+    fn migrate(&mut self, dry_run: bool) -> Result<Vec<String>, JiraError> {
+        let data = match read_to_string(&self.path) {
+            Ok(data) => data,
+            // Nothing saved yet, nothing to upgrade.
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(self.read_error(e)),
+        };
+        let mut document = serde_yaml::from_str(&data)
+            .map_err(|e| JiraError::parse("Failed to parse serialised data", e))?;
+        let changes = schema::upgrade(&mut document)?;
+        if !dry_run && !changes.is_empty() {
+            let ticket_store = self.load()?;
+            self.save(&ticket_store)?;
+        }
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::{TicketDraft, Title};
+    use crate::persistence::{StorageBackend, YamlFileBackend, TICKET_STORE};
+    use crate::store::TicketStore;
+
+    #[test]
+    fn a_missing_file_loads_an_empty_store() {
+        // arrange
+        let dir = tempfile::tempdir().unwrap();
+        let mut backend = YamlFileBackend::new(dir.path().join(TICKET_STORE));
+
+        // act
+        let ticket_store = backend.load().unwrap();
+
+        // assert
+        assert!(ticket_store.list().is_empty());
+    }
+
+    #[test]
+    fn saved_tickets_are_loaded_back() {
+        // arrange
+        let dir = tempfile::tempdir().unwrap();
+        let mut backend = YamlFileBackend::new(dir.path().join(TICKET_STORE));
+        let mut ticket_store = TicketStore::new();
+        let id = ticket_store.create(TicketDraft {
+            title: Title::new("Fix login".to_string()).unwrap(),
+            description: "It is broken".to_string(),
+        });
+
+        // act
+        backend.save(&ticket_store).unwrap();
+        let loaded = backend.load().unwrap();
+
+        // assert
+        assert_eq!(loaded.get(id), ticket_store.get(id));
+    }
+
+    #[test]
+    fn saving_does_not_leave_temporary_files_behind() {
+        // arrange
+        let dir = tempfile::tempdir().unwrap();
+        let mut backend = YamlFileBackend::new(dir.path().join(TICKET_STORE));
+
+        // act
+        backend.save(&TicketStore::new()).unwrap();
+        backend.save(&TicketStore::new()).unwrap();
+
+        // assert
+        let files: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(files, vec![TICKET_STORE]);
+    }
+
+    #[test]
+    fn migrating_rewrites_old_stores_unless_it_is_a_dry_run() {
+        // arrange
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(TICKET_STORE);
+        std::fs::write(&path, "current_id: 0\ndata: {}\n").unwrap();
+        let mut backend = YamlFileBackend::new(path.clone());
+
+        // act
+        let dry_run = backend.migrate(true).unwrap();
+        let untouched = std::fs::read_to_string(&path).unwrap();
+        let migrated = backend.migrate(false).unwrap();
+
+        // assert
+        assert!(!dry_run.is_empty());
+        assert_eq!(dry_run, migrated);
+        assert_eq!(untouched, "current_id: 0\ndata: {}\n");
+        assert!(backend.migrate(true).unwrap().is_empty());
+    }
+}
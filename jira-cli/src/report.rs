@@ -0,0 +1,359 @@
+use crate::models::{Status, Ticket, TicketId};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+
+const SECONDS_PER_DAY: i64 = 86_400;
+/// Percentiles shown for lead and cycle times.
+const PERCENTILES: [u8; 3] = [50, 85, 95];
+/// Longest window a report can cover, in days.
+pub const MAX_DAYS: i64 = 36_500;
+/// Width, in characters, of the longest bar of the cumulative flow diagram.
+const BAR_WIDTH: usize = 40;
+
+/// How long a completed [Ticket](Ticket) took to get to [Done](Status::Done).
+#[derive(PartialEq, Debug)]
+pub struct TicketFlow {
+    pub id: TicketId,
+    pub done_at: DateTime<Utc>,
+    /// From creation to [Done](Status::Done).
+    /// Missing for tickets created before creation times were recorded.
+    pub lead_time: Option<Duration>,
+    /// From the first move to [InProgress](Status::InProgress) to [Done](Status::Done).
+    /// Missing for tickets that never went through [InProgress](Status::InProgress).
+    pub cycle_time: Option<Duration>,
+}
+
+/// Lead time, cycle time and cumulative flow of the tickets over a time window.
+#[derive(PartialEq, Debug)]
+pub struct FlowReport {
+    /// Tickets completed within the window, ordered by completion time.
+    pub completed: Vec<TicketFlow>,
+    /// For each day in the window, the number of tickets in each status at the
+    /// end of that day, in the order given by [Status::all](Status::all).
+    pub cumulative_flow: Vec<(DateTime<Utc>, Vec<usize>)>,
+}
+
+impl FlowReport {
+    /// Build the report for the days going from `since` to `until`, both included:
+    /// the window starts at the beginning of the day of `since`.
+    pub fn compute(tickets: &[&Ticket], since: DateTime<Utc>, until: DateTime<Utc>) -> Self {
+        let first_day = start_of_day(since);
+        let mut completed: Vec<TicketFlow> = tickets
+            .iter()
+            .filter_map(|t| ticket_flow(t))
+            .filter(|f| f.done_at >= first_day && f.done_at <= until)
+            .collect();
+        completed.sort_by_key(|f| (f.done_at, f.id));
+
+        let mut cumulative_flow = Vec::new();
+        let mut day = first_day;
+        while day <= until {
+            let end_of_day = day + Duration::days(1);
+            let counts = Status::all()
+                .iter()
+                .map(|status| {
+                    tickets
+                        .iter()
+                        .filter(|t| status_at(t, end_of_day) == Some(*status))
+                        .count()
+                })
+                .collect();
+            cumulative_flow.push((day, counts));
+            day = end_of_day;
+        }
+
+        Self {
+            completed,
+            cumulative_flow,
+        }
+    }
+
+    /// The given percentile of the lead times of the completed tickets.
+    pub fn lead_time_percentile(&self, p: u8) -> Option<Duration> {
+        percentile(
+            self.completed.iter().filter_map(|f| f.lead_time).collect(),
+            p,
+        )
+    }
+
+    /// The given percentile of the cycle times of the completed tickets.
+    pub fn cycle_time_percentile(&self, p: u8) -> Option<Duration> {
+        percentile(
+            self.completed.iter().filter_map(|f| f.cycle_time).collect(),
+            p,
+        )
+    }
+
+    /// Render the report as plain text.
+    pub fn render(&self) -> String {
+        let mut lines = vec![format!("Completed tickets: {}", self.completed.len())];
+        if !self.completed.is_empty() {
+            lines.push(format!(
+                "{:<8}{:<12}{:<12}",
+                "ID", "LEAD TIME", "CYCLE TIME"
+            ));
+            for flow in &self.completed {
+                lines.push(format!(
+                    "{:<8}{:<12}{}",
+                    flow.id,
+                    format_duration(flow.lead_time),
+                    format_duration(flow.cycle_time)
+                ));
+            }
+        }
+
+        lines.push(String::new());
+        let percentiles = |percentile: &dyn Fn(u8) -> Option<Duration>| {
+            PERCENTILES
+                .iter()
+                .map(|p| format!("p{} {}", p, format_duration(percentile(*p))))
+                .collect::<Vec<String>>()
+                .join("  ")
+        };
+        lines.push(format!(
+            "Lead time   {}",
+            percentiles(&|p| self.lead_time_percentile(p))
+        ));
+        lines.push(format!(
+            "Cycle time  {}",
+            percentiles(&|p| self.cycle_time_percentile(p))
+        ));
+        lines.push(String::new());
+        lines.push("Cumulative flow (# done, ! blocked, = in progress, . to do):".to_string());
+        let header: Vec<String> = Status::all()
+            .iter()
+            .map(|s| format!("{:?}", s).to_uppercase())
+            .collect();
+        lines.push(format!("{:<12}{}", "DATE", header.join("  ")));
+        let max_total = self
+            .cumulative_flow
+            .iter()
+            .map(|(_, counts)| counts.iter().sum::<usize>())
+            .max()
+            .unwrap_or(0);
+        for (day, counts) in &self.cumulative_flow {
+            let cells: Vec<String> = counts
+                .iter()
+                .zip(header.iter())
+                .map(|(count, h)| format!("{:<width$}", count, width = h.len()))
+                .collect();
+            lines.push(format!(
+                "{:<12}{}  {}",
+                day.format("%Y-%m-%d"),
+                cells.join("  "),
+                bar(counts, max_total)
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+/// When the ticket was last moved to [Done](Status::Done), if it is done.
+fn completed_at(ticket: &Ticket) -> Option<DateTime<Utc>> {
+    if ticket.status != Status::Done {
+        return None;
+    }
+    ticket
+        .history
+        .iter()
+        .rev()
+        .find(|c| c.to == Status::Done)
+        .map(|c| c.at)
+}
+
+fn ticket_flow(ticket: &Ticket) -> Option<TicketFlow> {
+    let done_at = completed_at(ticket)?;
+    let started_at = ticket
+        .history
+        .iter()
+        .find(|c| c.to == Status::InProgress)
+        .map(|c| c.at)
+        .filter(|at| *at <= done_at);
+    Some(TicketFlow {
+        id: ticket.id,
+        done_at,
+        lead_time: ticket.created_at.map(|created_at| done_at - created_at),
+        cycle_time: started_at.map(|started_at| done_at - started_at),
+    })
+}
+
+/// The status of the ticket just before the given instant.
+/// Returns `None` if the ticket did not exist yet.
+fn status_at(ticket: &Ticket, at: DateTime<Utc>) -> Option<Status> {
+    if let Some(created_at) = ticket.created_at {
+        if created_at >= at {
+            return None;
+        }
+    }
+    // Tickets start in the status they left with their first move,
+    // or the one they are still in if they never moved.
+    let initial = ticket
+        .history
+        .first()
+        .map(|c| c.from)
+        .unwrap_or(ticket.status);
+    Some(
+        ticket
+            .history
+            .iter()
+            .take_while(|c| c.at < at)
+            .last()
+            .map(|c| c.to)
+            .unwrap_or(initial),
+    )
+}
+
+/// The value below which `p` percent of the durations fall, using the nearest-rank method.
+fn percentile(mut durations: Vec<Duration>, p: u8) -> Option<Duration> {
+    if durations.is_empty() {
+        return None;
+    }
+    durations.sort();
+    let rank = (p as usize * durations.len()).div_ceil(100);
+    Some(durations[rank.max(1) - 1])
+}
+
+/// Midnight (UTC) of the day the instant falls in.
+fn start_of_day(at: DateTime<Utc>) -> DateTime<Utc> {
+    let seconds = at.timestamp();
+    Utc.timestamp_opt(seconds - seconds.rem_euclid(SECONDS_PER_DAY), 0)
+        .unwrap()
+}
+
+fn format_duration(duration: Option<Duration>) -> String {
+    match duration {
+        Some(d) => format!("{:.1}d", d.num_seconds() as f64 / SECONDS_PER_DAY as f64),
+        None => "-".to_string(),
+    }
+}
+
+/// A stacked bar for a day of the cumulative flow diagram, completed work first.
+fn bar(counts: &[usize], max_total: usize) -> String {
+    if max_total == 0 {
+        return String::new();
+    }
+    Status::all()
+        .iter()
+        .zip(counts.iter())
+        .rev()
+        .map(|(status, count)| {
+            let symbol = match status {
+                Status::Done => "#",
+                Status::Blocked => "!",
+                Status::InProgress => "=",
+                Status::ToDo => ".",
+            };
+            symbol.repeat(count * BAR_WIDTH / max_total)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::{Status, StatusChange, Ticket};
+    use crate::report::{percentile, FlowReport};
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+
+    /// Noon (UTC) of the given day of June 2020.
+    fn day(d: i64) -> DateTime<Utc> {
+        let june_first = 1_590_969_600;
+        Utc.timestamp_opt(june_first + (d - 1) * 86_400 + 43_200, 0)
+            .unwrap()
+    }
+
+    fn ticket(id: u64, created: i64, moves: &[(Status, Status, i64)]) -> Ticket {
+        let history: Vec<StatusChange> = moves
+            .iter()
+            .map(|(from, to, at)| StatusChange {
+                from: *from,
+                to: *to,
+                at: day(*at),
+                author: None,
+            })
+            .collect();
+        let status = history.last().map(|c| c.to).unwrap_or(Status::ToDo);
+        Ticket {
+            created_at: Some(day(created)),
+            history,
+            ..Ticket::sample(id, "A ticket", status)
+        }
+    }
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        let durations: Vec<Duration> = (1..=10).map(Duration::days).collect();
+
+        assert_eq!(percentile(durations.clone(), 50), Some(Duration::days(5)));
+        assert_eq!(percentile(durations.clone(), 95), Some(Duration::days(10)));
+        assert_eq!(percentile(Vec::new(), 50), None);
+    }
+
+    #[test]
+    fn lead_and_cycle_times_of_completed_tickets() {
+        // arrange
+        let done = ticket(
+            1,
+            1,
+            &[
+                (Status::ToDo, Status::InProgress, 3),
+                (Status::InProgress, Status::Done, 5),
+            ],
+        );
+        let skipped_progress = ticket(2, 2, &[(Status::ToDo, Status::Done, 4)]);
+        let not_done = ticket(3, 1, &[(Status::ToDo, Status::InProgress, 2)]);
+        let tickets = [&done, &skipped_progress, &not_done];
+
+        // act
+        let report = FlowReport::compute(&tickets, day(1), day(10));
+
+        // assert
+        assert_eq!(report.completed.len(), 2);
+        assert_eq!(report.completed[0].id, 2);
+        assert_eq!(report.completed[0].lead_time, Some(Duration::days(2)));
+        assert_eq!(report.completed[0].cycle_time, None);
+        assert_eq!(report.completed[1].lead_time, Some(Duration::days(4)));
+        assert_eq!(report.completed[1].cycle_time, Some(Duration::days(2)));
+    }
+
+    #[test]
+    fn tickets_completed_outside_of_the_window_are_left_out() {
+        // arrange
+        let done = ticket(1, 1, &[(Status::ToDo, Status::Done, 2)]);
+
+        // act
+        let report = FlowReport::compute(&[&done], day(5), day(10));
+
+        // assert
+        assert!(report.completed.is_empty());
+    }
+
+    #[test]
+    fn tickets_completed_earlier_on_the_first_day_are_included() {
+        // arrange
+        let done = ticket(1, 1, &[(Status::ToDo, Status::Done, 5)]);
+        let now = day(5) + Duration::hours(3);
+
+        // act
+        let report = FlowReport::compute(&[&done], now, now);
+
+        // assert
+        assert_eq!(report.completed.len(), 1);
+    }
+
+    #[test]
+    fn cumulative_flow_counts_tickets_per_status_at_the_end_of_each_day() {
+        // arrange
+        let first = ticket(1, 1, &[(Status::ToDo, Status::InProgress, 2)]);
+        let second = ticket(2, 2, &[]);
+
+        // act
+        let report = FlowReport::compute(&[&first, &second], day(1), day(2));
+
+        // assert
+        let counts: Vec<Vec<usize>> = report
+            .cumulative_flow
+            .into_iter()
+            .map(|(_, counts)| counts)
+            .collect();
+        assert_eq!(counts, vec![vec![1, 0, 0, 0], vec![1, 1, 0, 0]]);
+    }
+}
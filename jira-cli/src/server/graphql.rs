@@ -0,0 +1,511 @@
+use crate::error::JiraError;
+use crate::models::{
+    Comment, Status, Ticket, TicketDraft, TicketFilter, TicketId, TicketPatch, Title,
+};
+use crate::server::{method_not_allowed, ApiRequest, ApiResponse, ServerState};
+use crate::store::TicketStore;
+use juniper::http::GraphQLRequest;
+use juniper::{FieldError, FieldResult, RootNode, ID};
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use tiny_http::Method;
+
+/// The GraphQL schema of the ticket store.
+pub type Schema = RootNode<'static, Query, Mutation>;
+
+/// What the resolvers work with during a single GraphQL request.
+pub struct Context {
+    ticket_store: RefCell<TicketStore>,
+    /// Tickets changed by the mutations of the request, to be saved once it is executed.
+    changed: RefCell<BTreeSet<TicketId>>,
+}
+
+impl juniper::Context for Context {}
+
+impl Context {
+    /// Run a change on the store, remembering the tickets it touched.
+    fn change<T>(
+        &self,
+        ids: &[TicketId],
+        change: impl FnOnce(&mut TicketStore) -> Result<T, JiraError>,
+    ) -> FieldResult<T> {
+        let result = change(&mut self.ticket_store.borrow_mut())?;
+        self.changed.borrow_mut().extend(ids);
+        Ok(result)
+    }
+
+    /// A copy of a ticket of the store, as a GraphQL object.
+    fn ticket(&self, id: TicketId) -> FieldResult<TicketObject> {
+        self.ticket_store
+            .borrow()
+            .get(id)
+            .cloned()
+            .map(TicketObject)
+            .ok_or_else(|| JiraError::ticket_not_found(id).into())
+    }
+}
+
+/// The status of a ticket, e.g. `IN_PROGRESS`.
+#[derive(juniper::GraphQLEnum, PartialEq, Debug, Copy, Clone)]
+#[graphql(name = "Status")]
+pub enum StatusValue {
+    ToDo,
+    InProgress,
+    Blocked,
+    Done,
+}
+
+impl From<Status> for StatusValue {
+    fn from(status: Status) -> Self {
+        match status {
+            Status::ToDo => StatusValue::ToDo,
+            Status::InProgress => StatusValue::InProgress,
+            Status::Blocked => StatusValue::Blocked,
+            Status::Done => StatusValue::Done,
+        }
+    }
+}
+
+impl From<StatusValue> for Status {
+    fn from(status: StatusValue) -> Self {
+        match status {
+            StatusValue::ToDo => Status::ToDo,
+            StatusValue::InProgress => Status::InProgress,
+            StatusValue::Blocked => Status::Blocked,
+            StatusValue::Done => Status::Done,
+        }
+    }
+}
+
+/// Which tickets to return: only the ones matching every given field.
+#[derive(juniper::GraphQLInputObject)]
+pub struct TicketFilterInput {
+    /// Tickets in one of these statuses.
+    statuses: Option<Vec<StatusValue>>,
+    /// Tickets whose title or description contain this text, ignoring case.
+    text: Option<String>,
+}
+
+/// A comment on a ticket.
+#[derive(juniper::GraphQLObject)]
+#[graphql(name = "Comment")]
+pub struct CommentObject {
+    /// Position of the comment on its ticket, starting from 0.
+    position: i32,
+    body: String,
+    /// Name of the user who wrote the comment, if known.
+    author: Option<String>,
+}
+
+/// A ticket of the store.
+pub struct TicketObject(Ticket);
+
+#[juniper::object(Context = Context, name = "Ticket")]
+impl TicketObject {
+    fn id(&self) -> ID {
+        ID::new(self.0.id.to_string())
+    }
+
+    fn title(&self) -> String {
+        self.0.title.to_string()
+    }
+
+    fn description(&self) -> &str {
+        &self.0.description
+    }
+
+    fn status(&self) -> StatusValue {
+        self.0.status.into()
+    }
+
+    /// When the ticket was created, unless it was created before it was recorded.
+    fn created_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.0.created_at
+    }
+
+    /// Key of the ticket in the tool it was imported from, e.g. `PROJ-42` for Jira.
+    fn external_key(&self) -> Option<&str> {
+        self.0.external_key.as_deref()
+    }
+
+    /// Comments on the ticket, oldest first.
+    fn comments(&self) -> Vec<CommentObject> {
+        self.0
+            .comments
+            .iter()
+            .enumerate()
+            .map(|(position, comment)| CommentObject {
+                position: position as i32,
+                body: comment.to_string(),
+                author: comment.author().map(str::to_string),
+            })
+            .collect()
+    }
+
+    /// Tickets related to this one.
+    fn related(&self, context: &Context) -> Vec<TicketObject> {
+        let ticket_store = context.ticket_store.borrow();
+        self.0
+            .related
+            .iter()
+            .filter_map(|id| ticket_store.get(*id).cloned())
+            .map(TicketObject)
+            .collect()
+    }
+}
+
+pub struct Query;
+
+#[juniper::object(Context = Context)]
+impl Query {
+    /// The ticket with the given id, if any.
+    fn ticket(context: &Context, id: ID) -> FieldResult<Option<TicketObject>> {
+        let id = parse_id(&id)?;
+        Ok(context
+            .ticket_store
+            .borrow()
+            .get(id)
+            .cloned()
+            .map(TicketObject))
+    }
+
+    /// The tickets matching the filter, in the order of their ids.
+    fn tickets(context: &Context, filter: Option<TicketFilterInput>) -> Vec<TicketObject> {
+        let filter = filter
+            .map(|filter| TicketFilter {
+                statuses: filter
+                    .statuses
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(Status::from)
+                    .collect(),
+                text: filter.text,
+            })
+            .unwrap_or_default();
+        let ticket_store = context.ticket_store.borrow();
+        let mut tickets: Vec<&Ticket> = ticket_store
+            .list()
+            .into_iter()
+            .filter(|t| filter.matches(t))
+            .collect();
+        tickets.sort_by_key(|t| t.id);
+        tickets.into_iter().cloned().map(TicketObject).collect()
+    }
+}
+
+pub struct Mutation;
+
+#[juniper::object(Context = Context)]
+impl Mutation {
+    fn create_ticket(
+        context: &Context,
+        title: String,
+        description: Option<String>,
+    ) -> FieldResult<TicketObject> {
+        let draft = TicketDraft {
+            title: Title::new(title)?,
+            description: description.unwrap_or_default(),
+        };
+        let id = context.ticket_store.borrow_mut().create(draft);
+        context.changed.borrow_mut().insert(id);
+        context.ticket(id)
+    }
+
+    /// Change the title and/or the description of a ticket.
+    fn update_ticket(
+        context: &Context,
+        id: ID,
+        title: Option<String>,
+        description: Option<String>,
+    ) -> FieldResult<TicketObject> {
+        let id = parse_id(&id)?;
+        let patch = TicketPatch {
+            title: title.map(Title::new).transpose()?,
+            description,
+        };
+        context.change(&[id], |ticket_store| {
+            ticket_store
+                .update_ticket(id, patch)
+                .ok_or_else(|| JiraError::ticket_not_found(id))
+        })?;
+        context.ticket(id)
+    }
+
+    /// Delete a ticket, returning its id.
+    fn delete_ticket(context: &Context, id: ID) -> FieldResult<ID> {
+        let ticket_id = parse_id(&id)?;
+        // The tickets related to it lose their relation as well.
+        let related = context.ticket(ticket_id)?.0.related;
+        context.change(&related, |ticket_store| {
+            ticket_store
+                .delete(ticket_id)
+                .ok_or_else(|| JiraError::ticket_not_found(ticket_id))
+        })?;
+        context.changed.borrow_mut().insert(ticket_id);
+        Ok(id)
+    }
+
+    /// Move a ticket to a new status. Set `force` to ignore the work-in-progress limit.
+    fn move_ticket(
+        context: &Context,
+        id: ID,
+        status: StatusValue,
+        force: Option<bool>,
+    ) -> FieldResult<TicketObject> {
+        let id = parse_id(&id)?;
+        context.change(&[id], |ticket_store| {
+            if force.unwrap_or(false) {
+                ticket_store
+                    .force_ticket_status(id, status.into())
+                    .ok_or_else(|| JiraError::ticket_not_found(id))
+            } else {
+                Ok(ticket_store.update_ticket_status(id, status.into())?)
+            }
+        })?;
+        context.ticket(id)
+    }
+
+    fn add_comment(context: &Context, id: ID, body: String) -> FieldResult<TicketObject> {
+        let id = parse_id(&id)?;
+        let comment = Comment::new(body)?;
+        context.change(&[id], |ticket_store| {
+            ticket_store
+                .add_comment_to_ticket(id, comment)
+                .ok_or_else(|| JiraError::ticket_not_found(id))
+        })?;
+        context.ticket(id)
+    }
+
+    /// Relate two tickets to each other, returning the first one.
+    fn relate_tickets(context: &Context, id: ID, other_id: ID) -> FieldResult<TicketObject> {
+        let (id, other_id) = (parse_id(&id)?, parse_id(&other_id)?);
+        if id == other_id {
+            return Err(FieldError::from("A ticket cannot be related to itself."));
+        }
+        context.change(&[id, other_id], |ticket_store| {
+            ticket_store
+                .relate_tickets(id, other_id)
+                .ok_or_else(|| missing_ticket(ticket_store, &[id, other_id]))
+        })?;
+        context.ticket(id)
+    }
+
+    /// Remove the relation between two tickets, returning the first one.
+    fn unrelate_tickets(context: &Context, id: ID, other_id: ID) -> FieldResult<TicketObject> {
+        let (id, other_id) = (parse_id(&id)?, parse_id(&other_id)?);
+        context.change(&[id, other_id], |ticket_store| {
+            ticket_store
+                .unrelate_tickets(id, other_id)
+                .ok_or_else(|| missing_ticket(ticket_store, &[id, other_id]))
+        })?;
+        context.ticket(id)
+    }
+}
+
+/// Build the GraphQL schema: queries and mutations over the tickets of the store.
+pub fn schema() -> Schema {
+    Schema::new(Query, Mutation)
+}
+
+/// Handle the requests on `/graphql`: a `POST` with a JSON body holding the `query`, and
+/// optionally its `variables` and `operationName`, or a `GET` with a `query` parameter.
+///
+/// Changes made by mutations are saved once the whole request is executed, and only if every
+/// mutation succeeded: a request is applied as a whole or not at all.
+pub fn handle(state: &mut ServerState, request: &ApiRequest) -> Result<ApiResponse, JiraError> {
+    let graphql_request: GraphQLRequest = match request.method {
+        Method::Post => request.json()?,
+        Method::Get => {
+            let query = request
+                .query_values("query")
+                .first()
+                .copied()
+                .ok_or_else(|| {
+                    JiraError::Validation("The query parameter is missing.".to_string())
+                })?;
+            GraphQLRequest::new(query.to_string(), None, None)
+        }
+        _ => return method_not_allowed(request),
+    };
+    let context = Context {
+        // The store is handed back right after the request is executed.
+        ticket_store: RefCell::new(std::mem::replace(state.ticket_store, TicketStore::new())),
+        changed: RefCell::new(BTreeSet::new()),
+    };
+    let schema = schema();
+    let response = graphql_request.execute(&schema, &context);
+    let status = if response.is_ok() { 200 } else { 400 };
+    let body = ApiResponse::json(status, &response);
+    *state.ticket_store = context.ticket_store.into_inner();
+    let changed = context.changed.into_inner();
+    let errors = error_messages(body.body.as_ref());
+    if !errors.is_empty() && !changed.is_empty() {
+        // Some mutations failed: fail the whole request, for the store to be rolled back,
+        // rather than keep the others and let clients retry them.
+        return Err(JiraError::Validation(format!(
+            "The mutations were not applied: {}",
+            errors.join("; ")
+        )));
+    }
+    match changed.iter().next() {
+        // Deleted tickets are removed from the storage.
+        Some(id) if changed.len() == 1 => state.backend.save_ticket(state.ticket_store, *id)?,
+        // Save the tickets changed by several mutations together: all of them, or none.
+        Some(_) => state.backend.save(state.ticket_store)?,
+        None => {}
+    }
+    Ok(body)
+}
+
+/// The messages of the errors of a GraphQL response, e.g. `Title cannot be empty`.
+fn error_messages(response: Option<&serde_json::Value>) -> Vec<&str> {
+    response
+        .and_then(|response| response["errors"].as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|error| error["message"].as_str())
+        .collect()
+}
+
+fn parse_id(id: &ID) -> Result<TicketId, JiraError> {
+    id.parse()
+        .map_err(|_| JiraError::NotFound(format!("{:?} is not a ticket id", id.to_string())))
+}
+
+/// The error for the first of `ids` that is not in the store.
+fn missing_ticket(ticket_store: &TicketStore, ids: &[TicketId]) -> JiraError {
+    let id = ids
+        .iter()
+        .copied()
+        .find(|id| ticket_store.get(*id).is_none())
+        .unwrap_or_default();
+    JiraError::ticket_not_found(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::persistence::{InMemoryBackend, StorageBackend};
+    use crate::server::{handle, ApiRequest, ServerState};
+    use crate::store::TicketStore;
+    use serde_json::{json, Value};
+    use tiny_http::Method;
+
+    fn graphql(
+        ticket_store: &mut TicketStore,
+        backend: &mut InMemoryBackend,
+        query: &str,
+    ) -> Value {
+        let request = ApiRequest {
+            method: Method::Post,
+            path: "/graphql".to_string(),
+            query: Vec::new(),
+            authorization: None,
+            body: json!({ "query": query }).to_string().into_bytes(),
+        };
+        handle(&mut ServerState::new(ticket_store, backend), &request)
+            .body
+            .unwrap()
+    }
+
+    #[test]
+    fn a_ticket_is_fetched_with_its_comments_and_related_tickets() {
+        // arrange
+        let mut ticket_store = TicketStore::new();
+        let mut backend = InMemoryBackend::new();
+        graphql(
+            &mut ticket_store,
+            &mut backend,
+            r#"mutation {
+                first: createTicket(title: "Fix login") { id }
+                second: createTicket(title: "Fix logout") { id }
+                addComment(id: "1", body: "Broken on mobile") { id }
+                relateTickets(id: "1", otherId: "2") { id }
+            }"#,
+        );
+
+        // act
+        let response = graphql(
+            &mut ticket_store,
+            &mut backend,
+            r#"{ ticket(id: "1") { title status comments { body } related { id title } } }"#,
+        );
+
+        // assert
+        assert_eq!(
+            response,
+            json!({ "data": { "ticket": {
+                "title": "Fix login",
+                "status": "TO_DO",
+                "comments": [{ "body": "Broken on mobile" }],
+                "related": [{ "id": "2", "title": "Fix logout" }],
+            } } })
+        );
+        let saved = backend.load().unwrap();
+        assert_eq!(saved.get(2).unwrap().related, vec![1]);
+    }
+
+    #[test]
+    fn tickets_are_filtered_and_errors_reported() {
+        // arrange
+        let mut ticket_store = TicketStore::new();
+        let mut backend = InMemoryBackend::new();
+        graphql(
+            &mut ticket_store,
+            &mut backend,
+            r#"mutation {
+                first: createTicket(title: "Fix login") { id }
+                second: createTicket(title: "Write docs") { id }
+                moveTicket(id: "2", status: DONE) { id }
+            }"#,
+        );
+
+        // act
+        let filtered = graphql(
+            &mut ticket_store,
+            &mut backend,
+            r#"{ tickets(filter: { statuses: [DONE] }) { title } }"#,
+        );
+        let failed = graphql(
+            &mut ticket_store,
+            &mut backend,
+            r#"mutation { addComment(id: "3", body: "Hello") { id } }"#,
+        );
+
+        // assert
+        assert_eq!(
+            filtered,
+            json!({ "data": { "tickets": [{ "title": "Write docs" }] } })
+        );
+        assert_eq!(failed["data"], Value::Null);
+        assert_eq!(
+            failed["errors"][0]["message"],
+            "There was no ticket associated to the ticket id 3"
+        );
+    }
+
+    #[test]
+    fn mutations_are_rolled_back_together_if_one_fails() {
+        // arrange
+        let mut ticket_store = TicketStore::new();
+        let mut backend = InMemoryBackend::new();
+
+        // act
+        let response = graphql(
+            &mut ticket_store,
+            &mut backend,
+            r#"mutation {
+                createTicket(title: "Fix login") { id }
+                addComment(id: "2", body: "Hello") { id }
+            }"#,
+        );
+
+        // assert
+        assert_eq!(
+            response["error"],
+            "The mutations were not applied: \
+             There was no ticket associated to the ticket id 2"
+        );
+        assert!(ticket_store.list().is_empty());
+        assert!(backend.load().unwrap().list().is_empty());
+    }
+}
@@ -0,0 +1,464 @@
+use crate::error::JiraError;
+use crate::models::{Comment, Status, Ticket, TicketDraft, TicketId, TicketPatch, Title};
+use crate::server::{http_status, method_not_allowed, ApiRequest, ApiResponse, Jql, ServerState};
+use crate::store::TicketStore;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tiny_http::Method;
+
+/// Number of issues returned by a search when `maxResults` is missing, as in Jira.
+const DEFAULT_MAX_RESULTS: usize = 50;
+
+/// Body of `POST /issue` and `PUT /issue/{key}`.
+#[derive(Deserialize)]
+struct IssueBody {
+    fields: IssueFields,
+}
+
+#[derive(Deserialize)]
+struct IssueFields {
+    summary: Option<String>,
+    description: Option<String>,
+}
+
+/// Body of `POST /issue/{key}/comment`.
+#[derive(Deserialize)]
+struct CommentBody {
+    body: String,
+}
+
+/// Body of `POST /issue/{key}/transitions`.
+#[derive(Deserialize)]
+struct TransitionBody {
+    transition: TransitionId,
+}
+
+#[derive(Deserialize)]
+struct TransitionId {
+    id: String,
+}
+
+/// Body of `POST /search`.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchBody {
+    #[serde(default)]
+    jql: String,
+    #[serde(default)]
+    start_at: usize,
+    max_results: Option<usize>,
+}
+
+/// Handle the subset of the Jira REST API v2 that scripts use most, `segments` being the path
+/// after `/rest/api/2`:
+///
+/// - `POST /issue`, then `GET`, `PUT` and `DELETE /issue/{key}`;
+/// - `GET` and `POST /issue/{key}/comment`;
+/// - `GET` and `POST /issue/{key}/transitions`, with a transition to each status;
+/// - `GET` and `POST /search`, with a [JQL subset](Jql);
+/// - `GET /serverInfo`, which clients call to check they are talking to Jira.
+///
+/// Issue keys are the project key followed by the ticket id, e.g. `IRON-12`. Tickets imported
+/// from Jira can also be found with their original key. Errors are returned as they are, to be
+/// reported with [error] once the request is rolled back.
+pub fn handle(
+    state: &mut ServerState,
+    request: &ApiRequest,
+    segments: &[&str],
+) -> Result<ApiResponse, JiraError> {
+    match (&request.method, segments) {
+        (Method::Get, ["serverInfo"]) => Ok(ApiResponse::ok(json!({
+            "version": "8.0.0",
+            "versionNumbers": [8, 0, 0],
+            "deploymentType": "Server",
+            "serverTitle": "IronJira",
+        }))),
+        (Method::Get, ["search"]) => {
+            let number = |name: &str| -> Result<Option<usize>, JiraError> {
+                request
+                    .query_values(name)
+                    .first()
+                    .map(|value| {
+                        value.parse().map_err(|_| {
+                            JiraError::Validation(format!("{} must be a number", name))
+                        })
+                    })
+                    .transpose()
+            };
+            let jql = request.query_values("jql").first().copied().unwrap_or("");
+            search(
+                state,
+                jql,
+                number("startAt")?.unwrap_or(0),
+                number("maxResults")?,
+            )
+        }
+        (Method::Post, ["search"]) => {
+            let body: SearchBody = request.json()?;
+            search(state, &body.jql, body.start_at, body.max_results)
+        }
+        (_, ["search"]) => method_not_allowed(request),
+        (Method::Post, ["issue"]) => {
+            let body: IssueBody = request.json()?;
+            let summary = body.fields.summary.unwrap_or_default();
+            let draft = TicketDraft {
+                title: Title::new(summary)?,
+                description: body.fields.description.unwrap_or_default(),
+            };
+            let id = state.ticket_store.create(draft);
+            state.backend.save_ticket(state.ticket_store, id)?;
+            let key = issue_key(&state.project_key, id);
+            Ok(ApiResponse::json(
+                201,
+                json!({ "id": id.to_string(), "key": key, "self": issue_url(id) }),
+            )
+            .located_at(issue_url(id)))
+        }
+        (_, ["issue"]) => method_not_allowed(request),
+        (method, ["issue", key]) => {
+            let id = find_issue(state, key)?;
+            match method {
+                Method::Get => {
+                    let ticket = get(state.ticket_store, id)?;
+                    Ok(ApiResponse::ok(issue(ticket, &state.project_key)))
+                }
+                Method::Put => {
+                    let body: IssueBody = request.json()?;
+                    let patch = TicketPatch {
+                        title: body.fields.summary.map(Title::new).transpose()?,
+                        description: body.fields.description,
+                    };
+                    state.ticket_store.update_ticket(id, patch);
+                    state.backend.save_ticket(state.ticket_store, id)?;
+                    Ok(ApiResponse::empty(204))
+                }
+                Method::Delete => {
+                    state.ticket_store.delete(id);
+                    state.backend.delete_ticket(state.ticket_store, id)?;
+                    Ok(ApiResponse::empty(204))
+                }
+                _ => method_not_allowed(request),
+            }
+        }
+        (Method::Get, ["issue", key, "comment"]) => {
+            let id = find_issue(state, key)?;
+            Ok(ApiResponse::ok(comments(get(state.ticket_store, id)?)))
+        }
+        (Method::Post, ["issue", key, "comment"]) => {
+            let id = find_issue(state, key)?;
+            let body: CommentBody = request.json()?;
+            state
+                .ticket_store
+                .add_comment_to_ticket(id, Comment::new(body.body)?);
+            state.backend.save_ticket(state.ticket_store, id)?;
+            let ticket = get(state.ticket_store, id)?;
+            let position = ticket.comments.len() - 1;
+            Ok(ApiResponse::json(
+                201,
+                comment(position, &ticket.comments[position]),
+            ))
+        }
+        (Method::Get, ["issue", key, "transitions"]) => {
+            let id = find_issue(state, key)?;
+            let current = get(state.ticket_store, id)?.status;
+            let transitions: Vec<Value> = Status::all()
+                .iter()
+                .filter(|status| **status != current)
+                .map(|status| {
+                    let (id, name, _) = status_details(*status);
+                    json!({ "id": id, "name": name, "to": status_json(*status) })
+                })
+                .collect();
+            Ok(ApiResponse::ok(json!({ "transitions": transitions })))
+        }
+        (Method::Post, ["issue", key, "transitions"]) => {
+            let id = find_issue(state, key)?;
+            let body: TransitionBody = request.json()?;
+            let status = Status::all()
+                .iter()
+                .copied()
+                .find(|status| status_details(*status).0 == body.transition.id)
+                .ok_or_else(|| {
+                    JiraError::Validation(format!(
+                        "There is no transition with id {:?}",
+                        body.transition.id
+                    ))
+                })?;
+            state.ticket_store.update_ticket_status(id, status)?;
+            state.backend.save_ticket(state.ticket_store, id)?;
+            Ok(ApiResponse::empty(204))
+        }
+        (_, ["issue", _, "comment"]) | (_, ["issue", _, "transitions"]) => {
+            method_not_allowed(request)
+        }
+        _ => Err(JiraError::NotFound(format!(
+            "There is no endpoint at {}",
+            request.path
+        ))),
+    }
+}
+
+/// The response describing an error the way Jira does, for clients to show it.
+pub fn error(error: &JiraError) -> ApiResponse {
+    ApiResponse::json(
+        http_status(error),
+        json!({ "errorMessages": [error.to_string()], "errors": {} }),
+    )
+}
+
+fn search(
+    state: &ServerState,
+    jql: &str,
+    start_at: usize,
+    max_results: Option<usize>,
+) -> Result<ApiResponse, JiraError> {
+    let query = Jql::parse(jql, &state.project_key, &state.status_mapping)?;
+    let mut tickets: Vec<&Ticket> = state
+        .ticket_store
+        .list()
+        .into_iter()
+        .filter(|t| query.matches(t))
+        .collect();
+    query.sort(&mut tickets);
+    let max_results = max_results.unwrap_or(DEFAULT_MAX_RESULTS);
+    let issues: Vec<Value> = tickets
+        .iter()
+        .skip(start_at)
+        .take(max_results)
+        .map(|t| issue(t, &state.project_key))
+        .collect();
+    Ok(ApiResponse::ok(json!({
+        "startAt": start_at,
+        "maxResults": max_results,
+        "total": tickets.len(),
+        "issues": issues,
+    })))
+}
+
+/// The id of the ticket with the given issue key: `IRON-12`, the key of an issue imported
+/// from Jira, or the bare id.
+fn find_issue(state: &ServerState, key: &str) -> Result<TicketId, JiraError> {
+    let id = state
+        .ticket_store
+        .find_by_external_key(key)
+        .or_else(|| match key.rfind('-') {
+            Some(index) if key[..index].eq_ignore_ascii_case(&state.project_key) => {
+                key[index + 1..].parse().ok()
+            }
+            Some(_) => None,
+            None => key.parse().ok(),
+        });
+    id.filter(|id| state.ticket_store.get(*id).is_some())
+        .ok_or_else(|| {
+            JiraError::NotFound(
+                "Issue does not exist or you do not have permission to see it.".to_string(),
+            )
+        })
+}
+
+fn get(ticket_store: &TicketStore, id: TicketId) -> Result<&Ticket, JiraError> {
+    ticket_store
+        .get(id)
+        .ok_or_else(|| JiraError::ticket_not_found(id))
+}
+
+fn issue_key(project_key: &str, id: TicketId) -> String {
+    format!("{}-{}", project_key, id)
+}
+
+fn issue_url(id: TicketId) -> String {
+    format!("/rest/api/2/issue/{}", id)
+}
+
+/// A ticket, as Jira represents issues.
+fn issue(ticket: &Ticket, project_key: &str) -> Value {
+    json!({
+        "id": ticket.id.to_string(),
+        "key": issue_key(project_key, ticket.id),
+        "self": issue_url(ticket.id),
+        "fields": {
+            "summary": ticket.title.to_string(),
+            "description": ticket.description,
+            "status": status_json(ticket.status),
+            "issuetype": { "name": "Task" },
+            "project": { "key": project_key, "name": project_key },
+            "created": ticket
+                .created_at
+                .map(|at| at.format("%Y-%m-%dT%H:%M:%S%.3f%z").to_string()),
+            "comment": comments(ticket),
+        }
+    })
+}
+
+fn comments(ticket: &Ticket) -> Value {
+    let comments: Vec<Value> = ticket
+        .comments
+        .iter()
+        .enumerate()
+        .map(|(position, c)| comment(position, c))
+        .collect();
+    json!({
+        "startAt": 0,
+        "maxResults": comments.len(),
+        "total": comments.len(),
+        "comments": comments,
+    })
+}
+
+/// Comments have no id of their own: they are numbered from 1 within their ticket.
+fn comment(position: usize, comment: &Comment) -> Value {
+    let mut value = json!({ "id": (position + 1).to_string(), "body": comment.to_string() });
+    if let Some(author) = comment.author() {
+        value["author"] = json!({ "name": author, "displayName": author });
+    }
+    value
+}
+
+fn status_json(status: Status) -> Value {
+    let (id, name, category) = status_details(status);
+    json!({
+        "id": id,
+        "name": name,
+        "statusCategory": { "key": category },
+    })
+}
+
+/// The id, name and category key of a status in Jira, the id doubling as the id of the
+/// transition to it.
+fn status_details(status: Status) -> (&'static str, &'static str, &'static str) {
+    match status {
+        Status::ToDo => ("1", "To Do", "new"),
+        Status::InProgress => ("2", "In Progress", "indeterminate"),
+        Status::Blocked => ("3", "Blocked", "indeterminate"),
+        Status::Done => ("4", "Done", "done"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::Status;
+    use crate::persistence::InMemoryBackend;
+    use crate::server::{handle, ApiRequest, ApiResponse, ServerState};
+    use crate::store::TicketStore;
+    use tiny_http::Method;
+
+    fn send(state: &mut ServerState, method: Method, url: &str, body: &str) -> ApiResponse {
+        let (path, query) = match url.find('?') {
+            Some(index) => (&url[..index], &url[index + 1..]),
+            None => (url, ""),
+        };
+        let request = ApiRequest {
+            method,
+            path: path.to_string(),
+            query: query
+                .split('&')
+                .filter(|pair| !pair.is_empty())
+                .map(|pair| {
+                    let (key, value) = pair.split_at(pair.find('=').unwrap());
+                    (key.to_string(), value[1..].to_string())
+                })
+                .collect(),
+            authorization: None,
+            body: body.as_bytes().to_vec(),
+        };
+        handle(state, &request)
+    }
+
+    #[test]
+    fn issues_are_created_commented_and_transitioned() {
+        // arrange
+        let mut ticket_store = TicketStore::new();
+        let mut backend = InMemoryBackend::new();
+        let mut state = ServerState::new(&mut ticket_store, &mut backend);
+        let issue = r#"{"fields": {"project": {"key": "IRON"}, "summary": "Fix login",
+                        "issuetype": {"name": "Bug"}}}"#;
+
+        // act
+        let created = send(&mut state, Method::Post, "/rest/api/2/issue", issue);
+        let comment = r#"{"body": "On it"}"#;
+        send(
+            &mut state,
+            Method::Post,
+            "/rest/api/2/issue/IRON-1/comment",
+            comment,
+        );
+        let transitions = send(
+            &mut state,
+            Method::Get,
+            "/rest/api/2/issue/iron-1/transitions",
+            "",
+        );
+        let done = r#"{"transition": {"id": "4"}}"#;
+        let moved = send(
+            &mut state,
+            Method::Post,
+            "/rest/api/2/issue/IRON-1/transitions",
+            done,
+        );
+        let fetched = send(&mut state, Method::Get, "/rest/api/2/issue/IRON-1", "");
+
+        // assert
+        assert_eq!(created.status, 201);
+        assert_eq!(created.body.unwrap()["key"], "IRON-1");
+        assert_eq!(
+            transitions.body.unwrap()["transitions"]
+                .as_array()
+                .unwrap()
+                .len(),
+            3
+        );
+        assert_eq!(moved.status, 204);
+        let fields = fetched.body.unwrap()["fields"].clone();
+        assert_eq!(fields["summary"], "Fix login");
+        assert_eq!(fields["status"]["name"], "Done");
+        assert_eq!(fields["comment"]["comments"][0]["body"], "On it");
+        assert_eq!(ticket_store.get(1).unwrap().status, Status::Done);
+    }
+
+    #[test]
+    fn search_pages_through_the_issues_matching_the_query() {
+        // arrange
+        let mut ticket_store = TicketStore::new();
+        let mut backend = InMemoryBackend::new();
+        let mut state = ServerState::new(&mut ticket_store, &mut backend);
+        for summary in &["Fix login", "Write docs", "Fix logout", "Fix signup"] {
+            let issue = format!(r#"{{"fields": {{"summary": "{}"}}}}"#, summary);
+            send(&mut state, Method::Post, "/rest/api/2/issue", &issue);
+        }
+
+        // act
+        let url = "/rest/api/2/search?jql=summary ~ fix ORDER BY key DESC&startAt=1&maxResults=1";
+        let page = send(&mut state, Method::Get, url, "");
+        let body = r#"{"jql": "status = \"To Do\" AND key in (IRON-1, IRON-2)"}"#;
+        let posted = send(&mut state, Method::Post, "/rest/api/2/search", body);
+        let invalid = send(
+            &mut state,
+            Method::Get,
+            "/rest/api/2/search?jql=assignee = me",
+            "",
+        );
+
+        // assert
+        let page = page.body.unwrap();
+        assert_eq!(page["total"], 3);
+        assert_eq!(page["issues"][0]["key"], "IRON-3");
+        assert_eq!(posted.body.unwrap()["total"], 2);
+        assert_eq!(invalid.status, 400);
+        assert!(invalid.body.unwrap()["errorMessages"][0].is_string());
+    }
+
+    #[test]
+    fn missing_issues_are_reported_like_jira_does() {
+        let mut ticket_store = TicketStore::new();
+        let mut backend = InMemoryBackend::new();
+        let mut state = ServerState::new(&mut ticket_store, &mut backend);
+
+        let response = send(&mut state, Method::Get, "/rest/api/2/issue/IRON-7", "");
+
+        assert_eq!(response.status, 404);
+        assert_eq!(
+            response.body.unwrap()["errorMessages"][0],
+            "Issue does not exist or you do not have permission to see it."
+        );
+    }
+}
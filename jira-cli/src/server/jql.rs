@@ -0,0 +1,523 @@
+use crate::error::JiraError;
+use crate::models::{Status, Ticket};
+use crate::transfer::StatusMapping;
+use std::str::FromStr;
+
+/// A JQL query, limited to the subset most scripts use:
+///
+/// - `project`, `key`, `id` and `status` compared with `=`, `!=`, `in (…)` and `not in (…)`;
+/// - `summary`, `description` and `text` compared with `~` and `!~`;
+/// - clauses combined with `AND`, `OR` and parentheses;
+/// - an `ORDER BY` on `key`, `id`, `created`, `status` or `summary`, `ASC` or `DESC`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Jql {
+    expression: Expression,
+    order_by: Vec<(SortField, bool)>,
+    project_key: String,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+enum Expression {
+    /// Matches every ticket, for an empty query.
+    All,
+    Clause {
+        predicate: Predicate,
+        negated: bool,
+    },
+    And(Vec<Expression>),
+    Or(Vec<Expression>),
+}
+
+#[derive(PartialEq, Debug, Clone)]
+enum Predicate {
+    /// Project keys, uppercased.
+    Project(Vec<String>),
+    /// Issue keys, e.g. `IRON-12` or the key of an issue imported from Jira.
+    Key(Vec<String>),
+    Id(Vec<u64>),
+    Status(Vec<Status>),
+    /// Lowercased text searched in the title, the description or both.
+    Text {
+        summary: bool,
+        description: bool,
+        text: String,
+    },
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+enum SortField {
+    Id,
+    Created,
+    Status,
+    Summary,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+enum Token {
+    Word(String),
+    /// A quoted string, which is never a keyword.
+    Quoted(String),
+    Operator(&'static str),
+    OpenParen,
+    CloseParen,
+    Comma,
+}
+
+/// How deep parentheses can be nested: the parser and the matcher recurse at every level.
+const MAX_NESTING: usize = 32;
+
+const OPERATORS: [&str; 8] = ["!=", "!~", "<=", ">=", "=", "~", "<", ">"];
+
+impl Jql {
+    /// Parse a query about the issues of project `project_key`, resolving Jira status names
+    /// with `mapping`. Anything outside of the supported subset is a
+    /// [Validation](JiraError::Validation) error.
+    pub fn parse(
+        query: &str,
+        project_key: &str,
+        mapping: &StatusMapping,
+    ) -> Result<Jql, JiraError> {
+        let tokens = tokenize(query)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+            mapping,
+            depth: 0,
+        };
+        let expression = if parser.peek().is_none() || parser.peek_keyword("order") {
+            Expression::All
+        } else {
+            parser.expression()?
+        };
+        let order_by = parser.order_by()?;
+        if let Some(token) = parser.peek() {
+            return Err(unsupported(&format!("unexpected {:?}", token)));
+        }
+        Ok(Jql {
+            expression,
+            order_by,
+            project_key: project_key.to_uppercase(),
+        })
+    }
+
+    /// Returns `true` if the ticket satisfies the query.
+    pub fn matches(&self, ticket: &Ticket) -> bool {
+        self.evaluate(&self.expression, ticket)
+    }
+
+    /// Order the tickets as asked by the `ORDER BY` of the query, by id otherwise.
+    pub fn sort(&self, tickets: &mut Vec<&Ticket>) {
+        tickets.sort_by_key(|t| t.id);
+        for (field, descending) in self.order_by.iter().rev() {
+            // Sorting is stable: the first field of the `ORDER BY` ends up deciding.
+            tickets.sort_by(|a, b| {
+                let ordering = match field {
+                    SortField::Id => a.id.cmp(&b.id),
+                    SortField::Created => a.created_at.cmp(&b.created_at),
+                    SortField::Status => status_rank(a.status).cmp(&status_rank(b.status)),
+                    SortField::Summary => a.title.to_string().cmp(&b.title.to_string()),
+                };
+                if *descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            });
+        }
+    }
+
+    fn evaluate(&self, expression: &Expression, ticket: &Ticket) -> bool {
+        match expression {
+            Expression::All => true,
+            Expression::And(expressions) => expressions.iter().all(|e| self.evaluate(e, ticket)),
+            Expression::Or(expressions) => expressions.iter().any(|e| self.evaluate(e, ticket)),
+            Expression::Clause { predicate, negated } => {
+                let matches = match predicate {
+                    Predicate::Project(keys) => keys.contains(&self.project_key),
+                    Predicate::Key(keys) => {
+                        let key = format!("{}-{}", self.project_key, ticket.id);
+                        keys.iter().any(|k| {
+                            k.eq_ignore_ascii_case(&key)
+                                || Some(k.as_str()) == ticket.external_key.as_deref()
+                        })
+                    }
+                    Predicate::Id(ids) => ids.contains(&ticket.id),
+                    Predicate::Status(statuses) => statuses.contains(&ticket.status),
+                    Predicate::Text {
+                        summary,
+                        description,
+                        text,
+                    } => {
+                        (*summary && ticket.title.to_string().to_lowercase().contains(text))
+                            || (*description && ticket.description.to_lowercase().contains(text))
+                    }
+                };
+                matches != *negated
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    mapping: &'a StatusMapping,
+    /// Number of parentheses opened around the current position.
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), JiraError> {
+        if self.peek_keyword(keyword) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(unsupported(&format!("expected {}", keyword.to_uppercase())))
+        }
+    }
+
+    /// `and ( OR and )*`
+    fn expression(&mut self) -> Result<Expression, JiraError> {
+        let mut alternatives = vec![self.and()?];
+        while self.peek_keyword("or") {
+            self.position += 1;
+            alternatives.push(self.and()?);
+        }
+        Ok(if alternatives.len() == 1 {
+            alternatives.remove(0)
+        } else {
+            Expression::Or(alternatives)
+        })
+    }
+
+    /// `primary ( AND primary )*`
+    fn and(&mut self) -> Result<Expression, JiraError> {
+        let mut conditions = vec![self.primary()?];
+        while self.peek_keyword("and") {
+            self.position += 1;
+            conditions.push(self.primary()?);
+        }
+        Ok(if conditions.len() == 1 {
+            conditions.remove(0)
+        } else {
+            Expression::And(conditions)
+        })
+    }
+
+    /// `( expression )` or a clause.
+    fn primary(&mut self) -> Result<Expression, JiraError> {
+        if self.peek() == Some(&Token::OpenParen) {
+            if self.depth == MAX_NESTING {
+                return Err(unsupported(&format!(
+                    "more than {} nested parentheses",
+                    MAX_NESTING
+                )));
+            }
+            self.position += 1;
+            self.depth += 1;
+            let expression = self.expression()?;
+            self.depth -= 1;
+            return match self.next() {
+                Some(Token::CloseParen) => Ok(expression),
+                _ => Err(unsupported("missing )")),
+            };
+        }
+        self.clause()
+    }
+
+    fn clause(&mut self) -> Result<Expression, JiraError> {
+        let field = match self.next() {
+            Some(Token::Word(field)) | Some(Token::Quoted(field)) => field.to_lowercase(),
+            token => return Err(unsupported(&format!("expected a field, found {:?}", token))),
+        };
+        let (operator, values) = match self.next() {
+            Some(Token::Operator(operator)) => (operator, vec![self.value()?]),
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case("in") => ("in", self.list()?),
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case("not") => {
+                self.expect_keyword("in")?;
+                ("not in", self.list()?)
+            }
+            token => {
+                return Err(unsupported(&format!(
+                    "expected an operator after {}, found {:?}",
+                    field, token
+                )))
+            }
+        };
+
+        let (negated, text_search) = match operator {
+            "=" | "in" => (false, false),
+            "!=" | "not in" => (true, false),
+            "~" => (false, true),
+            "!~" => (true, true),
+            _ => return Err(unsupported(&format!("the {} operator", operator))),
+        };
+        let predicate = match (field.as_str(), text_search) {
+            ("project", false) => {
+                Predicate::Project(values.iter().map(|v| v.to_uppercase()).collect())
+            }
+            ("key" | "issuekey", false) => Predicate::Key(values),
+            ("id", false) => Predicate::Id(
+                values
+                    .iter()
+                    .map(|v| {
+                        v.parse()
+                            .map_err(|_| unsupported(&format!("{:?} is not an id", v)))
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            ("status", false) => Predicate::Status(
+                values
+                    .iter()
+                    .map(|v| self.status(v))
+                    .collect::<Result<_, _>>()?,
+            ),
+            ("summary" | "description" | "text", true) => {
+                if values.len() != 1 {
+                    return Err(unsupported("~ takes a single value"));
+                }
+                Predicate::Text {
+                    summary: field != "description",
+                    description: field != "summary",
+                    text: values[0].to_lowercase(),
+                }
+            }
+            _ => {
+                return Err(unsupported(&format!(
+                    "the {} operator on {}",
+                    operator, field
+                )))
+            }
+        };
+        Ok(Expression::Clause { predicate, negated })
+    }
+
+    fn value(&mut self) -> Result<String, JiraError> {
+        match self.next() {
+            Some(Token::Word(value)) | Some(Token::Quoted(value)) => Ok(value),
+            token => Err(unsupported(&format!("expected a value, found {:?}", token))),
+        }
+    }
+
+    /// `( value ( , value )* )`
+    fn list(&mut self) -> Result<Vec<String>, JiraError> {
+        if self.next() != Some(Token::OpenParen) {
+            return Err(unsupported("expected ( after IN"));
+        }
+        let mut values = vec![self.value()?];
+        loop {
+            match self.next() {
+                Some(Token::Comma) => values.push(self.value()?),
+                Some(Token::CloseParen) => return Ok(values),
+                _ => return Err(unsupported("missing ) after the values of IN")),
+            }
+        }
+    }
+
+    /// `ORDER BY field [ASC | DESC] ( , field [ASC | DESC] )*`, if present.
+    fn order_by(&mut self) -> Result<Vec<(SortField, bool)>, JiraError> {
+        let mut order_by = Vec::new();
+        if !self.peek_keyword("order") {
+            return Ok(order_by);
+        }
+        self.position += 1;
+        self.expect_keyword("by")?;
+        loop {
+            let field = match self.value()?.to_lowercase().as_str() {
+                "key" | "id" => SortField::Id,
+                "created" => SortField::Created,
+                "status" => SortField::Status,
+                "summary" => SortField::Summary,
+                field => return Err(unsupported(&format!("ordering by {}", field))),
+            };
+            let descending = if self.peek_keyword("desc") {
+                self.position += 1;
+                true
+            } else {
+                if self.peek_keyword("asc") {
+                    self.position += 1;
+                }
+                false
+            };
+            order_by.push((field, descending));
+            if self.peek() != Some(&Token::Comma) {
+                return Ok(order_by);
+            }
+            self.position += 1;
+        }
+    }
+
+    fn status(&self, name: &str) -> Result<Status, JiraError> {
+        self.mapping
+            .get(name)
+            .or_else(|| Status::from_str(name).ok())
+            .ok_or_else(|| {
+                JiraError::Validation(format!(
+                    "The status {:?} in the JQL query does not exist. \
+                     Map it to a status with jira.status_mapping in the configuration file.",
+                    name
+                ))
+            })
+    }
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>, JiraError> {
+    let mut tokens = Vec::new();
+    let mut rest = query.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let length = match c {
+            '(' | ')' | ',' => {
+                tokens.push(match c {
+                    '(' => Token::OpenParen,
+                    ')' => Token::CloseParen,
+                    _ => Token::Comma,
+                });
+                1
+            }
+            '"' | '\'' => {
+                let end = rest[1..]
+                    .find(c)
+                    .ok_or_else(|| unsupported("unterminated string"))?;
+                tokens.push(Token::Quoted(rest[1..=end].to_string()));
+                end + 2
+            }
+            _ => match OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+                Some(operator) => {
+                    tokens.push(Token::Operator(operator));
+                    operator.len()
+                }
+                None => {
+                    let end = rest
+                        .find(|c: char| c.is_whitespace() || "()=,!~<>\"'".contains(c))
+                        .unwrap_or(rest.len());
+                    if end == 0 {
+                        return Err(unsupported(&format!("unexpected {:?}", c)));
+                    }
+                    tokens.push(Token::Word(rest[..end].to_string()));
+                    end
+                }
+            },
+        };
+        rest = rest[length..].trim_start();
+    }
+    Ok(tokens)
+}
+
+fn status_rank(status: Status) -> Option<usize> {
+    Status::all().iter().position(|s| *s == status)
+}
+
+fn unsupported(details: &str) -> JiraError {
+    JiraError::Validation(format!(
+        "The JQL query is not supported by IronJira: {}.",
+        details
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::{Status, Ticket, TicketDraft, Title};
+    use crate::server::Jql;
+    use crate::store::TicketStore;
+    use crate::transfer::StatusMapping;
+
+    fn ticket_store() -> TicketStore {
+        let mut ticket_store = TicketStore::new();
+        for (title, status) in &[
+            ("Fix login", Status::InProgress),
+            ("Write docs", Status::ToDo),
+            ("Fix logout", Status::Done),
+        ] {
+            let id = ticket_store.create(TicketDraft {
+                title: Title::new(title.to_string()).unwrap(),
+                description: String::new(),
+            });
+            ticket_store.force_ticket_status(id, *status);
+        }
+        ticket_store
+    }
+
+    fn search(ticket_store: &TicketStore, query: &str) -> Vec<u64> {
+        let jql = Jql::parse(query, "IRON", &StatusMapping::new(&Default::default())).unwrap();
+        let mut tickets: Vec<&Ticket> = ticket_store
+            .list()
+            .into_iter()
+            .filter(|t| jql.matches(t))
+            .collect();
+        jql.sort(&mut tickets);
+        tickets.iter().map(|t| t.id).collect()
+    }
+
+    #[test]
+    fn clauses_are_combined_with_and_or_and_parentheses() {
+        let ticket_store = ticket_store();
+
+        assert_eq!(search(&ticket_store, ""), vec![1, 2, 3]);
+        assert_eq!(
+            search(&ticket_store, "project = iron AND status = 'In Progress'"),
+            vec![1]
+        );
+        assert_eq!(
+            search(&ticket_store, "status not in (Done, \"To Do\")"),
+            vec![1]
+        );
+        assert_eq!(
+            search(&ticket_store, "summary ~ fix AND status != done"),
+            vec![1]
+        );
+        assert_eq!(
+            search(
+                &ticket_store,
+                "(key = IRON-2 OR text ~ logout) AND project = IRON"
+            ),
+            vec![2, 3]
+        );
+        assert!(search(&ticket_store, "project = OTHER").is_empty());
+    }
+
+    #[test]
+    fn results_are_ordered_by_the_order_by_clause() {
+        let ticket_store = ticket_store();
+
+        assert_eq!(search(&ticket_store, "ORDER BY key DESC"), vec![3, 2, 1]);
+        assert_eq!(
+            search(&ticket_store, "summary ~ fix order by summary desc"),
+            vec![3, 1]
+        );
+        assert_eq!(
+            search(&ticket_store, "order by status, key desc"),
+            vec![2, 1, 3]
+        );
+    }
+
+    #[test]
+    fn unsupported_queries_are_rejected() {
+        let mapping = StatusMapping::new(&Default::default());
+
+        for query in &[
+            "assignee = currentUser()",
+            "status = Nonexistent",
+            "summary = 'exact'",
+            "status in (Done",
+            "created > -1w",
+            "status = Done AND",
+            format!("{}id = 1{}", "(".repeat(100_000), ")".repeat(100_000)).as_str(),
+        ] {
+            assert!(Jql::parse(query, "IRON", &mapping).is_err(), "{}", query);
+        }
+    }
+}
@@ -0,0 +1,531 @@
+use crate::error::JiraError;
+use crate::persistence::StorageBackend;
+use crate::store::TicketStore;
+use crate::transfer::StatusMapping;
+use crate::webhooks::{self, DeliveryQueue};
+use chrono::Utc;
+use serde::Serialize;
+use std::io::Read;
+use std::sync::mpsc;
+use std::thread;
+use tiny_http::{Header, Method, Server};
+
+pub mod graphql;
+mod jira;
+mod jql;
+mod rest;
+mod sync;
+
+pub use jql::*;
+pub use rest::*;
+pub use sync::*;
+
+/// Largest request body accepted, in bytes.
+const MAX_BODY_SIZE: u64 = 1024 * 1024;
+
+/// What the handlers of the API work with, shared by all requests.
+pub struct ServerState<'a> {
+    pub ticket_store: &'a mut TicketStore,
+    /// Where every change is saved before responding.
+    pub backend: &'a mut dyn StorageBackend,
+    /// Key of the project in the Jira-compatible API, e.g. `IRON` for issue `IRON-12`.
+    pub project_key: String,
+    /// The [Status] of the Jira status names used in JQL queries.
+    pub status_mapping: StatusMapping,
+    /// Where the events of the tickets changed by requests are sent, if anywhere.
+    pub webhooks: Option<&'a mut DeliveryQueue>,
+}
+
+/// An HTTP request, as seen by the handlers of the API.
+#[derive(PartialEq, Debug, Clone)]
+pub struct ApiRequest {
+    pub method: Method,
+    /// The path of the URL, without the query string, e.g. `/tickets/12`.
+    pub path: String,
+    /// The decoded parameters of the query string, in order. A parameter can be repeated.
+    pub query: Vec<(String, String)>,
+    /// The `Authorization` header, e.g. `Bearer ij_1a2b3c4d_…`.
+    pub authorization: Option<String>,
+    pub body: Vec<u8>,
+}
+
+/// The response of a handler of the API.
+#[derive(PartialEq, Debug, Clone)]
+pub struct ApiResponse {
+    pub status: u16,
+    /// The JSON body, if any.
+    pub body: Option<serde_json::Value>,
+    /// Where the resource created by the request can be found.
+    pub location: Option<String>,
+}
+
+impl<'a> ServerState<'a> {
+    /// Serve `ticket_store`, saving changes with `backend`, as project `IRON` with the
+    /// default Jira statuses.
+    pub fn new(ticket_store: &'a mut TicketStore, backend: &'a mut dyn StorageBackend) -> Self {
+        Self {
+            ticket_store,
+            backend,
+            project_key: "IRON".to_string(),
+            status_mapping: StatusMapping::new(&Default::default()),
+            webhooks: None,
+        }
+    }
+
+    /// Use another project key in the Jira-compatible API.
+    pub fn with_project_key(mut self, project_key: &str) -> Self {
+        self.project_key = project_key.to_string();
+        self
+    }
+
+    /// Recognise other Jira status names in JQL queries.
+    pub fn with_status_mapping(mut self, status_mapping: StatusMapping) -> Self {
+        self.status_mapping = status_mapping;
+        self
+    }
+
+    /// Send the events of the tickets changed by requests to webhooks.
+    pub fn with_webhooks(mut self, queue: &'a mut DeliveryQueue) -> Self {
+        self.webhooks = Some(queue);
+        self
+    }
+}
+
+impl ApiResponse {
+    /// A `200 OK` response with a JSON body.
+    pub fn ok(body: impl Serialize) -> Self {
+        Self::json(200, body)
+    }
+
+    /// A response with the given status and a JSON body.
+    pub fn json(status: u16, body: impl Serialize) -> Self {
+        Self {
+            status,
+            // Our own types always serialise to JSON.
+            body: Some(serde_json::to_value(body).unwrap_or_default()),
+            location: None,
+        }
+    }
+
+    /// A response without body, e.g. `204 No Content`.
+    pub fn empty(status: u16) -> Self {
+        Self {
+            status,
+            body: None,
+            location: None,
+        }
+    }
+
+    /// Add the location of the resource created by the request.
+    pub fn located_at(mut self, location: String) -> Self {
+        self.location = Some(location);
+        self
+    }
+
+    /// The response describing an error, e.g. `404 Not Found` for a missing ticket.
+    pub fn error(error: &JiraError) -> Self {
+        Self::json(
+            http_status(error),
+            serde_json::json!({ "error": error.to_string() }),
+        )
+    }
+}
+
+impl ApiRequest {
+    /// The values of a query parameter, in the order they were given.
+    pub fn query_values(&self, name: &str) -> Vec<&str> {
+        self.query
+            .iter()
+            .filter(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    /// Deserialise the JSON body of the request.
+    pub fn json<'a, T: serde::Deserialize<'a>>(&'a self) -> Result<T, JiraError> {
+        serde_json::from_slice(&self.body)
+            .map_err(|e| JiraError::Validation(format!("The request body is not valid: {}", e)))
+    }
+}
+
+/// The HTTP status code matching each kind of [JiraError](JiraError).
+pub fn http_status(error: &JiraError) -> u16 {
+    match error {
+        JiraError::Validation(_) => 400,
+        JiraError::NotFound(_) => 404,
+        JiraError::Conflict(_) => 409,
+        JiraError::Unauthorized(_) => 401,
+        JiraError::Io(_) | JiraError::Parse(_) => 500,
+    }
+}
+
+/// Serve the API on `address`, e.g. `127.0.0.1:8080`, until the process is stopped.
+///
+/// Requests are handled one at a time against the same store, and every change is saved
+/// before responding: clients never see a change that was not persisted. Webhooks are
+/// notified from another thread once the response is sent, so that a slow receiver never
+/// holds up the requests.
+pub fn serve(address: &str, state: &mut ServerState) -> Result<(), JiraError> {
+    let server = Server::http(address)
+        .map_err(|e| JiraError::io(&format!("Failed to listen on {}", address), e))?;
+    println!("Serving the ticket store on http://{}", address);
+    let queue = state.webhooks.take();
+    thread::scope(|scope| {
+        let events = queue.map(|queue| {
+            let (sender, receiver) = mpsc::channel();
+            scope.spawn(move || webhooks::deliver_in_background(queue, receiver));
+            sender
+        });
+        loop {
+            let request = server
+                .recv()
+                .map_err(|e| JiraError::io("Failed to receive a request", e))?;
+            respond(state, request);
+            match &events {
+                // The delivery thread only stops once the sender is dropped.
+                Some(events) => events
+                    .send(webhooks::prepare(state.ticket_store))
+                    .unwrap_or_default(),
+                // Nobody is listening: the events are dropped.
+                None => drop(state.ticket_store.take_events()),
+            }
+        }
+    })
+}
+
+/// Handle a request and send back the response.
+fn respond(state: &mut ServerState, mut request: tiny_http::Request) {
+    let mut body = Vec::new();
+    let read = Read::take(request.as_reader(), MAX_BODY_SIZE + 1).read_to_end(&mut body);
+    let response = match read {
+        Ok(size) if size as u64 > MAX_BODY_SIZE => ApiResponse::json(
+            413,
+            serde_json::json!({
+                "error": format!("Request bodies are limited to {} bytes", MAX_BODY_SIZE)
+            }),
+        ),
+        Ok(_) => {
+            let (path, query) = split_url(request.url());
+            let authorization = request
+                .headers()
+                .iter()
+                .find(|header| header.field.equiv("Authorization"))
+                .map(|header| header.value.to_string());
+            let api_request = ApiRequest {
+                method: request.method().clone(),
+                path,
+                query,
+                authorization,
+                body,
+            };
+            handle(state, &api_request)
+        }
+        Err(e) => ApiResponse::error(&JiraError::io("Failed to read the request", e)),
+    };
+    if let Err(e) = request.respond(to_http_response(response)) {
+        eprintln!("Failed to send a response: {}", e);
+    }
+}
+
+/// Authenticate a request and route it to the handler of its endpoint.
+///
+/// Changes are recorded as made by the authenticated user, if any. A request that fails
+/// leaves the store as it found it, so that a change that could not be saved is neither
+/// served nor sent to the webhooks.
+pub fn handle(state: &mut ServerState, request: &ApiRequest) -> ApiResponse {
+    let snapshot = may_change(request).then(|| state.ticket_store.clone());
+    let result = authenticate(state.ticket_store, request).and_then(|user| {
+        state.ticket_store.set_author(user);
+        route(state, request)
+    });
+    state.ticket_store.set_author(None);
+    result.unwrap_or_else(|e| {
+        if let Some(snapshot) = snapshot {
+            *state.ticket_store = snapshot;
+        }
+        if is_jira(request) {
+            jira::error(&e)
+        } else {
+            ApiResponse::error(&e)
+        }
+    })
+}
+
+/// Whether a request is for the Jira-compatible API, which reports errors its own way.
+fn is_jira(request: &ApiRequest) -> bool {
+    request.path.starts_with("/rest/api/2/") || request.path == "/rest/api/2"
+}
+
+/// Whether a request may change the store: anything but reads, bearing in mind that GraphQL
+/// mutations can be sent with `GET`.
+fn may_change(request: &ApiRequest) -> bool {
+    request.method != Method::Get || request.path.trim_end_matches('/') == "/graphql"
+}
+
+/// The user a request is authenticated as, from its `Authorization: Bearer <token>` header.
+///
+/// Requests need a token as soon as a user exists: until then the API is open to anyone,
+/// and nobody is recorded as the author of changes.
+fn authenticate(
+    ticket_store: &TicketStore,
+    request: &ApiRequest,
+) -> Result<Option<String>, JiraError> {
+    if ticket_store.list_users().is_empty() {
+        return Ok(None);
+    }
+    let token = request
+        .authorization
+        .as_deref()
+        .and_then(|authorization| authorization.strip_prefix("Bearer "))
+        .ok_or_else(|| {
+            JiraError::Unauthorized(
+                "This server needs an API token, sent as `Authorization: Bearer <token>`."
+                    .to_string(),
+            )
+        })?;
+    ticket_store
+        .authenticate(token.trim(), Utc::now())
+        .map(|user| Some(user.to_string()))
+        .ok_or_else(|| {
+            JiraError::Unauthorized(
+                "The API token is not valid: it may have expired or been revoked.".to_string(),
+            )
+        })
+}
+
+/// Route a request to the handler of its endpoint.
+fn route(state: &mut ServerState, request: &ApiRequest) -> Result<ApiResponse, JiraError> {
+    let segments: Vec<&str> = request
+        .path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    match segments.as_slice() {
+        ["tickets", ..] => rest::handle(state, request, &segments[1..]),
+        ["rest", "api", "2", ..] => jira::handle(state, request, &segments[3..]),
+        ["graphql"] => graphql::handle(state, request),
+        ["store", ..] => sync::handle(state, request, &segments[1..]),
+        _ => Err(JiraError::NotFound(format!(
+            "There is no endpoint at {}",
+            request.path
+        ))),
+    }
+}
+
+/// The error of a request using a method the endpoint does not support.
+fn method_not_allowed(request: &ApiRequest) -> Result<ApiResponse, JiraError> {
+    Ok(ApiResponse::json(
+        405,
+        serde_json::json!({
+            "error": format!("{} is not supported on {}", request.method, request.path)
+        }),
+    ))
+}
+
+fn to_http_response(response: ApiResponse) -> tiny_http::Response<std::io::Cursor<Vec<u8>>> {
+    let has_body = response.body.is_some();
+    let body = response
+        .body
+        .map(|body| body.to_string().into_bytes())
+        .unwrap_or_default();
+    let mut http_response = tiny_http::Response::from_data(body).with_status_code(response.status);
+    if has_body {
+        if let Ok(header) = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]) {
+            http_response.add_header(header);
+        }
+    }
+    if response.status == 401 {
+        if let Ok(header) = Header::from_bytes(&b"WWW-Authenticate"[..], &b"Bearer"[..]) {
+            http_response.add_header(header);
+        }
+    }
+    if let Some(location) = response.location {
+        if let Ok(header) = Header::from_bytes(&b"Location"[..], location.as_bytes()) {
+            http_response.add_header(header);
+        }
+    }
+    http_response
+}
+
+/// Split a URL into its path and the decoded parameters of its query string.
+fn split_url(url: &str) -> (String, Vec<(String, String)>) {
+    let (path, query) = match url.find('?') {
+        Some(index) => (&url[..index], &url[index + 1..]),
+        None => (url, ""),
+    };
+    let query = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.find('=') {
+            Some(index) => (decode(&pair[..index]), decode(&pair[index + 1..])),
+            None => (decode(pair), String::new()),
+        })
+        .collect();
+    (decode(path), query)
+}
+
+/// Decode a component of a URL: `+` is a space and `%XX` an escaped byte.
+fn decode(component: &str) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (escaped, bytes[i]) {
+            (Some(byte), _) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (None, b'+') => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            (None, byte) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::JiraError;
+    use crate::models::{TicketDraft, Title};
+    use crate::persistence::{InMemoryBackend, StorageBackend};
+    use crate::server::{handle, http_status, split_url, ApiRequest, ServerState};
+    use crate::store::TicketStore;
+    use tiny_http::Method;
+
+    #[test]
+    fn requests_need_a_valid_token_once_a_user_exists() {
+        // arrange
+        let mut ticket_store = TicketStore::new();
+        let mut backend = InMemoryBackend::new();
+        let id = ticket_store.create(TicketDraft {
+            title: Title::new("Fix login".to_string()).unwrap(),
+            description: String::new(),
+        });
+        let (_, secret) = ticket_store.create_token("alice", None).unwrap();
+        let comment = |authorization: Option<String>| ApiRequest {
+            method: Method::Post,
+            path: format!("/tickets/{}/comments", id),
+            query: Vec::new(),
+            authorization,
+            body: br#"{"comment": "On it"}"#.to_vec(),
+        };
+        let mut state = ServerState::new(&mut ticket_store, &mut backend);
+
+        // act
+        let anonymous = handle(&mut state, &comment(None));
+        let forged = handle(
+            &mut state,
+            &comment(Some("Bearer ij_00000000_guess".to_string())),
+        );
+        let authenticated = handle(&mut state, &comment(Some(format!("Bearer {}", secret))));
+
+        // assert
+        assert_eq!(anonymous.status, 401);
+        assert_eq!(forged.status, 401);
+        assert_eq!(authenticated.status, 201);
+        let comments = &ticket_store.get(id).unwrap().comments;
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments[0].author(), Some("alice"));
+    }
+
+    /// A backend whose disk is always full.
+    struct FailingBackend;
+
+    impl StorageBackend for FailingBackend {
+        fn load(&mut self) -> Result<TicketStore, JiraError> {
+            Ok(TicketStore::new())
+        }
+
+        fn save(&mut self, _ticket_store: &TicketStore) -> Result<(), JiraError> {
+            Err(JiraError::Io("No space left on device".to_string()))
+        }
+    }
+
+    #[test]
+    fn changes_that_could_not_be_saved_are_rolled_back() {
+        // arrange
+        let mut ticket_store = TicketStore::new();
+        let mut backend = FailingBackend;
+        let request = ApiRequest {
+            method: Method::Post,
+            path: "/tickets".to_string(),
+            query: Vec::new(),
+            authorization: None,
+            body: br#"{"title": "Fix login"}"#.to_vec(),
+        };
+
+        // act
+        let response = handle(
+            &mut ServerState::new(&mut ticket_store, &mut backend),
+            &request,
+        );
+
+        // assert
+        assert_eq!(response.status, 500);
+        assert!(ticket_store.list().is_empty());
+        assert!(ticket_store.take_events().is_empty());
+    }
+
+    #[test]
+    fn issues_that_could_not_be_saved_are_rolled_back() {
+        // arrange
+        let mut ticket_store = TicketStore::new();
+        let mut backend = FailingBackend;
+        let request = ApiRequest {
+            method: Method::Post,
+            path: "/rest/api/2/issue".to_string(),
+            query: Vec::new(),
+            authorization: None,
+            body: br#"{"fields": {"summary": "Fix login"}}"#.to_vec(),
+        };
+
+        // act
+        let response = handle(
+            &mut ServerState::new(&mut ticket_store, &mut backend),
+            &request,
+        );
+
+        // assert
+        assert_eq!(response.status, 500);
+        assert_eq!(
+            response.body.unwrap()["errorMessages"][0],
+            "No space left on device"
+        );
+        assert!(ticket_store.list().is_empty());
+        assert!(ticket_store.take_events().is_empty());
+    }
+
+    #[test]
+    fn query_strings_are_decoded() {
+        let (path, query) = split_url("/tickets?status=todo&text=fix+the%20login%21&status=done");
+
+        assert_eq!(path, "/tickets");
+        assert_eq!(
+            query,
+            vec![
+                ("status".to_string(), "todo".to_string()),
+                ("text".to_string(), "fix the login!".to_string()),
+                ("status".to_string(), "done".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn errors_map_to_http_status_codes() {
+        assert_eq!(http_status(&JiraError::Validation(String::new())), 400);
+        assert_eq!(http_status(&JiraError::ticket_not_found(1)), 404);
+        assert_eq!(http_status(&JiraError::Conflict(String::new())), 409);
+        assert_eq!(http_status(&JiraError::Unauthorized(String::new())), 401);
+        assert_eq!(http_status(&JiraError::Io(String::new())), 500);
+    }
+}
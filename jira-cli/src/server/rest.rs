@@ -0,0 +1,356 @@
+use crate::error::JiraError;
+use crate::models::{
+    Comment, LinkedCommit, Status, StatusChange, Ticket, TicketDraft, TicketFilter, TicketId,
+    TicketPatch, Title,
+};
+use crate::server::{method_not_allowed, ApiRequest, ApiResponse, ServerState};
+use crate::store::TicketStore;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use tiny_http::Method;
+
+/// A [Ticket](Ticket) as represented by the API.
+#[derive(PartialEq, Debug, Clone, Serialize)]
+pub struct TicketResource {
+    pub id: TicketId,
+    pub title: String,
+    pub description: String,
+    pub status: Status,
+    pub comments: Vec<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub history: Vec<StatusChange>,
+    pub external_key: Option<String>,
+    pub commits: Vec<LinkedCommit>,
+    pub related: Vec<TicketId>,
+}
+
+impl From<&Ticket> for TicketResource {
+    fn from(ticket: &Ticket) -> Self {
+        Self {
+            id: ticket.id,
+            title: ticket.title.to_string(),
+            description: ticket.description.clone(),
+            status: ticket.status,
+            comments: ticket.comments.iter().map(|c| c.to_string()).collect(),
+            created_at: ticket.created_at,
+            history: ticket.history.clone(),
+            external_key: ticket.external_key.clone(),
+            commits: ticket.commits.clone(),
+            related: ticket.related.clone(),
+        }
+    }
+}
+
+/// Body of `POST /tickets`.
+#[derive(Deserialize)]
+struct CreateTicket {
+    title: String,
+    #[serde(default)]
+    description: String,
+}
+
+/// Body of `PATCH /tickets/{id}`: only the given fields are changed.
+#[derive(Deserialize)]
+struct PatchTicket {
+    title: Option<String>,
+    description: Option<String>,
+}
+
+/// Body of `POST /tickets/{id}/status`.
+#[derive(Deserialize)]
+struct MoveTicket {
+    status: String,
+    /// Move the ticket even if the new status is at its work-in-progress limit.
+    #[serde(default)]
+    force: bool,
+}
+
+/// Body of `POST /tickets/{id}/comments`.
+#[derive(Deserialize)]
+struct AddComment {
+    comment: String,
+}
+
+/// Handle the requests on `/tickets`, `segments` being the path after it:
+///
+/// - `GET /tickets`, filtered by `status` (repeatable) and `text` query parameters;
+/// - `POST /tickets`;
+/// - `GET`, `PATCH` and `DELETE /tickets/{id}`;
+/// - `POST /tickets/{id}/status`, answering `409 Conflict` at a work-in-progress limit;
+/// - `POST /tickets/{id}/comments`.
+pub fn handle(
+    state: &mut ServerState,
+    request: &ApiRequest,
+    segments: &[&str],
+) -> Result<ApiResponse, JiraError> {
+    let ServerState {
+        ticket_store,
+        backend,
+        ..
+    } = state;
+    let ticket_store: &mut TicketStore = ticket_store;
+    match (&request.method, segments) {
+        (Method::Get, []) => list(ticket_store, request),
+        (Method::Post, []) => {
+            let body: CreateTicket = request.json()?;
+            let draft = TicketDraft {
+                title: Title::new(body.title)?,
+                description: body.description,
+            };
+            let id = ticket_store.create(draft);
+            backend.save_ticket(ticket_store, id)?;
+            Ok(ticket_response(201, ticket_store, id)?.located_at(format!("/tickets/{}", id)))
+        }
+        (_, []) => method_not_allowed(request),
+        (method, [id]) => {
+            let id = parse_id(id)?;
+            match method {
+                Method::Get => ticket_response(200, ticket_store, id),
+                Method::Patch => {
+                    let body: PatchTicket = request.json()?;
+                    let patch = TicketPatch {
+                        title: body.title.map(Title::new).transpose()?,
+                        description: body.description,
+                    };
+                    ticket_store
+                        .update_ticket(id, patch)
+                        .ok_or_else(|| JiraError::ticket_not_found(id))?;
+                    backend.save_ticket(ticket_store, id)?;
+                    ticket_response(200, ticket_store, id)
+                }
+                Method::Delete => {
+                    ticket_store
+                        .delete(id)
+                        .ok_or_else(|| JiraError::ticket_not_found(id))?;
+                    backend.delete_ticket(ticket_store, id)?;
+                    Ok(ApiResponse::empty(204))
+                }
+                _ => method_not_allowed(request),
+            }
+        }
+        (Method::Post, [id, "status"]) => {
+            let id = parse_id(id)?;
+            let body: MoveTicket = request.json()?;
+            let status =
+                Status::from_str(&body.status).map_err(|e| JiraError::Validation(e.to_string()))?;
+            if body.force {
+                ticket_store
+                    .force_ticket_status(id, status)
+                    .ok_or_else(|| JiraError::ticket_not_found(id))?;
+            } else {
+                ticket_store.update_ticket_status(id, status)?;
+            }
+            backend.save_ticket(ticket_store, id)?;
+            ticket_response(200, ticket_store, id)
+        }
+        (Method::Post, [id, "comments"]) => {
+            let id = parse_id(id)?;
+            let body: AddComment = request.json()?;
+            ticket_store
+                .add_comment_to_ticket(id, Comment::new(body.comment)?)
+                .ok_or_else(|| JiraError::ticket_not_found(id))?;
+            backend.save_ticket(ticket_store, id)?;
+            ticket_response(201, ticket_store, id)
+        }
+        (_, [_, "status"]) | (_, [_, "comments"]) => method_not_allowed(request),
+        _ => Err(JiraError::NotFound(format!(
+            "There is no endpoint at {}",
+            request.path
+        ))),
+    }
+}
+
+/// The tickets matching the query parameters, in the order of their ids.
+fn list(ticket_store: &TicketStore, request: &ApiRequest) -> Result<ApiResponse, JiraError> {
+    let statuses = request
+        .query_values("status")
+        .into_iter()
+        .map(|status| Status::from_str(status).map_err(|e| JiraError::Validation(e.to_string())))
+        .collect::<Result<Vec<Status>, JiraError>>()?;
+    let filter = TicketFilter {
+        statuses,
+        text: request.query_values("text").first().map(|t| t.to_string()),
+    };
+    let mut tickets: Vec<TicketResource> = ticket_store
+        .list()
+        .into_iter()
+        .filter(|t| filter.matches(t))
+        .map(TicketResource::from)
+        .collect();
+    tickets.sort_by_key(|t| t.id);
+    Ok(ApiResponse::ok(tickets))
+}
+
+fn ticket_response(
+    status: u16,
+    ticket_store: &TicketStore,
+    id: TicketId,
+) -> Result<ApiResponse, JiraError> {
+    let ticket = ticket_store
+        .get(id)
+        .ok_or_else(|| JiraError::ticket_not_found(id))?;
+    Ok(ApiResponse::json(status, TicketResource::from(ticket)))
+}
+
+fn parse_id(id: &str) -> Result<TicketId, JiraError> {
+    id.parse()
+        .map_err(|_| JiraError::NotFound(format!("{:?} is not a ticket id", id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::Status;
+    use crate::persistence::{InMemoryBackend, StorageBackend};
+    use crate::server::{handle, ApiRequest, ApiResponse, ServerState};
+    use crate::store::TicketStore;
+    use tiny_http::Method;
+
+    fn request(method: Method, path: &str, body: &str) -> ApiRequest {
+        ApiRequest {
+            method,
+            path: path.to_string(),
+            query: Vec::new(),
+            authorization: None,
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    fn field(response: &ApiResponse, name: &str) -> serde_json::Value {
+        response.body.as_ref().unwrap()[name].clone()
+    }
+
+    #[test]
+    fn tickets_are_created_changed_and_saved() {
+        // arrange
+        let mut ticket_store = TicketStore::new();
+        let mut backend = InMemoryBackend::new();
+        let mut send = |method, path: &str, body: &str| {
+            handle(
+                &mut ServerState::new(&mut ticket_store, &mut backend),
+                &request(method, path, body),
+            )
+        };
+
+        // act
+        let created = send(Method::Post, "/tickets", r#"{"title": "Fix login"}"#);
+        let patched = send(Method::Patch, "/tickets/1", r#"{"description": "Broken"}"#);
+        let moved = send(Method::Post, "/tickets/1/status", r#"{"status": "done"}"#);
+        let commented = send(
+            Method::Post,
+            "/tickets/1/comments",
+            r#"{"comment": "LGTM"}"#,
+        );
+
+        // assert
+        assert_eq!(created.status, 201);
+        assert_eq!(created.location, Some("/tickets/1".to_string()));
+        assert_eq!(field(&patched, "description"), "Broken");
+        assert_eq!(field(&moved, "status"), "Done");
+        assert_eq!(commented.status, 201);
+        assert_eq!(field(&commented, "comments"), serde_json::json!(["LGTM"]));
+        let saved = backend.load().unwrap();
+        assert_eq!(saved.get(1).unwrap().comments.len(), 1);
+    }
+
+    #[test]
+    fn store_errors_map_to_status_codes() {
+        // arrange
+        let mut ticket_store = TicketStore::new();
+        let mut backend = InMemoryBackend::new();
+        let mut send = |method, path: &str, body: &str| {
+            handle(
+                &mut ServerState::new(&mut ticket_store, &mut backend),
+                &request(method, path, body),
+            )
+            .status
+        };
+        send(Method::Post, "/tickets", r#"{"title": "Fix login"}"#);
+
+        // act & assert
+        assert_eq!(send(Method::Get, "/tickets/2", ""), 404);
+        assert_eq!(send(Method::Get, "/tickets/abc", ""), 404);
+        assert_eq!(send(Method::Post, "/tickets", r#"{"title": ""}"#), 400);
+        assert_eq!(send(Method::Post, "/tickets", "not json"), 400);
+        assert_eq!(send(Method::Put, "/tickets/1", "{}"), 405);
+        assert_eq!(send(Method::Get, "/nothing", ""), 404);
+        assert_eq!(send(Method::Delete, "/tickets/1", ""), 204);
+        assert_eq!(send(Method::Delete, "/tickets/1", ""), 404);
+    }
+
+    #[test]
+    fn a_wip_limit_is_a_conflict_unless_forced() {
+        // arrange
+        let mut ticket_store = TicketStore::new();
+        let mut backend = InMemoryBackend::new();
+        ticket_store
+            .set_wip_limit(Status::InProgress, Some(1))
+            .unwrap();
+        let mut send = |method, path: &str, body: &str| {
+            handle(
+                &mut ServerState::new(&mut ticket_store, &mut backend),
+                &request(method, path, body),
+            )
+            .status
+        };
+        send(Method::Post, "/tickets", r#"{"title": "Fix login"}"#);
+        send(Method::Post, "/tickets", r#"{"title": "Fix logout"}"#);
+        send(
+            Method::Post,
+            "/tickets/1/status",
+            r#"{"status": "in-progress"}"#,
+        );
+
+        // act
+        let refused = send(
+            Method::Post,
+            "/tickets/2/status",
+            r#"{"status": "in-progress"}"#,
+        );
+        let forced = send(
+            Method::Post,
+            "/tickets/2/status",
+            r#"{"status": "in-progress", "force": true}"#,
+        );
+
+        // assert
+        assert_eq!(refused, 409);
+        assert_eq!(forced, 200);
+    }
+
+    #[test]
+    fn tickets_are_listed_with_filters() {
+        // arrange
+        let mut ticket_store = TicketStore::new();
+        let mut backend = InMemoryBackend::new();
+        for title in &["Fix login", "Write docs", "Fix logout"] {
+            let body = format!(r#"{{"title": "{}"}}"#, title);
+            handle(
+                &mut ServerState::new(&mut ticket_store, &mut backend),
+                &request(Method::Post, "/tickets", &body),
+            );
+        }
+        let mut list = request(Method::Get, "/tickets", "");
+        list.query = vec![
+            ("status".to_string(), "todo".to_string()),
+            ("text".to_string(), "fix".to_string()),
+        ];
+
+        // act
+        let response = handle(
+            &mut ServerState::new(&mut ticket_store, &mut backend),
+            &list,
+        );
+
+        // assert
+        let titles: Vec<String> = response
+            .body
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["title"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(titles, vec!["Fix login", "Fix logout"]);
+    }
+}
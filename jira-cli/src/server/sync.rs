@@ -0,0 +1,299 @@
+use crate::error::JiraError;
+use crate::models::{Ticket, TicketId};
+use crate::server::{method_not_allowed, ApiRequest, ApiResponse, ServerState};
+use crate::store::{StoreSettings, TicketStore};
+use serde::{Deserialize, Serialize};
+use tiny_http::Method;
+
+/// The whole store, as downloaded by the [RemoteBackend](crate::persistence::RemoteBackend).
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct StoreSnapshot {
+    pub current_id: u64,
+    pub tickets: Vec<Ticket>,
+    pub settings: StoreSettings,
+}
+
+/// Body of `PUT /store/tickets/{id}`.
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct PutTicket {
+    /// The ticket as the client loaded it, `None` for a ticket it created.
+    pub expected: Option<Ticket>,
+    pub ticket: Ticket,
+}
+
+/// Body of `DELETE /store/tickets/{id}`.
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteTicket {
+    /// The ticket as the client loaded it.
+    pub expected: Ticket,
+}
+
+/// Body of `PUT /store/settings`.
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct PutSettings {
+    /// The settings as the client loaded them.
+    pub expected: StoreSettings,
+    pub settings: StoreSettings,
+}
+
+/// Handle the requests on `/store`, `segments` being the path after it. They let remote
+/// clients work on a copy of the store and upload what they changed:
+///
+/// - `GET /store`, the whole store;
+/// - `PUT` and `DELETE /store/tickets/{id}`, the former answering with the ticket as saved;
+/// - `PUT /store/settings`, which leaves the users of the server alone.
+///
+/// Changes carry the version of the ticket, or settings, the client started from. They are
+/// refused with `409 Conflict` if someone else changed it in the meantime.
+pub fn handle(
+    state: &mut ServerState,
+    request: &ApiRequest,
+    segments: &[&str],
+) -> Result<ApiResponse, JiraError> {
+    let ServerState {
+        ticket_store,
+        backend,
+        ..
+    } = state;
+    let ticket_store: &mut TicketStore = ticket_store;
+    match (&request.method, segments) {
+        (Method::Get, []) => {
+            let (current_id, mut tickets, _) = ticket_store.clone().into_parts();
+            tickets.sort_by_key(|t| t.id);
+            Ok(ApiResponse::ok(StoreSnapshot {
+                current_id,
+                tickets,
+                settings: shared_settings(ticket_store),
+            }))
+        }
+        (_, []) => method_not_allowed(request),
+        (Method::Put, ["tickets", id]) => {
+            let id = parse_id(id)?;
+            let body: PutTicket = request.json()?;
+            if body.ticket.id != id {
+                return Err(JiraError::Validation(format!(
+                    "The ticket has id {}, not {}",
+                    body.ticket.id, id
+                )));
+            }
+            check_unchanged(ticket_store.get(id), body.expected.as_ref(), id)?;
+            check_appended_only(ticket_store.get(id), &body.ticket)?;
+            ticket_store.put_ticket(body.ticket);
+            backend.save_ticket(ticket_store, id)?;
+            // The server may have recorded authors the client does not know about.
+            Ok(ApiResponse::ok(ticket_store.get(id)))
+        }
+        (Method::Delete, ["tickets", id]) => {
+            let id = parse_id(id)?;
+            let body: DeleteTicket = request.json()?;
+            check_unchanged(ticket_store.get(id), Some(&body.expected), id)?;
+            ticket_store.delete(id);
+            backend.delete_ticket(ticket_store, id)?;
+            Ok(ApiResponse::empty(204))
+        }
+        (_, ["tickets", _]) => method_not_allowed(request),
+        (Method::Put, ["settings"]) => {
+            let mut body: PutSettings = request.json()?;
+            if shared_settings(ticket_store) != body.expected {
+                return Err(JiraError::Conflict(
+                    "The views or configuration of the store were changed by someone else. Run the command again."
+                        .to_string(),
+                ));
+            }
+            body.settings.users = ticket_store.settings().users;
+            ticket_store.set_settings(body.settings);
+            backend.save_settings(ticket_store)?;
+            Ok(ApiResponse::empty(204))
+        }
+        (_, ["settings"]) => method_not_allowed(request),
+        _ => Err(JiraError::NotFound(format!(
+            "There is no endpoint at {}",
+            request.path
+        ))),
+    }
+}
+
+/// The settings remote clients work with: users and their tokens stay on the server.
+fn shared_settings(ticket_store: &TicketStore) -> StoreSettings {
+    StoreSettings {
+        users: Default::default(),
+        ..ticket_store.settings()
+    }
+}
+
+/// Refuse to change a ticket that is no longer the one the client started from.
+fn check_unchanged(
+    current: Option<&Ticket>,
+    expected: Option<&Ticket>,
+    id: TicketId,
+) -> Result<(), JiraError> {
+    if current == expected {
+        return Ok(());
+    }
+    Err(JiraError::Conflict(match current {
+        Some(_) => format!(
+            "Ticket {} was changed by someone else. Run the command again.",
+            id
+        ),
+        None => format!(
+            "Ticket {} was deleted by someone else. Run the command again.",
+            id
+        ),
+    }))
+}
+
+/// Refuse uploads rewriting the past of a ticket: comments and status changes can only be
+/// added. Their authors are left out, since the server records them.
+fn check_appended_only(current: Option<&Ticket>, uploaded: &Ticket) -> Result<(), JiraError> {
+    let current = match current {
+        Some(current) => current,
+        None => return Ok(()),
+    };
+    let comments_kept = uploaded.comments.len() >= current.comments.len()
+        && current
+            .comments
+            .iter()
+            .zip(&uploaded.comments)
+            .all(|(before, after)| before.to_string() == after.to_string());
+    let history_kept = uploaded.history.len() >= current.history.len()
+        && current
+            .history
+            .iter()
+            .zip(&uploaded.history)
+            .all(|(before, after)| {
+                (before.from, before.to, before.at) == (after.from, after.to, after.at)
+            });
+    if comments_kept && history_kept {
+        Ok(())
+    } else {
+        Err(JiraError::Validation(format!(
+            "The comments and status history of ticket {} can only be added to, not changed.",
+            current.id
+        )))
+    }
+}
+
+fn parse_id(id: &str) -> Result<TicketId, JiraError> {
+    id.parse()
+        .map_err(|_| JiraError::NotFound(format!("{:?} is not a ticket id", id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::{Comment, Status, Ticket, TicketDraft, Title};
+    use crate::persistence::InMemoryBackend;
+    use crate::server::{handle, ApiRequest, PutTicket, ServerState};
+    use crate::store::TicketStore;
+    use tiny_http::Method;
+
+    #[test]
+    fn tickets_changed_since_they_were_loaded_are_not_overwritten() {
+        // arrange
+        let mut ticket_store = TicketStore::new();
+        let mut backend = InMemoryBackend::new();
+        let id = ticket_store.create(TicketDraft {
+            title: Title::new("Fix login".to_string()).unwrap(),
+            description: String::new(),
+        });
+        let loaded = ticket_store.get(id).cloned();
+        let mut changed = loaded.clone().unwrap();
+        changed.status = Status::Done;
+        ticket_store.force_ticket_status(id, Status::InProgress);
+        ticket_store.take_events();
+        let body = PutTicket {
+            expected: loaded,
+            ticket: changed,
+        };
+        let request = ApiRequest {
+            method: Method::Put,
+            path: format!("/store/tickets/{}", id),
+            query: Vec::new(),
+            authorization: None,
+            body: serde_json::to_vec(&body).unwrap(),
+        };
+
+        // act
+        let refused = handle(
+            &mut ServerState::new(&mut ticket_store, &mut backend),
+            &request,
+        );
+        let mut rebased = body.clone();
+        rebased.expected = ticket_store.get(id).cloned();
+        rebased.ticket = ticket_store.get(id).cloned().unwrap();
+        rebased.ticket.status = Status::Done;
+        let accepted = handle(
+            &mut ServerState::new(&mut ticket_store, &mut backend),
+            &ApiRequest {
+                body: serde_json::to_vec(&rebased).unwrap(),
+                ..request
+            },
+        );
+
+        // assert
+        assert_eq!(refused.status, 409);
+        assert_eq!(accepted.status, 200);
+        assert_eq!(ticket_store.get(id).unwrap().status, Status::Done);
+        let events: Vec<&str> = ticket_store
+            .take_events()
+            .iter()
+            .map(|e| e.name())
+            .collect();
+        assert_eq!(events, vec!["ticket.moved"]);
+    }
+
+    #[test]
+    fn comments_can_only_be_added_and_are_credited_to_the_authenticated_user() {
+        // arrange
+        let mut ticket_store = TicketStore::new();
+        let mut backend = InMemoryBackend::new();
+        let id = ticket_store.create(TicketDraft {
+            title: Title::new("Fix login".to_string()).unwrap(),
+            description: String::new(),
+        });
+        ticket_store.set_author(Some("alice".to_string()));
+        ticket_store.add_comment_to_ticket(id, Comment::new("On it".to_string()).unwrap());
+        ticket_store.set_author(None);
+        let (_, bob) = ticket_store.create_token("bob", None).unwrap();
+        let loaded = ticket_store.get(id).cloned().unwrap();
+        let mut rewritten = loaded.clone();
+        rewritten.comments[0] = Comment::new("Not me".to_string())
+            .unwrap()
+            .with_author("alice");
+        let mut appended = loaded.clone();
+        appended.comments[0] = Comment::new("On it".to_string()).unwrap();
+        appended.comments.push(
+            Comment::new("Done".to_string())
+                .unwrap()
+                .with_author("alice"),
+        );
+        let put = |ticket: &Ticket| ApiRequest {
+            method: Method::Put,
+            path: format!("/store/tickets/{}", id),
+            query: Vec::new(),
+            authorization: Some(format!("Bearer {}", bob)),
+            body: serde_json::to_vec(&PutTicket {
+                expected: Some(loaded.clone()),
+                ticket: ticket.clone(),
+            })
+            .unwrap(),
+        };
+
+        // act
+        let refused = handle(
+            &mut ServerState::new(&mut ticket_store, &mut backend),
+            &put(&rewritten),
+        );
+        let accepted = handle(
+            &mut ServerState::new(&mut ticket_store, &mut backend),
+            &put(&appended),
+        );
+
+        // assert
+        assert_eq!(refused.status, 400);
+        assert_eq!(accepted.status, 200);
+        let comments = &ticket_store.get(id).unwrap().comments;
+        assert_eq!(comments[0].author(), Some("alice"));
+        assert_eq!(comments[1].to_string(), "Done");
+        assert_eq!(comments[1].author(), Some("bob"));
+    }
+}
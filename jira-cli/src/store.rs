@@ -1,22 +1,141 @@
-use crate::models::{Comment, DeletedTicket, Status, Ticket, TicketDraft, TicketId, TicketPatch};
+use crate::models::{
+    ApiToken, Comment, DeletedTicket, LinkedCommit, Status, StatusChange, StoreConfig, Ticket,
+    TicketDraft, TicketEvent, TicketId, TicketPatch, User, View, WipLimitError, WipLimits,
+};
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
 
 /// In-memory database where we store the saved [`Ticket`]s.
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct TicketStore {
     /// Current state of the internal sequence, used for id generation in generate_id.
     current_id: u64,
     /// The collection of stored tickets.
     data: HashMap<TicketId, Ticket>,
+    /// Saved [View]s, indexed by name.
+    #[serde(default)]
+    views: BTreeMap<String, View>,
+    #[serde(default)]
+    config: StoreConfig,
+    /// [User]s of the API served by `jira-cli serve`, indexed by name.
+    #[serde(default)]
+    users: BTreeMap<String, User>,
+    /// What happened to the tickets since the events were last taken, oldest first.
+    #[serde(skip)]
+    events: Vec<TicketEvent>,
+    /// Who the comments and status changes made from now on are recorded as written by.
+    #[serde(skip)]
+    author: Option<String>,
 }
 
+/// Everything a [TicketStore] holds apart from its tickets.
+///
+/// Used by storage backends that persist tickets one by one.
+#[derive(PartialEq, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StoreSettings {
+    #[serde(default)]
+    pub views: BTreeMap<String, View>,
+    #[serde(default)]
+    pub config: StoreConfig,
+    #[serde(default)]
+    pub users: BTreeMap<String, User>,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+/// What [import](TicketStore::import) did with a ticket.
+pub enum ImportOutcome {
+    Created,
+    /// The ticket was imported before and has been updated.
+    Updated,
+    /// The ticket was imported before and had nothing new.
+    Unchanged,
+}
+
+#[derive(PartialEq, Debug)]
+/// Error if the status of a ticket cannot be updated
+pub enum StatusUpdateError {
+    /// There is no ticket with the given identifier.
+    TicketNotFound(TicketId),
+    /// The move would put more tickets in a status than its work-in-progress limit allows.
+    WipLimitExceeded { status: Status, limit: usize },
+}
+
+impl fmt::Display for StatusUpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StatusUpdateError::TicketNotFound(id) => write!(
+                f,
+                "There was no ticket associated to the ticket id {:?}",
+                id
+            ),
+            StatusUpdateError::WipLimitExceeded { status, limit } => write!(
+                f,
+                "{:?} already holds its maximum of {} ticket(s). Use --force to move the ticket anyway.",
+                status, limit
+            ),
+        }
+    }
+}
+
+impl Error for StatusUpdateError {}
+
 impl TicketStore {
     /// Create a new empty [`TicketStore`] instance.
     pub fn new() -> Self {
         Self {
             current_id: 0,
             data: HashMap::new(),
+            views: BTreeMap::new(),
+            config: StoreConfig::default(),
+            users: BTreeMap::new(),
+            events: Vec::new(),
+            author: None,
+        }
+    }
+
+    /// Rebuild a store from its parts, as persisted by a storage backend.
+    pub(crate) fn from_parts(
+        current_id: u64,
+        tickets: Vec<Ticket>,
+        settings: StoreSettings,
+    ) -> Self {
+        let mut data: HashMap<TicketId, Ticket> = tickets.into_iter().map(|t| (t.id, t)).collect();
+        // Backends saving tickets one by one can keep relations to a ticket deleted since.
+        let ids: Vec<TicketId> = data.keys().copied().collect();
+        for ticket in data.values_mut() {
+            ticket.related.retain(|id| ids.contains(id));
+        }
+        Self {
+            current_id,
+            data,
+            views: settings.views,
+            config: settings.config,
+            users: settings.users,
+            events: Vec::new(),
+            author: None,
+        }
+    }
+
+    /// Split the store into its parts, the reverse of [from_parts](TicketStore::from_parts).
+    pub(crate) fn into_parts(self) -> (u64, Vec<Ticket>, StoreSettings) {
+        let settings = self.settings();
+        (self.current_id, self.data.into_values().collect(), settings)
+    }
+
+    /// Id of the last ticket created in the store.
+    pub(crate) fn current_id(&self) -> u64 {
+        self.current_id
+    }
+
+    /// A copy of the [StoreSettings] of the store.
+    pub(crate) fn settings(&self) -> StoreSettings {
+        StoreSettings {
+            views: self.views.clone(),
+            config: self.config.clone(),
+            users: self.users.clone(),
         }
     }
 
@@ -31,21 +150,92 @@ impl TicketStore {
             title: draft.title,
             status: Status::ToDo,
             comments: Vec::new(),
+            created_at: Some(Utc::now()),
+            history: Vec::new(),
+            external_key: None,
+            commits: Vec::new(),
+            related: Vec::new(),
         };
+        self.events.push(TicketEvent::Created {
+            ticket_id: id,
+            at: ticket.created_at.unwrap_or_else(Utc::now),
+        });
         self.data.insert(ticket.id, ticket);
         id
     }
 
+    /// Persist a ticket brought in from another tool, keeping its status and comments.
+    /// No status change is recorded in its history: the ticket did not move in IronJira.
+    ///
+    /// If a ticket was imported before with the same `external_key`, it is not duplicated:
+    /// only the comments it does not have yet are added to it, unless `overwrite` is set, in
+    /// which case its title, description, status and comments are replaced.
+    /// Returns the id of the ticket and what happened to it.
+    pub fn import(
+        &mut self,
+        draft: TicketDraft,
+        status: Status,
+        comments: Vec<Comment>,
+        external_key: Option<String>,
+        overwrite: bool,
+    ) -> (TicketId, ImportOutcome) {
+        let existing = external_key
+            .as_deref()
+            .and_then(|key| self.find_by_external_key(key));
+        let (id, outcome) = match existing {
+            Some(id) => (id, ImportOutcome::Updated),
+            None => (self.create(draft.clone()), ImportOutcome::Created),
+        };
+        let ticket = match self.data.get_mut(&id) {
+            Some(ticket) => ticket,
+            None => return (id, outcome),
+        };
+        if outcome == ImportOutcome::Created || overwrite {
+            ticket.title = draft.title;
+            ticket.description = draft.description;
+            ticket.status = status;
+            ticket.comments = comments;
+            ticket.external_key = external_key;
+            return (id, outcome);
+        }
+        let known = ticket.comments.len();
+        for comment in comments {
+            if !ticket.comments.contains(&comment) {
+                ticket.comments.push(comment);
+            }
+        }
+        if ticket.comments.len() == known {
+            (id, ImportOutcome::Unchanged)
+        } else {
+            (id, outcome)
+        }
+    }
+
+    /// Retrieve the id of the ticket imported with the given external key, if any.
+    pub fn find_by_external_key(&self, key: &str) -> Option<TicketId> {
+        self.data
+            .values()
+            .find(|t| t.external_key.as_deref() == Some(key))
+            .map(|t| t.id)
+    }
+
     /// Remove a [Ticket] from the store.
     /// Returns None if the [Ticket](Ticket) is not there or [DeletedTicket](DeletedTicket) if there was one.
+    /// The ticket is no longer related to any other ticket.
     pub fn delete(&mut self, ticket_id: TicketId) -> Option<DeletedTicket> {
-        self.data.remove(&ticket_id).map(DeletedTicket)
+        let deleted = self.data.remove(&ticket_id)?;
+        for id in &deleted.related {
+            if let Some(ticket) = self.data.get_mut(id) {
+                ticket.related.retain(|related| *related != ticket_id);
+            }
+        }
+        Some(DeletedTicket(deleted))
     }
 
     /// Returns list off all inserted [Ticket](Ticket)
     /// Returns an empty list of tickets is there are no tickets in the store
     pub fn list(&self) -> Vec<&Ticket> {
-        self.data.iter().map(|(_, ticket)| ticket).collect()
+        self.data.values().collect()
     }
 
     /// Generate a unique id by incrementing monotonically a private counter.
@@ -71,20 +261,297 @@ impl TicketStore {
         })
     }
 
-    // Update a [Ticket] [Status] given an identifier and new [Status]. Returns `None` if there is no ticket with such an identifier.
-    pub fn update_ticket_status(&mut self, id: TicketId, status: Status) -> Option<()> {
-        self.data.get_mut(&id).map(|t| t.status = status)
+    /// Update a [Ticket] [Status] given an identifier and new [Status].
+    /// Refuses moves that would exceed the [work-in-progress limit](WipLimits) of the new [Status].
+    pub fn update_ticket_status(
+        &mut self,
+        id: TicketId,
+        status: Status,
+    ) -> Result<(), StatusUpdateError> {
+        let ticket = self
+            .data
+            .get(&id)
+            .ok_or(StatusUpdateError::TicketNotFound(id))?;
+        if ticket.status != status {
+            if let Some(limit) = self.config.wip_limits.get(status) {
+                if self.count_in_status(status) >= limit {
+                    return Err(StatusUpdateError::WipLimitExceeded { status, limit });
+                }
+            }
+        }
+        self.force_ticket_status(id, status)
+            .ok_or(StatusUpdateError::TicketNotFound(id))
+    }
+
+    /// Update a [Ticket] [Status] given an identifier and new [Status], ignoring work-in-progress limits.
+    /// The change is recorded in the ticket history.
+    /// Returns `None` if there is no ticket with such an identifier.
+    pub fn force_ticket_status(&mut self, id: TicketId, status: Status) -> Option<()> {
+        let events = &mut self.events;
+        let author = &self.author;
+        self.data.get_mut(&id).map(|t| {
+            if t.status != status {
+                let change = StatusChange {
+                    from: t.status,
+                    to: status,
+                    at: Utc::now(),
+                    author: author.clone(),
+                };
+                events.push(TicketEvent::Moved {
+                    ticket_id: id,
+                    from: change.from,
+                    to: change.to,
+                    at: change.at,
+                });
+                t.history.push(change);
+                t.status = status;
+            }
+        })
+    }
+
+    /// Number of tickets currently in the given [Status].
+    pub fn count_in_status(&self, status: Status) -> usize {
+        self.data.values().filter(|t| t.status == status).count()
     }
 
+    /// Add a comment to a [Ticket], written by the current author if there is one.
     pub fn add_comment_to_ticket(&mut self, id: TicketId, comment: Comment) -> Option<()> {
-        self.data.get_mut(&id).map(|t| t.comments.push(comment))
+        let events = &mut self.events;
+        let comment = match &self.author {
+            Some(author) => comment.with_author(author),
+            None => comment,
+        };
+        self.data.get_mut(&id).map(|t| {
+            events.push(TicketEvent::Commented {
+                ticket_id: id,
+                comment: comment.to_string(),
+                at: Utc::now(),
+            });
+            t.comments.push(comment)
+        })
+    }
+
+    /// Insert or replace a whole [Ticket], as sent by a remote client which changed it on
+    /// its own copy of the store. Events are recorded for what changed.
+    ///
+    /// Comments and status changes can only be added: the ones already in the store are kept
+    /// as they are, and the new ones are recorded as written by the current author, whatever
+    /// author the client sent.
+    pub(crate) fn put_ticket(&mut self, mut ticket: Ticket) {
+        let now = Utc::now();
+        let id = ticket.id;
+        let previous = self.data.get(&id);
+        match previous {
+            Some(previous) => {
+                if previous.status != ticket.status {
+                    self.events.push(TicketEvent::Moved {
+                        ticket_id: id,
+                        from: previous.status,
+                        to: ticket.status,
+                        at: now,
+                    });
+                }
+                for comment in ticket.comments.iter().skip(previous.comments.len()) {
+                    self.events.push(TicketEvent::Commented {
+                        ticket_id: id,
+                        comment: comment.to_string(),
+                        at: now,
+                    });
+                }
+            }
+            None => self.events.push(TicketEvent::Created {
+                ticket_id: id,
+                at: ticket.created_at.unwrap_or(now),
+            }),
+        }
+        let (comments, history) = previous.map_or((Vec::new(), Vec::new()), |p| {
+            (p.comments.clone(), p.history.clone())
+        });
+        for comment in ticket.comments.iter_mut().skip(comments.len()) {
+            comment.set_author(self.author.clone());
+        }
+        for change in ticket.history.iter_mut().skip(history.len()) {
+            change.author = self.author.clone();
+        }
+        ticket
+            .comments
+            .splice(..comments.len().min(ticket.comments.len()), comments);
+        ticket
+            .history
+            .splice(..history.len().min(ticket.history.len()), history);
+        self.current_id = self.current_id.max(id);
+        self.data.insert(id, ticket);
+    }
+
+    /// Replace the views and configuration of the store.
+    pub(crate) fn set_settings(&mut self, settings: StoreSettings) {
+        self.views = settings.views;
+        self.config = settings.config;
+        self.users = settings.users;
+    }
+
+    /// Take the [TicketEvent]s recorded since the last call, oldest first.
+    pub fn take_events(&mut self) -> Vec<TicketEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Record the comments and status changes made from now on as written by `author`,
+    /// e.g. the [User] authenticated by the server, or by nobody in particular if `None`.
+    pub fn set_author(&mut self, author: Option<String>) {
+        self.author = author;
+    }
+
+    /// Create an [ApiToken] for a [User], creating the user if needed. The token expires after
+    /// `lifetime`, or never if `None`.
+    /// Returns the token along with the secret to hand over to the user: it is not stored.
+    /// Returns `None` if the token would expire too far in the future to be represented.
+    pub fn create_token(
+        &mut self,
+        user: &str,
+        lifetime: Option<Duration>,
+    ) -> Option<(ApiToken, String)> {
+        let (token, secret) = loop {
+            let (token, secret) = ApiToken::generate(Utc::now(), lifetime)?;
+            // Ids are short enough to type, and name a token whoever owns it:
+            // make sure they stay unique across users.
+            let taken = self
+                .users
+                .values()
+                .flat_map(|user| &user.tokens)
+                .any(|t| t.id == token.id);
+            if !taken {
+                break (token, secret);
+            }
+        };
+        self.users
+            .entry(user.to_string())
+            .or_default()
+            .tokens
+            .push(token.clone());
+        Some((token, secret))
+    }
+
+    /// Revoke the [ApiToken] with the given id.
+    /// Returns `None` if there is no such token, or the name of the [User] it belonged to.
+    pub fn revoke_token(&mut self, id: &str) -> Option<String> {
+        self.users.iter_mut().find_map(|(name, user)| {
+            let before = user.tokens.len();
+            user.tokens.retain(|t| t.id != id);
+            (user.tokens.len() != before).then(|| name.clone())
+        })
+    }
+
+    /// Returns all [User]s with their names, in alphabetical order.
+    pub fn list_users(&self) -> Vec<(&String, &User)> {
+        self.users.iter().collect()
+    }
+
+    /// The name of the [User] owning `secret`, unless it is not a valid token or it expired
+    /// at `now`.
+    pub fn authenticate(&self, secret: &str, now: DateTime<Utc>) -> Option<&str> {
+        let id = ApiToken::id_of(secret)?;
+        self.users
+            .iter()
+            .find(|(_, user)| {
+                user.tokens
+                    .iter()
+                    .any(|t| t.id == id && t.accepts(secret, now))
+            })
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Record a commit referencing a [Ticket], unless it is already linked to it.
+    /// Returns `None` if there is no ticket with such an identifier, or whether the commit was added.
+    pub fn link_commit(&mut self, id: TicketId, commit: LinkedCommit) -> Option<bool> {
+        self.data.get_mut(&id).map(|t| {
+            if t.commits.iter().any(|c| c.hash == commit.hash) {
+                false
+            } else {
+                t.commits.push(commit);
+                true
+            }
+        })
+    }
+
+    /// Relate two [Ticket]s to each other. A ticket cannot be related to itself.
+    /// Returns `None` if one of the tickets does not exist, or whether the relation was added.
+    pub fn relate_tickets(&mut self, id: TicketId, other: TicketId) -> Option<bool> {
+        if !self.data.contains_key(&id) || !self.data.contains_key(&other) {
+            return None;
+        }
+        let related = self.data.get(&id).map(|t| t.related.contains(&other));
+        if id == other || related == Some(true) {
+            return Some(false);
+        }
+        for (from, to) in [(id, other), (other, id)].iter() {
+            if let Some(ticket) = self.data.get_mut(from) {
+                ticket.related.push(*to);
+            }
+        }
+        Some(true)
+    }
+
+    /// Remove the relation between two [Ticket]s.
+    /// Returns `None` if one of the tickets does not exist, or whether they were related.
+    pub fn unrelate_tickets(&mut self, id: TicketId, other: TicketId) -> Option<bool> {
+        if !self.data.contains_key(&id) || !self.data.contains_key(&other) {
+            return None;
+        }
+        let mut removed = false;
+        for (from, to) in [(id, other), (other, id)].iter() {
+            if let Some(ticket) = self.data.get_mut(from) {
+                let before = ticket.related.len();
+                ticket.related.retain(|related| related != to);
+                removed |= ticket.related.len() != before;
+            }
+        }
+        Some(removed)
+    }
+
+    /// Save a [View] under the given name.
+    /// Returns the [View] previously saved under the same name, if there was one.
+    pub fn save_view(&mut self, name: String, view: View) -> Option<View> {
+        self.views.insert(name, view)
+    }
+
+    /// Retrieve a saved [View] by name. Returns `None` if there is no view with such a name.
+    pub fn get_view(&self, name: &str) -> Option<&View> {
+        self.views.get(name)
+    }
+
+    /// Returns all saved [View]s with their names, in alphabetical order.
+    pub fn list_views(&self) -> Vec<(&String, &View)> {
+        self.views.iter().collect()
+    }
+
+    /// Remove a saved [View]. Returns `None` if there is no view with such a name.
+    pub fn delete_view(&mut self, name: &str) -> Option<View> {
+        self.views.remove(name)
+    }
+
+    /// The work-in-progress limits configured for this store.
+    pub fn wip_limits(&self) -> &WipLimits {
+        &self.config.wip_limits
+    }
+
+    /// Set the work-in-progress limit of a [Status], or remove it by passing `None`.
+    pub fn set_wip_limit(
+        &mut self,
+        status: Status,
+        limit: Option<usize>,
+    ) -> Result<(), WipLimitError> {
+        self.config.wip_limits.set(status, limit)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::models::{Comment, Status, Ticket, TicketDraft, TicketPatch, Title};
-    use crate::store::TicketStore;
+    use crate::models::{
+        Column, Comment, SortKey, Status, Ticket, TicketDraft, TicketFilter, TicketPatch, Title,
+        View,
+    };
+    use crate::store::{StatusUpdateError, TicketStore};
+    use chrono::{Duration, Utc};
     use fake::{Fake, Faker};
     use std::collections::HashSet;
 
@@ -265,14 +732,19 @@ mod tests {
         let ticket = generate_and_persist_ticket(&mut ticket_store);
 
         //act
-        ticket_store.update_ticket_status(ticket.id, Status::Done);
+        ticket_store
+            .update_ticket_status(ticket.id, Status::Done)
+            .expect("Failed to update status.");
 
         //assert
         let updated_ticket = ticket_store
             .get(ticket.id)
             .expect("Failed to retrieve ticket.");
 
-        assert_eq!(updated_ticket.status, Status::Done)
+        assert_eq!(updated_ticket.status, Status::Done);
+        assert_eq!(updated_ticket.history.len(), 1);
+        assert_eq!(updated_ticket.history[0].from, Status::ToDo);
+        assert_eq!(updated_ticket.history[0].to, Status::Done);
     }
 
     #[test]
@@ -305,4 +777,167 @@ mod tests {
         //assert
         assert!(result.is_none());
     }
+
+    #[test]
+    fn saved_views_can_be_retrieved_and_deleted() {
+        //arrange
+        let mut ticket_store = TicketStore::new();
+        let view = View {
+            filter: TicketFilter {
+                statuses: vec![Status::Blocked],
+                text: None,
+            },
+            sort: SortKey::Id,
+            descending: false,
+            columns: Column::defaults(),
+        };
+
+        //act
+        let previous = ticket_store.save_view("blocked".to_string(), view.clone());
+
+        //assert
+        assert!(previous.is_none());
+        assert_eq!(ticket_store.get_view("blocked"), Some(&view));
+        assert_eq!(ticket_store.list_views().len(), 1);
+        assert_eq!(ticket_store.delete_view("blocked"), Some(view));
+        assert!(ticket_store.get_view("blocked").is_none());
+    }
+
+    #[test]
+    fn moving_a_ticket_beyond_the_wip_limit_is_refused_unless_forced() {
+        //arrange
+        let mut ticket_store = TicketStore::new();
+        let first = generate_and_persist_ticket(&mut ticket_store);
+        let second = generate_and_persist_ticket(&mut ticket_store);
+        ticket_store
+            .set_wip_limit(Status::InProgress, Some(1))
+            .unwrap();
+        ticket_store
+            .update_ticket_status(first.id, Status::InProgress)
+            .unwrap();
+
+        //act
+        let result = ticket_store.update_ticket_status(second.id, Status::InProgress);
+
+        //assert
+        assert_eq!(
+            result,
+            Err(StatusUpdateError::WipLimitExceeded {
+                status: Status::InProgress,
+                limit: 1
+            })
+        );
+        assert!(ticket_store
+            .force_ticket_status(second.id, Status::InProgress)
+            .is_some());
+        assert_eq!(ticket_store.count_in_status(Status::InProgress), 2);
+    }
+
+    #[test]
+    fn updating_the_status_of_a_missing_ticket_fails() {
+        //arrange
+        let mut ticket_store = TicketStore::new();
+
+        //act
+        let result = ticket_store.update_ticket_status(1, Status::Done);
+
+        //assert
+        assert_eq!(result, Err(StatusUpdateError::TicketNotFound(1)));
+    }
+
+    #[test]
+    fn relations_go_both_ways_and_disappear_with_deleted_tickets() {
+        //arrange
+        let mut ticket_store = TicketStore::new();
+        let first = generate_and_persist_ticket(&mut ticket_store);
+        let second = generate_and_persist_ticket(&mut ticket_store);
+        let third = generate_and_persist_ticket(&mut ticket_store);
+
+        //act
+        let related = ticket_store.relate_tickets(first.id, second.id);
+        let related_again = ticket_store.relate_tickets(second.id, first.id);
+        ticket_store.relate_tickets(first.id, third.id);
+        ticket_store.delete(third.id);
+
+        //assert
+        assert_eq!(related, Some(true));
+        assert_eq!(related_again, Some(false));
+        assert_eq!(ticket_store.relate_tickets(first.id, 42), None);
+        assert_eq!(ticket_store.get(first.id).unwrap().related, vec![second.id]);
+        assert_eq!(ticket_store.get(second.id).unwrap().related, vec![first.id]);
+        assert_eq!(
+            ticket_store.unrelate_tickets(second.id, first.id),
+            Some(true)
+        );
+        assert!(ticket_store.get(first.id).unwrap().related.is_empty());
+    }
+
+    #[test]
+    fn changes_are_recorded_as_events() {
+        //arrange
+        let mut ticket_store = TicketStore::new();
+        let ticket = generate_and_persist_ticket(&mut ticket_store);
+        ticket_store.take_events();
+
+        //act
+        ticket_store.force_ticket_status(ticket.id, Status::Done);
+        ticket_store.force_ticket_status(ticket.id, Status::Done);
+        ticket_store.add_comment_to_ticket(ticket.id, Comment::new("LGTM".to_string()).unwrap());
+
+        //assert
+        let events: Vec<&str> = ticket_store
+            .take_events()
+            .iter()
+            .map(|e| e.name())
+            .collect();
+        assert_eq!(events, vec!["ticket.moved", "ticket.commented"]);
+        assert!(ticket_store.take_events().is_empty());
+    }
+
+    #[test]
+    fn tokens_authenticate_their_user_until_revoked_or_expired() {
+        //arrange
+        let mut ticket_store = TicketStore::new();
+        let (token, secret) = ticket_store
+            .create_token("alice", Some(Duration::days(1)))
+            .unwrap();
+        let (_, forever) = ticket_store.create_token("alice", None).unwrap();
+        let now = Utc::now();
+
+        //act
+        let authenticated = ticket_store.authenticate(&secret, now).map(str::to_string);
+        let expired = ticket_store
+            .authenticate(&secret, now + Duration::days(2))
+            .is_none();
+        let revoked_from = ticket_store.revoke_token(&token.id);
+
+        //assert
+        assert_eq!(authenticated, Some("alice".to_string()));
+        assert!(expired);
+        assert_eq!(revoked_from, Some("alice".to_string()));
+        assert_eq!(ticket_store.authenticate(&secret, now), None);
+        assert_eq!(ticket_store.authenticate(&forever, now), Some("alice"));
+        assert_eq!(ticket_store.authenticate("ij_0000_guess", now), None);
+        assert_eq!(ticket_store.revoke_token(&token.id), None);
+    }
+
+    #[test]
+    fn comments_and_status_changes_are_recorded_with_their_author() {
+        //arrange
+        let mut ticket_store = TicketStore::new();
+        let ticket = generate_and_persist_ticket(&mut ticket_store);
+        ticket_store.set_author(Some("alice".to_string()));
+
+        //act
+        ticket_store.force_ticket_status(ticket.id, Status::Done);
+        ticket_store.add_comment_to_ticket(ticket.id, Comment::new("LGTM".to_string()).unwrap());
+        ticket_store.set_author(None);
+        ticket_store.add_comment_to_ticket(ticket.id, Comment::new("Thanks".to_string()).unwrap());
+
+        //assert
+        let ticket = ticket_store.get(ticket.id).unwrap();
+        assert_eq!(ticket.history[0].author.as_deref(), Some("alice"));
+        assert_eq!(ticket.comments[0].author(), Some("alice"));
+        assert_eq!(ticket.comments[1].author(), None);
+    }
 }
@@ -0,0 +1,246 @@
+use crate::error::JiraError;
+use crate::models::{Comment, Status, Ticket, TicketDraft, Title};
+use crate::transfer::{ImportedTicket, RowError};
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+const HEADER: [&str; 7] = [
+    "id",
+    "title",
+    "description",
+    "status",
+    "comments",
+    "created_at",
+    "external_key",
+];
+
+/// Write every ticket as a row of a CSV file, with a header row.
+///
+/// Comments are written in a single cell, as a JSON array of comments with their authors,
+/// e.g. `[{"comment":"Done?","author":"alice"}]`. The cell is empty for tickets without comments.
+pub fn export_csv(tickets: &[&Ticket], output: impl Write) -> Result<(), JiraError> {
+    let mut writer = csv::Writer::from_writer(output);
+    let write_error = |e: csv::Error| JiraError::io("Failed to write the CSV file", e);
+    writer.write_record(HEADER).map_err(write_error)?;
+    for ticket in tickets {
+        let comments = match ticket.comments.as_slice() {
+            [] => String::new(),
+            // Comments always serialise to JSON.
+            comments => serde_json::to_string(comments).unwrap_or_default(),
+        };
+        writer
+            .write_record(&[
+                ticket.id.to_string(),
+                ticket.title.to_string(),
+                ticket.description.clone(),
+                format!("{:?}", ticket.status),
+                comments,
+                ticket
+                    .created_at
+                    .map(|at| at.to_rfc3339())
+                    .unwrap_or_default(),
+                ticket.external_key.clone().unwrap_or_default(),
+            ])
+            .map_err(write_error)?;
+    }
+    writer
+        .flush()
+        .map_err(|e| JiraError::io("Failed to write the CSV file", e))
+}
+
+/// Read tickets from a CSV file with a header row.
+///
+/// Columns are matched to ticket fields by their name in the header, ignoring case:
+/// `title` is required, `description`, `status`, `comments` and `external_key` are optional.
+/// Comments are read as [exported](export_csv), or as plain text with one comment per line. Any other column, `id` included, is ignored: imported tickets
+/// get a new id, unless their external key matches a ticket imported before.
+///
+/// Every row is validated on its own: the result holds either the ticket or why the row is invalid.
+pub fn read_csv(input: impl Read) -> Result<Vec<Result<ImportedTicket, RowError>>, JiraError> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(input);
+    let header = reader
+        .headers()
+        .map_err(|e| JiraError::parse("Failed to read the CSV header", e))?
+        .clone();
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|field| field.trim().eq_ignore_ascii_case(name))
+    };
+    let title = column("title")
+        .ok_or_else(|| JiraError::Validation("The CSV file has no title column.".to_string()))?;
+    let description = column("description");
+    let status = column("status");
+    let comments = column("comments");
+    let external_key = column("external_key");
+
+    let rows = reader
+        .records()
+        .map(|record| {
+            let record = record.map_err(|e| RowError {
+                location: line(e.position()),
+                details: e.to_string(),
+            })?;
+            let location = line(record.position());
+            let cell = |index: Option<usize>| index.and_then(|i| record.get(i)).unwrap_or("");
+            let row_error = |details: String| RowError {
+                location: location.clone(),
+                details,
+            };
+
+            let title =
+                Title::new(cell(Some(title)).to_string()).map_err(|e| row_error(e.to_string()))?;
+            let status = match cell(status).trim() {
+                "" => Status::ToDo,
+                status => Status::from_str(status).map_err(|e| row_error(e.to_string()))?,
+            };
+            let comments = read_comments(cell(comments)).map_err(row_error)?;
+            Ok(ImportedTicket {
+                draft: TicketDraft {
+                    title,
+                    description: cell(description).to_string(),
+                },
+                status,
+                comments,
+                external_key: Some(cell(external_key).trim())
+                    .filter(|key| !key.is_empty())
+                    .map(str::to_string),
+            })
+        })
+        .collect();
+    Ok(rows)
+}
+
+/// The comments of a cell: a JSON array as written by [export_csv], or plain text with one
+/// comment per line, e.g. for a cell of a spreadsheet starting with `[WIP]`.
+fn read_comments(cell: &str) -> Result<Vec<Comment>, String> {
+    if cell.trim_start().starts_with('[') {
+        if let Ok(comments) = serde_json::from_str::<Vec<Comment>>(cell) {
+            // Deserialising skips the checks of `Comment::new`.
+            for comment in &comments {
+                Comment::new(comment.to_string()).map_err(|e| e.to_string())?;
+            }
+            return Ok(comments);
+        }
+    }
+    cell.lines()
+        .filter(|comment| !comment.trim().is_empty())
+        .map(|comment| Comment::new(comment.to_string()))
+        .collect::<Result<Vec<Comment>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+fn line(position: Option<&csv::Position>) -> String {
+    format!("Line {}", position.map(|p| p.line()).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::{Comment, Status, TicketDraft, Title};
+    use crate::store::TicketStore;
+    use crate::transfer::{export_csv, import, read_csv, sorted_tickets};
+
+    #[test]
+    fn exported_tickets_can_be_imported_back() {
+        // arrange
+        let mut ticket_store = TicketStore::new();
+        let id = ticket_store.create(TicketDraft {
+            title: Title::new("Fix login, again".to_string()).unwrap(),
+            description: "It is \"broken\"".to_string(),
+        });
+        ticket_store.force_ticket_status(id, Status::Blocked);
+        for comment in &["First", "Second,\non two lines"] {
+            let comment = Comment::new(comment.to_string()).unwrap();
+            ticket_store.add_comment_to_ticket(id, comment.with_author("alice"));
+        }
+        let mut csv = Vec::new();
+        export_csv(&sorted_tickets(&ticket_store), &mut csv).unwrap();
+
+        // act
+        let rows = read_csv(csv.as_slice()).unwrap();
+
+        // assert
+        let imported = rows[0].clone().unwrap();
+        let ticket = ticket_store.get(id).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(imported.draft.title, ticket.title);
+        assert_eq!(imported.draft.description, ticket.description);
+        assert_eq!(imported.status, Status::Blocked);
+        assert_eq!(imported.comments, ticket.comments);
+    }
+
+    #[test]
+    fn invalid_rows_are_reported_and_the_others_imported() {
+        // arrange
+        let csv = "Title,Status,Owner\nFirst,done,me\n,todo,me\nThird,someday,me\nFourth,,me\n";
+        let mut ticket_store = TicketStore::new();
+
+        // act
+        let report = import(
+            &mut ticket_store,
+            read_csv(csv.as_bytes()).unwrap(),
+            false,
+            false,
+        );
+
+        // assert
+        assert_eq!(report.created, 2);
+        let locations: Vec<&str> = report.errors.iter().map(|e| e.location.as_str()).collect();
+        assert_eq!(locations, vec!["Line 3", "Line 4"]);
+        assert_eq!(ticket_store.list().len(), 2);
+    }
+
+    #[test]
+    fn a_dry_run_leaves_the_store_untouched() {
+        let mut ticket_store = TicketStore::new();
+
+        let report = import(
+            &mut ticket_store,
+            read_csv("title\nFirst\n".as_bytes()).unwrap(),
+            true,
+            false,
+        );
+
+        assert_eq!(report.created, 1);
+        assert!(ticket_store.list().is_empty());
+    }
+
+    #[test]
+    fn plain_text_comments_are_read_one_per_line() {
+        let csv = "title,comments\nFirst,\"One\nTwo\"\n";
+
+        let rows = read_csv(csv.as_bytes()).unwrap();
+
+        let comments: Vec<String> = rows[0]
+            .clone()
+            .unwrap()
+            .comments
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        assert_eq!(comments, vec!["One", "Two"]);
+    }
+
+    #[test]
+    fn plain_text_comments_can_start_with_a_bracket() {
+        let csv = "title,comments\nFirst,\"[WIP] still broken on mobile\nTwo\"\n";
+
+        let rows = read_csv(csv.as_bytes()).unwrap();
+
+        let comments: Vec<String> = rows[0]
+            .clone()
+            .unwrap()
+            .comments
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        assert_eq!(comments, vec!["[WIP] still broken on mobile", "Two"]);
+    }
+
+    #[test]
+    fn a_title_column_is_required() {
+        let result = read_csv("name,description\nFirst,Something\n".as_bytes());
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,284 @@
+use crate::error::JiraError;
+use crate::models::{Status, Ticket};
+use std::io::Write;
+
+const TITLE: &str = "IronJira report";
+
+/// Inline style of the HTML report: it must render the same when attached to an email,
+/// without fetching anything.
+const STYLE: &str = "body { font-family: -apple-system, 'Segoe UI', Helvetica, Arial, sans-serif; \
+max-width: 50em; margin: 2em auto; padding: 0 1em; color: #172b4d; line-height: 1.5; }
+h1 { border-bottom: 2px solid #dfe1e6; padding-bottom: .3em; }
+h2 { margin-top: 2em; color: #0052cc; }
+h3 { margin-bottom: .2em; }
+nav ul { list-style: none; padding-left: 1em; }
+.meta { color: #5e6c84; font-size: .9em; margin: 0; }
+.description { white-space: pre-wrap; }
+.comments { border-left: 3px solid #dfe1e6; padding-left: 1em; }
+.comments li { white-space: pre-wrap; }
+.empty { color: #5e6c84; font-style: italic; }";
+
+/// Write the tickets as a Markdown document: a table of contents, then a section for
+/// each [Status](Status) with the description and comments of its tickets.
+///
+/// Tickets keep the order they are given in within their status.
+pub fn export_markdown(tickets: &[&Ticket], mut output: impl Write) -> Result<(), JiraError> {
+    let mut document = format!("# {}\n\n## Contents\n\n", TITLE);
+    for (status, tickets) in by_status(tickets) {
+        document += &format!(
+            "- [{}](#{})\n",
+            section_title(status, &tickets),
+            anchor(status)
+        );
+        for ticket in tickets {
+            document += &format!(
+                "  - [#{} {}](#ticket-{})\n",
+                ticket.id,
+                escape_markdown(&ticket.title.to_string()),
+                ticket.id
+            );
+        }
+    }
+
+    for (status, tickets) in by_status(tickets) {
+        document += &format!(
+            "\n<a id=\"{}\"></a>\n\n## {}\n",
+            anchor(status),
+            section_title(status, &tickets)
+        );
+        if tickets.is_empty() {
+            document += "\n_No tickets._\n";
+        }
+        for ticket in tickets {
+            document += &format!(
+                "\n<a id=\"ticket-{}\"></a>\n\n### #{} {}\n\n",
+                ticket.id,
+                ticket.id,
+                escape_markdown(&ticket.title.to_string())
+            );
+            if let Some(meta) = meta(ticket) {
+                document += &format!("_{}_\n\n", escape_markdown(&meta));
+            }
+            if !ticket.description.trim().is_empty() {
+                document += &format!("{}\n\n", ticket.description.trim());
+            }
+            if ticket.comments.is_empty() {
+                document += "_No comments._\n";
+            } else {
+                document += "**Comments**\n\n";
+                for comment in &ticket.comments {
+                    // Indent the following lines so that a comment spanning several lines
+                    // stays in its list item.
+                    let comment = comment.to_string().trim().replace('\n', "\n  ");
+                    document += &format!("- {}\n", comment);
+                }
+            }
+        }
+    }
+    output
+        .write_all(document.as_bytes())
+        .map_err(|e| JiraError::io("Failed to write the Markdown report", e))
+}
+
+/// Write the tickets as a standalone HTML page, with the same content as
+/// [export_markdown](export_markdown). The style is inlined, the page loads no other file.
+pub fn export_html(tickets: &[&Ticket], mut output: impl Write) -> Result<(), JiraError> {
+    let mut document = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{}</title>\n<style>\n{}\n</style>\n</head>\n<body>\n<h1>{}</h1>\n",
+        TITLE, STYLE, TITLE
+    );
+
+    document += "<nav>\n<h2>Contents</h2>\n<ul>\n";
+    for (status, tickets) in by_status(tickets) {
+        document += &format!(
+            "<li><a href=\"#{}\">{}</a>",
+            anchor(status),
+            section_title(status, &tickets)
+        );
+        if !tickets.is_empty() {
+            document += "\n<ul>\n";
+            for ticket in tickets {
+                document += &format!(
+                    "<li><a href=\"#ticket-{}\">#{} {}</a></li>\n",
+                    ticket.id,
+                    ticket.id,
+                    escape_html(&ticket.title.to_string())
+                );
+            }
+            document += "</ul>\n";
+        }
+        document += "</li>\n";
+    }
+    document += "</ul>\n</nav>\n";
+
+    for (status, tickets) in by_status(tickets) {
+        document += &format!(
+            "<section>\n<h2 id=\"{}\">{}</h2>\n",
+            anchor(status),
+            section_title(status, &tickets)
+        );
+        if tickets.is_empty() {
+            document += "<p class=\"empty\">No tickets.</p>\n";
+        }
+        for ticket in tickets {
+            document += &format!(
+                "<article>\n<h3 id=\"ticket-{}\">#{} {}</h3>\n",
+                ticket.id,
+                ticket.id,
+                escape_html(&ticket.title.to_string())
+            );
+            if let Some(meta) = meta(ticket) {
+                document += &format!("<p class=\"meta\">{}</p>\n", escape_html(&meta));
+            }
+            if !ticket.description.trim().is_empty() {
+                document += &format!(
+                    "<p class=\"description\">{}</p>\n",
+                    escape_html(ticket.description.trim())
+                );
+            }
+            if ticket.comments.is_empty() {
+                document += "<p class=\"empty\">No comments.</p>\n";
+            } else {
+                document += "<ul class=\"comments\">\n";
+                for comment in &ticket.comments {
+                    document += &format!("<li>{}</li>\n", escape_html(&comment.to_string()));
+                }
+                document += "</ul>\n";
+            }
+            document += "</article>\n";
+        }
+        document += "</section>\n";
+    }
+    document += "</body>\n</html>\n";
+    output
+        .write_all(document.as_bytes())
+        .map_err(|e| JiraError::io("Failed to write the HTML report", e))
+}
+
+/// The tickets of each status, in the order statuses appear on the board.
+fn by_status<'a>(tickets: &[&'a Ticket]) -> Vec<(Status, Vec<&'a Ticket>)> {
+    Status::all()
+        .iter()
+        .map(|status| {
+            let in_status = tickets
+                .iter()
+                .filter(|t| t.status == *status)
+                .copied()
+                .collect();
+            (*status, in_status)
+        })
+        .collect()
+}
+
+fn section_title(status: Status, tickets: &[&Ticket]) -> String {
+    format!("{:?} ({})", status, tickets.len())
+}
+
+fn anchor(status: Status) -> String {
+    format!("status-{:?}", status).to_lowercase()
+}
+
+/// The details shown under the title of a ticket, if it has any.
+fn meta(ticket: &Ticket) -> Option<String> {
+    let details: Vec<String> = ticket
+        .created_at
+        .map(|at| format!("Created on {}", at.format("%Y-%m-%d")))
+        .into_iter()
+        .chain(
+            ticket
+                .external_key
+                .iter()
+                .map(|key| format!("Imported from {}", key)),
+        )
+        .collect();
+    if details.is_empty() {
+        None
+    } else {
+        Some(details.join(" · "))
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Escape the characters that would turn a title into a link or emphasis.
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\`*_[]<>#".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::{Comment, Status, TicketDraft, Title};
+    use crate::store::TicketStore;
+    use crate::transfer::{export_html, export_markdown, sorted_tickets};
+
+    fn ticket_store() -> TicketStore {
+        let mut ticket_store = TicketStore::new();
+        let login = ticket_store.create(TicketDraft {
+            title: Title::new("Fix <login>".to_string()).unwrap(),
+            description: "It is broken".to_string(),
+        });
+        ticket_store.force_ticket_status(login, Status::Blocked);
+        let comment = Comment::new("Waiting on IT & ops".to_string()).unwrap();
+        ticket_store.add_comment_to_ticket(login, comment);
+        ticket_store.create(TicketDraft {
+            title: Title::new("Write docs".to_string()).unwrap(),
+            description: String::new(),
+        });
+        ticket_store
+    }
+
+    #[test]
+    fn markdown_groups_tickets_by_status_after_a_table_of_contents() {
+        // arrange
+        let ticket_store = ticket_store();
+        let mut output = Vec::new();
+
+        // act
+        export_markdown(&sorted_tickets(&ticket_store), &mut output).unwrap();
+
+        // assert
+        let markdown = String::from_utf8(output).unwrap();
+        let position = |text: &str| markdown.find(text).unwrap();
+        assert!(position("## Contents") < position("## ToDo (1)"));
+        assert!(position("## ToDo (1)") < position("### #2 Write docs"));
+        assert!(position("### #2 Write docs") < position("## Blocked (1)"));
+        assert!(position("## Blocked (1)") < position("### #1 Fix \\<login\\>"));
+        assert!(markdown.contains("- [#1 Fix \\<login\\>](#ticket-1)"));
+        assert!(markdown.contains("It is broken"));
+        assert!(markdown.contains("- Waiting on IT & ops"));
+        assert!(markdown.contains("## Done (0)\n\n_No tickets._"));
+    }
+
+    #[test]
+    fn html_is_escaped_and_self_contained() {
+        // arrange
+        let ticket_store = ticket_store();
+        let mut output = Vec::new();
+
+        // act
+        export_html(&sorted_tickets(&ticket_store), &mut output).unwrap();
+
+        // assert
+        let html = String::from_utf8(output).unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<style>"));
+        assert!(html.contains("<a href=\"#ticket-1\">#1 Fix &lt;login&gt;</a>"));
+        assert!(html.contains("<h3 id=\"ticket-1\">#1 Fix &lt;login&gt;</h3>"));
+        assert!(html.contains("<li>Waiting on IT &amp; ops</li>"));
+        assert!(!html.contains("<link") && !html.contains("<script") && !html.contains("src="));
+    }
+}
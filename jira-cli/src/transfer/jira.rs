@@ -0,0 +1,447 @@
+use crate::error::JiraError;
+use crate::models::{Comment, Status, TicketDraft, Title};
+use crate::transfer::{ImportedTicket, RowError};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::{BufReader, Read};
+
+/// How the statuses of the default Jira workflows map onto [Status]es.
+const DEFAULT_STATUS_MAPPING: [(&str, Status); 12] = [
+    ("to do", Status::ToDo),
+    ("open", Status::ToDo),
+    ("backlog", Status::ToDo),
+    ("selected for development", Status::ToDo),
+    ("reopened", Status::ToDo),
+    ("in progress", Status::InProgress),
+    ("in review", Status::InProgress),
+    ("blocked", Status::Blocked),
+    ("on hold", Status::Blocked),
+    ("done", Status::Done),
+    ("closed", Status::Done),
+    ("resolved", Status::Done),
+];
+
+/// Turns the name of a Jira status into a [Status].
+///
+/// The statuses of the default Jira workflows are known out of the box; custom statuses, or
+/// different choices, come from the `jira.status_mapping` section of the configuration file.
+/// Names are matched ignoring case.
+#[derive(PartialEq, Debug, Clone)]
+pub struct StatusMapping {
+    mapping: BTreeMap<String, Status>,
+}
+
+impl StatusMapping {
+    /// The default mapping, overridden by `custom`.
+    pub fn new(custom: &BTreeMap<String, Status>) -> Self {
+        let defaults = DEFAULT_STATUS_MAPPING
+            .iter()
+            .map(|(name, status)| (name.to_string(), *status));
+        let custom = custom
+            .iter()
+            .map(|(name, status)| (name.to_lowercase(), *status));
+        Self {
+            mapping: defaults.chain(custom).collect(),
+        }
+    }
+
+    /// The [Status] a Jira status maps to, if it is known.
+    pub fn get(&self, jira_status: &str) -> Option<Status> {
+        self.mapping
+            .get(&jira_status.trim().to_lowercase())
+            .copied()
+    }
+}
+
+/// The fields of an issue we care about, whichever export they come from.
+#[derive(Default)]
+struct RawIssue {
+    key: String,
+    summary: String,
+    description: String,
+    status: String,
+    comments: Vec<String>,
+}
+
+impl RawIssue {
+    fn into_ticket(
+        self,
+        position: usize,
+        mapping: &StatusMapping,
+    ) -> Result<ImportedTicket, RowError> {
+        let location = if self.key.is_empty() {
+            format!("Issue #{}", position + 1)
+        } else {
+            format!("Issue {}", self.key)
+        };
+        let row_error = |details: String| RowError {
+            location: location.clone(),
+            details,
+        };
+        if self.key.is_empty() {
+            return Err(row_error("The issue has no key.".to_string()));
+        }
+        let title = Title::new(self.summary).map_err(|e| row_error(e.to_string()))?;
+        let status = match self.status.trim() {
+            "" => Status::ToDo,
+            name => mapping.get(name).ok_or_else(|| {
+                row_error(format!(
+                    "Unknown Jira status {:?}. Map it to a status in the jira.status_mapping \
+                     section of the configuration file.",
+                    name
+                ))
+            })?,
+        };
+        let comments = self
+            .comments
+            .into_iter()
+            .filter(|body| !body.trim().is_empty())
+            .map(Comment::new)
+            .collect::<Result<Vec<Comment>, _>>()
+            .map_err(|e| row_error(e.to_string()))?;
+        Ok(ImportedTicket {
+            draft: TicketDraft {
+                title,
+                description: self.description,
+            },
+            status,
+            comments,
+            external_key: Some(self.key),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct JsonIssue {
+    #[serde(default)]
+    key: String,
+    fields: JsonFields,
+}
+
+#[derive(Deserialize)]
+struct JsonFields {
+    #[serde(default)]
+    summary: String,
+    #[serde(default)]
+    description: Value,
+    status: Option<JsonStatus>,
+    comment: Option<JsonComments>,
+}
+
+#[derive(Deserialize)]
+struct JsonStatus {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct JsonComments {
+    comments: Vec<JsonComment>,
+}
+
+#[derive(Deserialize)]
+struct JsonComment {
+    body: Value,
+}
+
+/// Read the issues of a response of the Jira REST search endpoint (`/rest/api/2/search`),
+/// saved to a file. A bare array of issues is accepted as well.
+///
+/// Every issue is validated on its own: the result holds either the ticket or why the issue is invalid.
+pub fn read_jira_json(
+    input: impl Read,
+    mapping: &StatusMapping,
+) -> Result<Vec<Result<ImportedTicket, RowError>>, JiraError> {
+    let document: Value = serde_json::from_reader(input)
+        .map_err(|e| JiraError::parse("Failed to parse the Jira JSON export", e))?;
+    let issues = match document {
+        Value::Array(issues) => issues,
+        Value::Object(mut search) => match search.remove("issues") {
+            Some(Value::Array(issues)) => issues,
+            _ => {
+                return Err(JiraError::Parse(
+                    "The Jira JSON export has no list of issues.".to_string(),
+                ))
+            }
+        },
+        _ => {
+            return Err(JiraError::Parse(
+                "The Jira JSON export has no list of issues.".to_string(),
+            ))
+        }
+    };
+    let tickets = issues
+        .into_iter()
+        .enumerate()
+        .map(|(position, issue)| {
+            let issue: JsonIssue = serde_json::from_value(issue).map_err(|e| RowError {
+                location: format!("Issue #{}", position + 1),
+                details: e.to_string(),
+            })?;
+            let raw = RawIssue {
+                key: issue.key,
+                summary: issue.fields.summary,
+                description: plain_text(&issue.fields.description),
+                status: issue.fields.status.map(|s| s.name).unwrap_or_default(),
+                comments: issue
+                    .fields
+                    .comment
+                    .map(|c| c.comments.iter().map(|c| plain_text(&c.body)).collect())
+                    .unwrap_or_default(),
+            };
+            raw.into_ticket(position, mapping)
+        })
+        .collect();
+    Ok(tickets)
+}
+
+/// Read the issues of a Jira XML (RSS) export.
+///
+/// Every issue is validated on its own: the result holds either the ticket or why the issue is invalid.
+pub fn read_jira_xml(
+    input: impl Read,
+    mapping: &StatusMapping,
+) -> Result<Vec<Result<ImportedTicket, RowError>>, JiraError> {
+    let mut reader = Reader::from_reader(BufReader::new(input));
+    reader.trim_text(true);
+    let parse_error =
+        |e: quick_xml::Error| JiraError::parse("Failed to parse the Jira XML export", e);
+
+    let mut tickets = Vec::new();
+    let mut elements: Vec<String> = Vec::new();
+    let mut issue: Option<RawIssue> = None;
+    let mut buffer = Vec::new();
+    loop {
+        let text = match reader.read_event_into(&mut buffer).map_err(parse_error)? {
+            Event::Start(element) => {
+                let name = String::from_utf8_lossy(element.name().as_ref()).into_owned();
+                match (name.as_str(), issue.as_mut()) {
+                    ("item", _) => issue = Some(RawIssue::default()),
+                    ("comment", Some(issue)) => issue.comments.push(String::new()),
+                    _ => {}
+                }
+                elements.push(name);
+                None
+            }
+            Event::End(_) => {
+                if elements.pop().as_deref() == Some("item") {
+                    if let Some(issue) = issue.take() {
+                        tickets.push(issue.into_ticket(tickets.len(), mapping));
+                    }
+                }
+                None
+            }
+            Event::Text(text) => Some(text.unescape().map_err(parse_error)?.into_owned()),
+            Event::CData(text) => Some(String::from_utf8_lossy(&text.into_inner()).into_owned()),
+            Event::Eof => break,
+            _ => None,
+        };
+
+        if let (Some(text), Some(issue)) = (text, issue.as_mut()) {
+            // Only the direct children of an item describe the issue.
+            let parent = elements.len().checked_sub(2).map(|i| elements[i].as_str());
+            let field = match (parent, elements.last().map(String::as_str)) {
+                (Some("item"), Some("key")) => Some(&mut issue.key),
+                (Some("item"), Some("summary")) => Some(&mut issue.summary),
+                (Some("item"), Some("description")) => Some(&mut issue.description),
+                (Some("item"), Some("status")) => Some(&mut issue.status),
+                (Some("comments"), Some("comment")) => issue.comments.last_mut(),
+                _ => None,
+            };
+            if let Some(field) = field {
+                field.push_str(&strip_tags(&text));
+            }
+        }
+        buffer.clear();
+    }
+    Ok(tickets)
+}
+
+/// The text of a field that is either a string (REST API v2) or an Atlassian Document (v3).
+fn plain_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Object(node) => {
+            let own = node.get("text").and_then(Value::as_str).unwrap_or("");
+            let children = node
+                .get("content")
+                .and_then(Value::as_array)
+                .map(|children| {
+                    let texts: Vec<String> = children.iter().map(plain_text).collect();
+                    let separator = if node.get("type").and_then(Value::as_str) == Some("doc") {
+                        "\n"
+                    } else {
+                        ""
+                    };
+                    texts.join(separator)
+                })
+                .unwrap_or_default();
+            format!("{}{}", own, children)
+        }
+        _ => String::new(),
+    }
+}
+
+/// Remove the HTML markup the XML export wraps descriptions and comments in.
+fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::Status;
+    use crate::store::TicketStore;
+    use crate::transfer::{import, read_jira_json, read_jira_xml, StatusMapping};
+    use std::collections::BTreeMap;
+
+    const JSON: &str = r#"{
+        "total": 2,
+        "issues": [
+            {
+                "key": "PROJ-1",
+                "fields": {
+                    "summary": "Fix login",
+                    "description": "It is broken",
+                    "status": { "name": "In Review" },
+                    "comment": { "comments": [ { "body": "On it" } ] }
+                }
+            },
+            {
+                "key": "PROJ-2",
+                "fields": { "summary": "", "status": { "name": "Done" } }
+            }
+        ]
+    }"#;
+
+    const XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="0.92">
+  <channel>
+    <title>Jira</title>
+    <item>
+      <title>[PROJ-1] Fix login</title>
+      <key id="10001">PROJ-1</key>
+      <summary>Fix login</summary>
+      <description>&lt;p&gt;It is broken&lt;/p&gt;</description>
+      <status id="3">Waiting for QA</status>
+      <comments>
+        <comment id="1" author="alice">&lt;p&gt;On it&lt;/p&gt;</comment>
+      </comments>
+    </item>
+  </channel>
+</rss>"#;
+
+    #[test]
+    fn issues_are_read_from_the_json_export() {
+        // arrange
+        let mapping = StatusMapping::new(&BTreeMap::new());
+
+        // act
+        let rows = read_jira_json(JSON.as_bytes(), &mapping).unwrap();
+
+        // assert
+        let ticket = rows[0].clone().unwrap();
+        assert_eq!(ticket.external_key.as_deref(), Some("PROJ-1"));
+        assert_eq!(ticket.draft.description, "It is broken");
+        assert_eq!(ticket.status, Status::InProgress);
+        assert_eq!(ticket.comments[0].to_string(), "On it");
+        assert_eq!(rows[1].clone().unwrap_err().location, "Issue PROJ-2");
+    }
+
+    #[test]
+    fn issues_are_read_from_the_xml_export_with_a_custom_status_mapping() {
+        // arrange
+        let mut custom = BTreeMap::new();
+        custom.insert("Waiting for QA".to_string(), Status::Blocked);
+        let mapping = StatusMapping::new(&custom);
+
+        // act
+        let rows = read_jira_xml(XML.as_bytes(), &mapping).unwrap();
+
+        // assert
+        let ticket = rows[0].clone().unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(ticket.external_key.as_deref(), Some("PROJ-1"));
+        assert_eq!(ticket.draft.title.to_string(), "Fix login");
+        assert_eq!(ticket.draft.description, "It is broken");
+        assert_eq!(ticket.status, Status::Blocked);
+        assert_eq!(ticket.comments[0].to_string(), "On it");
+    }
+
+    #[test]
+    fn an_unknown_status_is_reported() {
+        let mapping = StatusMapping::new(&BTreeMap::new());
+
+        let rows = read_jira_xml(XML.as_bytes(), &mapping).unwrap();
+
+        assert!(rows[0].is_err());
+    }
+
+    #[test]
+    fn importing_the_same_export_again_only_adds_new_comments() {
+        // arrange
+        let mapping = StatusMapping::new(&BTreeMap::new());
+        let mut ticket_store = TicketStore::new();
+        import(
+            &mut ticket_store,
+            read_jira_json(JSON.as_bytes(), &mapping).unwrap(),
+            false,
+            false,
+        );
+        let id = ticket_store.find_by_external_key("PROJ-1").unwrap();
+        ticket_store.force_ticket_status(id, Status::Done);
+        let json = JSON.replace(r#""body": "On it""#, r#""body": "Fixed""#);
+
+        // act
+        let unchanged = import(
+            &mut ticket_store,
+            read_jira_json(JSON.as_bytes(), &mapping).unwrap(),
+            false,
+            false,
+        );
+        let commented = import(
+            &mut ticket_store,
+            read_jira_json(json.as_bytes(), &mapping).unwrap(),
+            false,
+            false,
+        );
+
+        // assert
+        let ticket = ticket_store.get(id).unwrap();
+        assert_eq!((unchanged.created, unchanged.updated), (0, 0));
+        assert_eq!((commented.created, commented.updated), (0, 1));
+        assert_eq!(ticket_store.list().len(), 1);
+        assert_eq!(ticket.status, Status::Done);
+        let comments: Vec<String> = ticket.comments.iter().map(|c| c.to_string()).collect();
+        assert_eq!(comments, vec!["On it", "Fixed"]);
+    }
+
+    #[test]
+    fn importing_again_with_overwrite_replaces_the_tickets() {
+        // arrange
+        let mapping = StatusMapping::new(&BTreeMap::new());
+        let mut ticket_store = TicketStore::new();
+        let rows = || read_jira_json(JSON.as_bytes(), &mapping).unwrap();
+        import(&mut ticket_store, rows(), false, false);
+        let id = ticket_store.find_by_external_key("PROJ-1").unwrap();
+        ticket_store.force_ticket_status(id, Status::Done);
+
+        // act
+        let report = import(&mut ticket_store, rows(), false, true);
+
+        // assert
+        assert_eq!(report.updated, 1);
+        assert_eq!(ticket_store.get(id).unwrap().status, Status::InProgress);
+    }
+}
@@ -0,0 +1,127 @@
+use crate::models::{Comment, Status, Ticket, TicketDraft};
+use crate::store::{ImportOutcome, TicketStore};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+mod csv;
+mod document;
+mod jira;
+
+pub use self::csv::*;
+pub use document::*;
+pub use jira::*;
+
+/// The file formats tickets can be imported from or exported to.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Format {
+    Csv,
+    /// The JSON returned by the search endpoint of the Jira REST API.
+    JiraJson,
+    /// The XML (RSS) issue export of Jira.
+    JiraXml,
+    /// A report grouping tickets by status, with a table of contents.
+    Markdown,
+    /// The same report as [Markdown](Format::Markdown), as a standalone HTML page.
+    Html,
+}
+
+impl FromStr for Format {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(Format::Csv),
+            "jira-json" => Ok(Format::JiraJson),
+            "jira-xml" => Ok(Format::JiraXml),
+            "markdown" | "md" => Ok(Format::Markdown),
+            "html" => Ok(Format::Html),
+            _ => Err("The format you specified is not valid. \
+                Valid values: csv, jira-json, jira-xml, markdown and html."
+                .into()),
+        }
+    }
+}
+
+/// A ticket read from a file, validated but not yet added to the store.
+#[derive(PartialEq, Debug, Clone)]
+pub struct ImportedTicket {
+    pub draft: TicketDraft,
+    pub status: Status,
+    pub comments: Vec<Comment>,
+    /// Key of the ticket in the tool it comes from, used to update it on the next import.
+    pub external_key: Option<String>,
+}
+
+/// Why a row of an imported file was skipped.
+#[derive(PartialEq, Debug, Clone)]
+pub struct RowError {
+    /// Where the row is in the file, e.g. `Line 3` or `Issue PROJ-42`.
+    pub location: String,
+    pub details: String,
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.details)
+    }
+}
+
+/// The outcome of an import.
+#[derive(PartialEq, Debug, Default)]
+pub struct ImportReport {
+    /// Tickets that were, or would be in a dry run, added to the store.
+    pub created: usize,
+    /// Tickets imported before that were, or would be in a dry run, updated.
+    /// Those without anything new are not counted.
+    pub updated: usize,
+    pub errors: Vec<RowError>,
+}
+
+/// Add every valid row to the store, or only count them if `dry_run` is set.
+/// Invalid rows are skipped and reported, they do not stop the import.
+///
+/// Tickets imported before only get their new comments, unless `overwrite` is set: see
+/// [TicketStore::import].
+pub fn import(
+    ticket_store: &mut TicketStore,
+    rows: Vec<Result<ImportedTicket, RowError>>,
+    dry_run: bool,
+    overwrite: bool,
+) -> ImportReport {
+    // A dry run imports into a copy of the store, thrown away afterwards.
+    let mut copy;
+    let ticket_store = if dry_run {
+        copy = ticket_store.clone();
+        &mut copy
+    } else {
+        ticket_store
+    };
+    let mut report = ImportReport::default();
+    for row in rows {
+        match row {
+            Ok(ImportedTicket {
+                draft,
+                status,
+                comments,
+                external_key,
+            }) => match ticket_store
+                .import(draft, status, comments, external_key, overwrite)
+                .1
+            {
+                ImportOutcome::Created => report.created += 1,
+                ImportOutcome::Updated => report.updated += 1,
+                ImportOutcome::Unchanged => {}
+            },
+            Err(e) => report.errors.push(e),
+        }
+    }
+    report
+}
+
+/// The tickets of the store, in the order of their ids.
+pub fn sorted_tickets(ticket_store: &TicketStore) -> Vec<&Ticket> {
+    let mut tickets = ticket_store.list();
+    tickets.sort_by_key(|t| t.id);
+    tickets
+}
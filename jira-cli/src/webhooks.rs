@@ -0,0 +1,450 @@
+use crate::config::{WebhookEndpoint, WebhooksConfig};
+use crate::error::JiraError;
+use crate::models::TicketEvent;
+use crate::persistence::write_atomically;
+use crate::server::TicketResource;
+use crate::store::TicketStore;
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::convert::TryFrom;
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+
+/// File of the store directory holding the deliveries still to be made.
+pub const WEBHOOK_QUEUE: &str = "webhook_queue.json";
+
+/// Longest wait before retrying a delivery, in seconds: 30 days.
+const MAX_BACKOFF: i64 = 30 * 24 * 60 * 60;
+
+/// Header holding the name of the event, e.g. `ticket.moved`.
+pub const EVENT_HEADER: &str = "X-IronJira-Event";
+
+/// Header holding the id of the delivery, the same for every attempt.
+pub const DELIVERY_HEADER: &str = "X-IronJira-Delivery";
+
+/// Header holding the signature of the payload, e.g. `sha256=5d3f…`, for endpoints with a secret.
+pub const SIGNATURE_HEADER: &str = "X-IronJira-Signature";
+
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+/// An event waiting to be sent to a webhook.
+pub struct Delivery {
+    /// Sent along with every attempt, for receivers to ignore the deliveries they already got.
+    pub id: String,
+    pub url: String,
+    /// Name of the event, e.g. `ticket.moved`.
+    pub event: String,
+    /// The JSON payload, exactly as it is sent and signed.
+    pub payload: String,
+    /// Number of failed attempts so far.
+    pub attempts: u32,
+    pub next_attempt_at: DateTime<Utc>,
+    /// Why the last attempt failed.
+    pub last_error: Option<String>,
+}
+
+/// An event ready to be queued, with its payload built while the store is at hand.
+#[derive(PartialEq, Debug, Clone)]
+pub struct PreparedEvent {
+    /// Name of the event, e.g. `ticket.moved`.
+    pub name: &'static str,
+    /// The JSON payload, with the ticket as it was when the event was taken.
+    pub payload: String,
+}
+
+/// What happened to the deliveries attempted by [deliver](DeliveryQueue::deliver).
+#[derive(PartialEq, Debug, Default)]
+pub struct DeliveryReport {
+    pub delivered: usize,
+    /// Deliveries that failed and will be retried later.
+    pub retried: usize,
+    /// Deliveries given up after too many attempts.
+    pub abandoned: Vec<Delivery>,
+}
+
+impl DeliveryReport {
+    /// The failures worth telling the user about, if any.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings: Vec<String> = self
+            .abandoned
+            .iter()
+            .map(|delivery| {
+                format!(
+                    "Gave up sending {} to {} after {} attempt(s): {}",
+                    delivery.event,
+                    delivery.url,
+                    delivery.attempts,
+                    delivery.last_error.as_deref().unwrap_or("unknown error")
+                )
+            })
+            .collect();
+        if self.retried > 0 {
+            warnings.push(format!(
+                "Failed to send {} webhook event(s), they will be retried later. See `jira-cli webhooks list`.",
+                self.retried
+            ));
+        }
+        warnings
+    }
+}
+
+/// The webhook deliveries still to be made, saved to a file after every change so that
+/// none is lost between two runs of the CLI.
+///
+/// Failed deliveries are retried with an exponential backoff: the first retry happens after
+/// [retry_delay](WebhooksConfig::retry_delay) seconds, the next one twice as late, and so on
+/// until [max_attempts](WebhooksConfig::max_attempts) is reached.
+pub struct DeliveryQueue {
+    path: PathBuf,
+    config: WebhooksConfig,
+    deliveries: Vec<Delivery>,
+}
+
+impl DeliveryQueue {
+    /// Open the queue saved at `path`. It is empty if nothing was saved yet.
+    pub fn open(path: PathBuf, config: WebhooksConfig) -> Result<Self, JiraError> {
+        let deliveries = match std::fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data).map_err(|e| {
+                JiraError::parse(&format!("Failed to parse the webhook queue {:?}", path), e)
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                return Err(JiraError::io(
+                    &format!("Failed to read the webhook queue {:?}", path),
+                    e,
+                ))
+            }
+        };
+        Ok(Self {
+            path,
+            config,
+            deliveries,
+        })
+    }
+
+    /// The deliveries still to be made, oldest first.
+    pub fn deliveries(&self) -> &[Delivery] {
+        &self.deliveries
+    }
+
+    /// Queue a delivery of every event to each endpoint interested in it.
+    /// Returns the number of deliveries queued.
+    pub fn enqueue(&mut self, events: &[PreparedEvent], now: DateTime<Utc>) -> usize {
+        let mut queued = 0;
+        for event in events {
+            let endpoints = self.config.endpoints.iter().filter(|endpoint| {
+                endpoint.events.is_empty() || endpoint.events.iter().any(|name| name == event.name)
+            });
+            for endpoint in endpoints {
+                self.deliveries.push(Delivery {
+                    id: format!(
+                        "{}{:09}-{}",
+                        now.timestamp(),
+                        now.timestamp_subsec_nanos(),
+                        queued
+                    ),
+                    url: endpoint.url.clone(),
+                    event: event.name.to_string(),
+                    payload: event.payload.clone(),
+                    attempts: 0,
+                    next_attempt_at: now,
+                    last_error: None,
+                });
+                queued += 1;
+            }
+        }
+        queued
+    }
+
+    /// Send the deliveries that are due at `now`, or every delivery if `all` is set.
+    /// Failed deliveries are scheduled for a retry, or given up after too many attempts.
+    pub fn deliver(&mut self, now: DateTime<Utc>, all: bool) -> DeliveryReport {
+        let mut report = DeliveryReport::default();
+        let agent = ureq::AgentBuilder::new()
+            .timeout(std::time::Duration::from_secs(self.config.timeout))
+            .build();
+        let mut remaining = Vec::new();
+        for mut delivery in std::mem::take(&mut self.deliveries) {
+            if !all && delivery.next_attempt_at > now {
+                remaining.push(delivery);
+                continue;
+            }
+            let endpoint = self.config.endpoints.iter().find(|e| e.url == delivery.url);
+            let result = match endpoint {
+                Some(endpoint) => send(&agent, endpoint, &delivery),
+                None => Err("the endpoint is no longer configured".to_string()),
+            };
+            delivery.attempts += 1;
+            match result {
+                Ok(()) => report.delivered += 1,
+                Err(e) if endpoint.is_some() && delivery.attempts < self.config.max_attempts => {
+                    delivery.next_attempt_at = now + self.backoff(delivery.attempts);
+                    delivery.last_error = Some(e);
+                    report.retried += 1;
+                    remaining.push(delivery);
+                }
+                Err(e) => {
+                    delivery.last_error = Some(e);
+                    report.abandoned.push(delivery);
+                }
+            }
+        }
+        self.deliveries = remaining;
+        report
+    }
+
+    /// Save the queue to its file.
+    pub fn save(&self) -> Result<(), JiraError> {
+        // Deliveries always serialise to JSON.
+        let data = serde_json::to_vec_pretty(&self.deliveries).unwrap_or_default();
+        write_atomically(&self.path, &data).map_err(|e| {
+            JiraError::io(
+                &format!("Failed to save the webhook queue {:?}", self.path),
+                e,
+            )
+        })
+    }
+
+    /// How long to wait after the given number of failed attempts.
+    /// Never longer than [MAX_BACKOFF](MAX_BACKOFF), whatever the configured delay.
+    fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 1u64 << attempts.saturating_sub(1).min(20);
+        let seconds = self.config.retry_delay.saturating_mul(factor);
+        Duration::seconds(i64::try_from(seconds).unwrap_or(i64::MAX).min(MAX_BACKOFF))
+    }
+}
+
+/// Queue the events recorded by the store since the last call, then send every delivery
+/// that is due. The queue is only saved if it changed.
+pub fn notify(
+    queue: &mut DeliveryQueue,
+    ticket_store: &mut TicketStore,
+) -> Result<DeliveryReport, JiraError> {
+    let now = Utc::now();
+    let queued = queue.enqueue(&prepare(ticket_store), now);
+    deliver_due(queue, queued, now)
+}
+
+/// Queue the events received from `events` and send the deliveries as they become due,
+/// until the sender is dropped. Meant to run on a thread of its own, so that slow receivers
+/// never hold up the one recording the events. Failures are reported on standard error.
+pub fn deliver_in_background(queue: &mut DeliveryQueue, events: Receiver<Vec<PreparedEvent>>) {
+    loop {
+        // Wake up regularly to retry the failed deliveries, even without new events.
+        let queued = match events.recv_timeout(std::time::Duration::from_secs(1)) {
+            Ok(events) => queue.enqueue(&events, Utc::now()),
+            Err(RecvTimeoutError::Timeout) => 0,
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+        match deliver_due(queue, queued, Utc::now()) {
+            Ok(report) => report
+                .warnings()
+                .iter()
+                .for_each(|warning| eprintln!("Warning: {}", warning)),
+            Err(e) => eprintln!("Failed to send webhook events: {}", e),
+        }
+    }
+}
+
+/// Take the events recorded by the store since the last call, with their payloads.
+pub fn prepare(ticket_store: &mut TicketStore) -> Vec<PreparedEvent> {
+    ticket_store
+        .take_events()
+        .iter()
+        .map(|event| PreparedEvent {
+            name: event.name(),
+            payload: payload(event, ticket_store),
+        })
+        .collect()
+}
+
+/// Send every delivery that is due at `now`. The queue is only saved if it changed,
+/// i.e. if something was sent or `queued` deliveries were just added to it.
+fn deliver_due(
+    queue: &mut DeliveryQueue,
+    queued: usize,
+    now: DateTime<Utc>,
+) -> Result<DeliveryReport, JiraError> {
+    if queue.deliveries.iter().all(|d| d.next_attempt_at > now) {
+        // Nothing to send: spare the receivers and the disk.
+        if queued > 0 {
+            queue.save()?;
+        }
+        return Ok(DeliveryReport::default());
+    }
+    let report = queue.deliver(now, false);
+    queue.save()?;
+    Ok(report)
+}
+
+/// The HMAC-SHA256 of `payload` keyed with `secret`, in hexadecimal.
+pub fn signature(secret: &str, payload: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(payload.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// The JSON payload of an event: its fields, along with the ticket as it is now.
+fn payload(event: &TicketEvent, ticket_store: &TicketStore) -> String {
+    // Events and tickets always serialise to JSON.
+    let mut payload = serde_json::to_value(event).unwrap_or_default();
+    if let Some(ticket) = ticket_store.get(event.ticket_id()) {
+        payload["ticket"] = serde_json::to_value(TicketResource::from(ticket)).unwrap_or_default();
+    }
+    payload.to_string()
+}
+
+fn send(
+    agent: &ureq::Agent,
+    endpoint: &WebhookEndpoint,
+    delivery: &Delivery,
+) -> Result<(), String> {
+    let mut request = agent
+        .post(&delivery.url)
+        .set("Content-Type", "application/json")
+        .set(EVENT_HEADER, &delivery.event)
+        .set(DELIVERY_HEADER, &delivery.id);
+    if let Some(secret) = &endpoint.secret {
+        let signature = format!("sha256={}", signature(secret, &delivery.payload));
+        request = request.set(SIGNATURE_HEADER, &signature);
+    }
+    request
+        .send_string(&delivery.payload)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{WebhookEndpoint, WebhooksConfig};
+    use crate::models::{TicketDraft, Title};
+    use crate::store::TicketStore;
+    use crate::webhooks::{signature, DeliveryQueue, SIGNATURE_HEADER, WEBHOOK_QUEUE};
+    use chrono::{Duration, Utc};
+    use std::thread;
+
+    fn config(url: &str) -> WebhooksConfig {
+        WebhooksConfig {
+            endpoints: vec![WebhookEndpoint {
+                url: url.to_string(),
+                secret: Some("s3cr3t".to_string()),
+                events: Vec::new(),
+            }],
+            max_attempts: 3,
+            retry_delay: 60,
+            timeout: 5,
+        }
+    }
+
+    fn ticket_store() -> TicketStore {
+        let mut ticket_store = TicketStore::new();
+        ticket_store.create(TicketDraft {
+            title: Title::new("Fix login".to_string()).unwrap(),
+            description: String::new(),
+        });
+        ticket_store
+    }
+
+    #[test]
+    fn payloads_are_signed_with_hmac_sha256() {
+        assert_eq!(
+            signature("key", "The quick brown fox jumps over the lazy dog"),
+            "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    fn events_are_posted_to_a_local_receiver() {
+        // arrange
+        let dir = tempfile::tempdir().unwrap();
+        let receiver = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", receiver.server_addr().to_ip().unwrap());
+        let received = thread::spawn(move || {
+            let mut request = receiver.recv().unwrap();
+            let mut body = String::new();
+            request.as_reader().read_to_string(&mut body).unwrap();
+            let signature = request
+                .headers()
+                .iter()
+                .find(|h| h.field.equiv(SIGNATURE_HEADER))
+                .map(|h| h.value.to_string());
+            request.respond(tiny_http::Response::empty(204)).unwrap();
+            (body, signature)
+        });
+        let mut queue = DeliveryQueue::open(dir.path().join(WEBHOOK_QUEUE), config(&url)).unwrap();
+        let mut ticket_store = ticket_store();
+
+        // act
+        let report = super::notify(&mut queue, &mut ticket_store).unwrap();
+
+        // assert
+        let (body, header) = received.join().unwrap();
+        let payload: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(payload["event"], "ticket.created");
+        assert_eq!(payload["ticket"]["title"], "Fix login");
+        assert_eq!(
+            header,
+            Some(format!("sha256={}", signature("s3cr3t", &body)))
+        );
+        assert_eq!(report.delivered, 1);
+        assert!(queue.deliveries().is_empty());
+    }
+
+    #[test]
+    fn failed_deliveries_are_retried_with_backoff_then_given_up() {
+        // arrange
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(WEBHOOK_QUEUE);
+        // Nothing listens on the port once the listener is dropped.
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let config = config(&format!("http://127.0.0.1:{}/hook", port));
+        let mut queue = DeliveryQueue::open(path.clone(), config.clone()).unwrap();
+        let now = Utc::now();
+        queue.enqueue(&super::prepare(&mut ticket_store()), now);
+
+        // act
+        let first = queue.deliver(now, false);
+        queue.save().unwrap();
+        let mut queue = DeliveryQueue::open(path, config).unwrap();
+        let too_early = queue.deliver(now + Duration::seconds(59), false);
+        let second = queue.deliver(now + Duration::seconds(60), false);
+        let retry_at = queue.deliveries()[0].next_attempt_at;
+        let third = queue.deliver(retry_at, false);
+
+        // assert
+        assert_eq!(first.retried, 1);
+        assert_eq!(too_early.retried, 0);
+        assert_eq!(second.retried, 1);
+        assert_eq!(retry_at, now + Duration::seconds(60 + 120));
+        assert_eq!(third.abandoned.len(), 1);
+        assert_eq!(third.abandoned[0].attempts, 3);
+        assert!(queue.deliveries().is_empty());
+    }
+
+    #[test]
+    fn retries_are_never_scheduled_further_than_the_longest_backoff() {
+        // arrange
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = config("http://127.0.0.1:1/hook");
+        config.retry_delay = u64::MAX;
+        let mut queue = DeliveryQueue::open(dir.path().join(WEBHOOK_QUEUE), config).unwrap();
+        let now = Utc::now();
+        queue.enqueue(&super::prepare(&mut ticket_store()), now);
+
+        // act
+        let report = queue.deliver(now, false);
+
+        // assert
+        assert_eq!(report.retried, 1);
+        assert_eq!(
+            queue.deliveries()[0].next_attempt_at,
+            now + Duration::seconds(super::MAX_BACKOFF)
+        );
+    }
+}